@@ -0,0 +1,165 @@
+//! Compares evaluating select predicates on raw cbor blocks against parsing
+//! the blocks first and evaluating the parsed version, as happens when the
+//! filter is placed after parse_cbor
+//!
+//! Run with `cargo bench --bench select`
+
+use oura::filters::select::{eval, NoLedger, Predicate};
+use oura::framework::Record;
+use pallas::codec::minicbor;
+use pallas::codec::utils::{KeyValuePairs, MaybeIndefArray, Nullable, Set};
+use pallas::interop::utxorpc::Mapper;
+use pallas::ledger::primitives::conway;
+use pallas::ledger::traverse::MultiEraBlock;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 200;
+
+const ADDRESS: &str = "019493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251";
+
+const PREDICATES: &[&str] = &[
+    r#"{ "match": "addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x" }"#,
+    r##"{ "match": "#127" }"##,
+    r#"{ "match": { "tx": { "outputs": [{ "lovelace": { "gte": 1 } }], "metadata": [{ "label": 127 }] } } }"#,
+];
+
+/// A conway tx that spends a single input into a single output
+fn tx(seed: u8) -> conway::Tx {
+    let output = conway::PostAlonzoTransactionOutput {
+        address: hex::decode(ADDRESS).unwrap().into(),
+        value: conway::Value::Coin(123000000),
+        datum_option: None,
+        script_ref: None,
+    };
+
+    let metadata = match seed % 2 {
+        0 => vec![(127, conway::Metadatum::Text("lorem".into()))],
+        _ => vec![(9980, conway::Metadatum::Text("ipsum".into()))],
+    };
+
+    conway::Tx {
+        transaction_body: conway::TransactionBody {
+            inputs: Set::from(vec![conway::TransactionInput {
+                transaction_id: [seed; 32].into(),
+                index: 0,
+            }]),
+            outputs: vec![conway::TransactionOutput::PostAlonzo(output)],
+            fee: 170000,
+            ttl: None,
+            certificates: None,
+            withdrawals: None,
+            auxiliary_data_hash: None,
+            validity_interval_start: None,
+            mint: None,
+            script_data_hash: None,
+            collateral: None,
+            required_signers: None,
+            network_id: None,
+            collateral_return: None,
+            total_collateral: None,
+            reference_inputs: None,
+            voting_procedures: None,
+            proposal_procedures: None,
+            treasury_value: None,
+            donation: None,
+        },
+        transaction_witness_set: conway::WitnessSet {
+            vkeywitness: None,
+            native_script: None,
+            bootstrap_witness: None,
+            plutus_v1_script: None,
+            plutus_data: None,
+            redeemer: None,
+            plutus_v2_script: None,
+            plutus_v3_script: None,
+        },
+        success: true,
+        auxiliary_data: Nullable::Some(conway::AuxiliaryData::Shelley(metadata.into())),
+    }
+}
+
+/// Encodes the txs as the cbor of a conway block
+fn block(txs: Vec<conway::Tx>) -> Vec<u8> {
+    let header_body = conway::HeaderBody {
+        block_number: 1,
+        slot: 100,
+        prev_hash: None,
+        issuer_vkey: vec![0x11; 32].into(),
+        vrf_vkey: vec![0x22; 32].into(),
+        vrf_result: conway::VrfCert(vec![].into(), vec![].into()),
+        block_body_size: 0,
+        block_body_hash: [0; 32].into(),
+        operational_cert: conway::OperationalCert {
+            operational_cert_hot_vkey: vec![0x33; 32].into(),
+            operational_cert_sequence_number: 0,
+            operational_cert_kes_period: 0,
+            operational_cert_sigma: vec![0x44; 64].into(),
+        },
+        protocol_version: (9, 0),
+    };
+
+    let auxiliary_data_set: Vec<_> = txs
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, tx)| match &tx.auxiliary_data {
+            Nullable::Some(x) => Some((idx as u32, x.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let block = conway::Block {
+        header: conway::Header {
+            header_body,
+            body_signature: vec![0x55; 64].into(),
+        },
+        transaction_bodies: MaybeIndefArray::Def(
+            txs.iter().map(|x| x.transaction_body.clone()).collect(),
+        ),
+        transaction_witness_sets: MaybeIndefArray::Def(
+            txs.iter()
+                .map(|x| x.transaction_witness_set.clone())
+                .collect(),
+        ),
+        auxiliary_data_set: KeyValuePairs::from(auxiliary_data_set),
+        invalid_transactions: None,
+    };
+
+    // blocks are wrapped along with the tag of their era
+    minicbor::to_vec((7u16, block)).unwrap()
+}
+
+fn measure(run: impl Fn()) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..ROUNDS {
+        run();
+    }
+
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    let block = block((0..400).map(|x| tx(x as u8)).collect());
+    let mapper = Mapper::new(NoLedger);
+
+    for x in PREDICATES {
+        let predicate: Predicate = serde_json::from_str(x).unwrap();
+
+        let parsed = Record::ParsedBlock(mapper.map_block(&MultiEraBlock::decode(&block).unwrap()));
+        let record = Record::CborBlock(block.clone());
+        assert_eq!(eval(&parsed, &predicate), eval(&record, &predicate));
+
+        let parsed = measure(|| {
+            let raw = MultiEraBlock::decode(&block).unwrap();
+            let record = Record::ParsedBlock(mapper.map_block(&raw));
+            black_box(eval(&record, &predicate));
+        });
+
+        let raw = measure(|| {
+            black_box(eval(&record, &predicate));
+        });
+
+        println!("{x}\n  parsed: {parsed:?}, raw: {raw:?}");
+    }
+}
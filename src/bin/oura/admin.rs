@@ -0,0 +1,251 @@
+use gasket::runtime::{StagePhase, TetherState};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use oura::pipeline::Pipeline;
+use pallas::network::miniprotocols::Point;
+use serde_json::{json, Map, Value as JsonValue};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+fn reply(status: StatusCode, body: JsonValue) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("response parts are valid")
+}
+
+fn describe_state(state: &TetherState) -> &'static str {
+    match state {
+        TetherState::Dropped => "dropped",
+        TetherState::Blocked(_) => "blocked",
+        TetherState::Alive(StagePhase::Bootstrap) => "bootstrap",
+        TetherState::Alive(StagePhase::Working) => "working",
+        TetherState::Alive(StagePhase::Teardown) => "teardown",
+        TetherState::Alive(StagePhase::Ended) => "ended",
+    }
+}
+
+fn point_to_json(point: &Point) -> JsonValue {
+    match point {
+        Point::Origin => json!({ "slot": 0, "hash": null }),
+        Point::Specific(slot, hash) => json!({ "slot": slot, "hash": hex::encode(hash) }),
+    }
+}
+
+/// Alive until any stage ends while its source is still running
+fn health(pipeline: &Pipeline) -> Response<Body> {
+    match pipeline.has_stopped() {
+        true => reply(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "status": "stopped" }),
+        ),
+        false => reply(StatusCode::OK, json!({ "status": "ok" })),
+    }
+}
+
+/// Ready once every source found the intersection with the chain
+fn ready(pipeline: &Pipeline) -> Response<Body> {
+    match pipeline.is_ready() {
+        true => reply(StatusCode::OK, json!({ "ready": true })),
+        false => reply(StatusCode::SERVICE_UNAVAILABLE, json!({ "ready": false })),
+    }
+}
+
+fn cursor(pipeline: &Pipeline) -> Response<Body> {
+    let mut out = Map::new();
+
+    for name in pipeline.pipeline_names() {
+        let points: Vec<_> = pipeline
+            .breadcrumbs(name)
+            .map(|x| x.points())
+            .unwrap_or_default()
+            .iter()
+            .map(point_to_json)
+            .collect();
+
+        out.insert(name.to_owned(), points.into());
+    }
+
+    reply(StatusCode::OK, out.into())
+}
+
+fn stages(pipeline: &Pipeline) -> Response<Body> {
+    let stages: Vec<_> = pipeline
+        .named_stages()
+        .into_iter()
+        .map(|(name, tether)| {
+            json!({
+                "pipeline": name,
+                "stage": tether.name(),
+                "state": describe_state(&tether.check_state()),
+            })
+        })
+        .collect();
+
+    reply(StatusCode::OK, stages.into())
+}
+
+fn paused(pipeline: &Pipeline) -> JsonValue {
+    let names: Vec<_> = pipeline
+        .pipeline_names()
+        .filter(|x| pipeline.is_paused(x).unwrap_or_default())
+        .collect();
+
+    json!({ "paused": names })
+}
+
+/// The decoded `pipeline` parameter of a query string, if any
+fn pipeline_param(query: Option<&str>) -> Option<String> {
+    form_urlencoded::parse(query?.as_bytes())
+        .find(|(key, _)| key == "pipeline")
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Pauses or resumes the source of the pipeline in the query, or all of them
+fn switch(pipeline: &Pipeline, req: &Request<Body>, pause: bool) -> Response<Body> {
+    let name = pipeline_param(req.uri().query());
+    let name = name.as_deref();
+
+    let found = match pause {
+        true => pipeline.pause(name),
+        false => pipeline.resume(name),
+    };
+
+    if !found {
+        return reply(
+            StatusCode::NOT_FOUND,
+            json!({ "error": "unknown pipeline" }),
+        );
+    }
+
+    info!(pipeline = name, pause, "source switched from admin api");
+
+    reply(StatusCode::OK, paused(pipeline))
+}
+
+fn route(pipeline: &Pipeline, req: &Request<Body>) -> Response<Body> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/health") => health(pipeline),
+        (&Method::GET, "/ready") => ready(pipeline),
+        (&Method::GET, "/cursor") => cursor(pipeline),
+        (&Method::GET, "/stages") => stages(pipeline),
+        (&Method::POST, "/pause") => switch(pipeline, req, true),
+        (&Method::POST, "/resume") => switch(pipeline, req, false),
+        (_, "/health" | "/ready" | "/cursor" | "/stages" | "/pause" | "/resume") => reply(
+            StatusCode::METHOD_NOT_ALLOWED,
+            json!({ "error": "method not allowed" }),
+        ),
+        _ => reply(StatusCode::NOT_FOUND, json!({ "error": "not found" })),
+    }
+}
+
+/// Serves the admin api until the task is aborted
+pub async fn serve(addr: SocketAddr, pipeline: Arc<Pipeline>) {
+    let make_service = make_service_fn(move |_| {
+        let pipeline = pipeline.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let response = route(&pipeline, &req);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+
+    let server = match Server::try_bind(&addr) {
+        Ok(x) => x,
+        Err(err) => {
+            warn!(%err, "can't start admin server");
+            return;
+        }
+    };
+
+    if let Err(err) = server.serve(make_service).await {
+        warn!(%err, "admin server failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oura::pipeline::Builder;
+
+    use super::*;
+
+    fn request(method: Method, uri: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn body(response: Response<Body>) -> JsonValue {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let bytes = runtime
+            .block_on(hyper::body::to_bytes(response.into_body()))
+            .unwrap();
+
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn parses_pipeline_param() {
+        assert_eq!(pipeline_param(None), None);
+        assert_eq!(pipeline_param(Some("other=x")), None);
+        assert_eq!(pipeline_param(Some("pipeline=pay")), Some("pay".into()));
+
+        assert_eq!(
+            pipeline_param(Some("other=x&pipeline=pay%2Fments")),
+            Some("pay/ments".into())
+        );
+
+        assert_eq!(
+            pipeline_param(Some("pipeline=my+pipeline")),
+            Some("my pipeline".into())
+        );
+    }
+
+    #[test]
+    fn routes_requests() {
+        let config = json!({
+            "source": { "type": "N2N", "peers": ["localhost:1"] },
+            "intersect": { "type": "Tip" },
+            "sink": { "type": "Noop" },
+        });
+
+        let pipeline = Builder::from_config(serde_json::from_value(config).unwrap())
+            .build()
+            .unwrap();
+
+        let response = route(&pipeline, &request(Method::GET, "/stages"));
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = route(&pipeline, &request(Method::GET, "/missing"));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = route(&pipeline, &request(Method::GET, "/pause"));
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let response = route(&pipeline, &request(Method::POST, "/health"));
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let response = route(&pipeline, &request(Method::POST, "/pause?pipeline=other"));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = route(&pipeline, &request(Method::POST, "/pause?pipeline=default"));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response), json!({ "paused": ["default"] }));
+
+        let response = route(&pipeline, &request(Method::POST, "/resume"));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response), json!({ "paused": [] }));
+
+        pipeline.teardown();
+    }
+}
@@ -1,4 +1,5 @@
-use gasket::{daemon::Daemon, metrics::Reading, runtime::Tether};
+use gasket::{metrics::Reading, runtime::Tether};
+use oura::pipeline::Pipeline;
 use std::{sync::Arc, time::Duration};
 
 struct TuiConsole {
@@ -101,7 +102,7 @@ impl TuiConsole {
     }
 }
 
-pub async fn render(daemon: Arc<Daemon>, tui_enabled: bool) {
+pub async fn render(pipeline: Arc<Pipeline>, tui_enabled: bool) {
     if !tui_enabled {
         return;
     }
@@ -109,7 +110,8 @@ pub async fn render(daemon: Arc<Daemon>, tui_enabled: bool) {
     let tui = TuiConsole::new();
 
     loop {
-        tui.refresh(daemon.tethers());
+        let stages = pipeline.named_stages();
+        tui.refresh(stages.iter().map(|(_, x)| x.as_ref()));
         tokio::time::sleep(Duration::from_secs(1)).await;
     }
 }
@@ -1,32 +1,75 @@
-use gasket::daemon::Daemon;
-use oura::{cursor, filters, framework::*, sinks, sources};
+use oura::{framework::*, pipeline};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
-use std::{sync::Arc, time::Duration};
-use tracing::info;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
 
-use crate::console;
+use crate::reload::{Snapshot, Watcher};
+use crate::{admin, console, prometheus};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetricsConfig {
     pub address: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminConfig {
+    pub address: Option<String>,
+}
+
+/// Config file with a single pipeline defined at the root
+#[derive(Deserialize)]
+struct SingleRoot {
+    #[serde(flatten)]
+    pipeline: pipeline::Config,
+    metrics: Option<MetricsConfig>,
+    admin: Option<AdminConfig>,
+}
+
+/// Config file with a map of named pipelines
 #[derive(Deserialize)]
-struct ConfigRoot {
-    source: sources::Config,
-    filters: Option<Vec<filters::Config>>,
-    sink: sinks::Config,
-    intersect: IntersectConfig,
-    finalize: Option<FinalizeConfig>,
-    chain: Option<ChainConfig>,
-    retries: Option<gasket::retries::Policy>,
-    cursor: Option<cursor::Config>,
+struct MultiRoot {
+    pipelines: BTreeMap<String, pipeline::Config>,
+    #[serde(default)]
+    share_sources: bool,
     metrics: Option<MetricsConfig>,
+    admin: Option<AdminConfig>,
+}
+
+pub struct NamedPipeline {
+    pub name: String,
+    pub config: pipeline::Config,
+    /// The config of the pipeline as generic json, used for comparisons
+    pub resolved: JsonValue,
+    /// Pipelines with the same group share a single source
+    pub source_group: Option<String>,
+}
+
+pub struct ConfigRoot {
+    pub pipelines: Vec<NamedPipeline>,
+    pub metrics: Option<MetricsConfig>,
+    pub admin: Option<AdminConfig>,
+}
+
+/// The settings that need to match for two pipelines to share a source
+fn source_identity(pipeline: &JsonValue) -> JsonValue {
+    json!({
+        "source": pipeline["source"],
+        "chain": pipeline["chain"],
+        "intersect": pipeline["intersect"],
+        "finalize": pipeline["finalize"],
+    })
 }
 
 impl ConfigRoot {
-    pub fn new(explicit_file: &Option<std::path::PathBuf>) -> Result<Self, config::ConfigError> {
+    /// Merges every config source, without deserializing the result
+    pub fn load(
+        explicit_file: &Option<std::path::PathBuf>,
+    ) -> Result<config::Config, config::ConfigError> {
         let mut s = config::Config::builder();
 
         // our base config will always be in /etc/scrolls
@@ -43,55 +86,92 @@ impl ConfigRoot {
         // finally, we use env vars to make some last-step overrides
         s = s.add_source(config::Environment::with_prefix("OURA").separator("_"));
 
-        s.build()?.try_deserialize()
+        s.build()
     }
-}
 
-fn define_gasket_policy(config: Option<&gasket::retries::Policy>) -> gasket::runtime::Policy {
-    let default_policy = gasket::retries::Policy {
-        max_retries: 20,
-        backoff_unit: Duration::from_secs(1),
-        backoff_factor: 2,
-        max_backoff: Duration::from_secs(60),
-        dismissible: false,
-    };
-
-    gasket::runtime::Policy {
-        tick_timeout: None,
-        bootstrap_retry: config.cloned().unwrap_or(default_policy.clone()),
-        work_retry: config.cloned().unwrap_or(default_policy.clone()),
-        teardown_retry: config.cloned().unwrap_or(default_policy.clone()),
+    /// True if the config defines a map of named pipelines
+    pub fn is_multi(raw: &config::Config) -> bool {
+        raw.get_table("pipelines").is_ok()
     }
-}
 
-fn connect_stages(
-    mut source: sources::Bootstrapper,
-    mut filters: Vec<filters::Bootstrapper>,
-    mut sink: sinks::Bootstrapper,
-    mut cursor: cursor::Bootstrapper,
-    policy: gasket::runtime::Policy,
-) -> Result<Daemon, Error> {
-    let mut prev = source.borrow_output();
+    /// Deserializes a merged config, either with one or many pipelines
+    pub fn from_raw(raw: config::Config) -> Result<Self, config::ConfigError> {
+        let mut json: JsonValue = raw.clone().try_deserialize()?;
+
+        if !Self::is_multi(&raw) {
+            let root: SingleRoot = raw.try_deserialize()?;
+
+            if let Some(x) = json.as_object_mut() {
+                x.remove("metrics");
+                x.remove("admin");
+            }
+
+            return Ok(Self {
+                pipelines: vec![NamedPipeline {
+                    name: pipeline::DEFAULT_NAME.to_owned(),
+                    config: root.pipeline,
+                    resolved: json,
+                    source_group: None,
+                }],
+                metrics: root.metrics,
+                admin: root.admin,
+            });
+        }
 
-    for filter in filters.iter_mut() {
-        gasket::messaging::tokio::connect_ports(prev, filter.borrow_input(), 100);
-        prev = filter.borrow_output();
-    }
+        let root: MultiRoot = raw.try_deserialize()?;
+
+        // each pipeline joins the group of the first one with the same source
+        let mut identities: Vec<(String, JsonValue)> = vec![];
+        let mut pipelines = vec![];
+
+        for (name, config) in root.pipelines {
+            let source_group = match root.share_sources {
+                true => {
+                    let identity = source_identity(&json["pipelines"][&name]);
+
+                    let group = match identities.iter().find(|(_, x)| *x == identity) {
+                        Some((group, _)) => group.clone(),
+                        None => {
+                            identities.push((name.clone(), identity));
+                            name.clone()
+                        }
+                    };
+
+                    Some(group)
+                }
+                false => None,
+            };
+
+            pipelines.push(NamedPipeline {
+                resolved: json["pipelines"][&name].clone(),
+                name,
+                config,
+                source_group,
+            });
+        }
 
-    gasket::messaging::tokio::connect_ports(prev, sink.borrow_input(), 100);
-    let prev = sink.borrow_cursor();
+        Ok(Self {
+            pipelines,
+            metrics: root.metrics,
+            admin: root.admin,
+        })
+    }
 
-    gasket::messaging::tokio::connect_ports(prev, cursor.borrow_track(), 100);
+    /// Builds every pipeline of the config into a single set of stages
+    pub fn into_builder(self) -> pipeline::multi::Builder {
+        let mut builder = pipeline::multi::Builder::new();
 
-    let mut tethers = vec![];
-    tethers.push(source.spawn(policy.clone()));
-    tethers.extend(filters.into_iter().map(|x| x.spawn(policy.clone())));
-    tethers.push(sink.spawn(policy.clone()));
-    tethers.push(cursor.spawn(policy));
+        for entry in self.pipelines {
+            let pipeline = pipeline::Builder::from_config(entry.config);
 
-    let runtime = Daemon(tethers);
+            builder = match entry.source_group {
+                Some(group) => builder.shared(entry.name, group, pipeline),
+                None => builder.pipeline(entry.name, pipeline),
+            };
+        }
 
-    Ok(runtime)
+        builder
+    }
 }
 
 fn setup_tracing() {
@@ -104,12 +184,11 @@ fn setup_tracing() {
 }
 
 async fn serve_prometheus(
-    daemon: Arc<Daemon>,
+    pipeline: Arc<pipeline::Pipeline>,
     metrics: Option<MetricsConfig>,
 ) -> Result<(), Error> {
     if let Some(metrics) = metrics {
         info!("starting metrics exporter");
-        let runtime = daemon.clone();
 
         let addr: SocketAddr = metrics
             .address
@@ -118,71 +197,270 @@ async fn serve_prometheus(
             .parse()
             .map_err(Error::parse)?;
 
-        gasket_prometheus::serve(addr, runtime).await;
+        prometheus::serve(addr, pipeline).await;
     }
 
     Ok(())
 }
 
-pub fn run(args: &Args) -> Result<(), Error> {
-    if !args.tui {
-        setup_tracing();
+async fn serve_admin(
+    pipeline: Arc<pipeline::Pipeline>,
+    admin: Option<AdminConfig>,
+) -> Result<(), Error> {
+    if let Some(admin) = admin {
+        info!("starting admin server");
+
+        let addr: SocketAddr = admin
+            .address
+            .as_deref()
+            .unwrap_or("0.0.0.0:9187")
+            .parse()
+            .map_err(Error::parse)?;
+
+        admin::serve(addr, pipeline).await;
+    }
+
+    Ok(())
+}
+
+/// Flags a shutdown once the process receives a SIGTERM or a SIGINT
+async fn listen_shutdown(requested: Arc<AtomicBool>) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(x) => x,
+            Err(err) => {
+                warn!(%err, "can't listen for SIGTERM");
+                return;
+            }
+        };
+
+        tokio::select! {
+            _ = terminate.recv() => info!("received SIGTERM"),
+            _ = tokio::signal::ctrl_c() => info!("received SIGINT"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            warn!(%err, "can't listen for SIGINT");
+            return;
+        }
+
+        info!("received SIGINT");
     }
 
-    let config = ConfigRoot::new(&args.config).map_err(Error::config)?;
+    requested.store(true, Ordering::SeqCst);
+}
+
+/// How long a reload waits for in-flight events before giving up on them
+const RELOAD_DRAIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long dismissed stages get to wrap up before the process exits
+const TEARDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Loads the config again, if it can replace the running one in place
+///
+/// The merged config is loaded only once, so that the pipelines that get
+/// validated are the same ones that get attached afterwards.
+fn prepare_reload(
+    args: &Args,
+    current: &Snapshot,
+) -> Result<Option<(config::Config, Snapshot)>, Error> {
+    let raw = ConfigRoot::load(&args.config).map_err(Error::config)?;
+    let config = ConfigRoot::from_raw(raw.clone()).map_err(Error::config)?;
+    let next = Snapshot::of(&config);
+
+    if !current.compare(&next)? {
+        info!("config didn't change, nothing to reload");
+        return Ok(None);
+    }
 
-    let chain = config.chain.unwrap_or_default();
-    let intersect = config.intersect;
-    let finalize = config.finalize;
-    let current_dir = std::env::current_dir().unwrap();
-    let cursor = config.cursor.unwrap_or_default();
-    let breadcrumbs = cursor.initial_load()?;
+    // a dry run doesn't touch the stores or cursors used by the running
+    // pipelines, which are still untouched if the config is refused
+    for entry in config.pipelines {
+        pipeline::Builder::from_config(entry.config).validate()?;
+    }
 
-    let ctx = Context {
-        chain,
-        intersect,
-        finalize,
-        current_dir,
-        breadcrumbs,
-    };
+    Ok(Some((raw, next)))
+}
 
-    let source = config.source.bootstrapper(&ctx)?;
+/// Builds the pipeline with the given name from a merged config
+fn pipeline_of(raw: &config::Config, name: &str) -> Result<pipeline::Builder, Error> {
+    let config = ConfigRoot::from_raw(raw.clone()).map_err(Error::config)?;
 
-    let filters = config
-        .filters
+    config
+        .pipelines
         .into_iter()
-        .flatten()
-        .map(|x| x.bootstrapper(&ctx))
-        .collect::<Result<_, _>>()?;
+        .find(|x| x.name == name)
+        .map(|x| pipeline::Builder::from_config(x.config))
+        .ok_or_else(|| Error::config(format!("there's no pipeline named `{name}`")))
+}
+
+/// Replaces the filters and sinks of the pipelines that changed, while their
+/// sources and cursors keep running
+///
+/// The running config of each pipeline is kept in `origins`, so that a
+/// pipeline that can't take the new stages gets its previous ones back.
+/// Returns the names of the pipelines that kept their previous stages.
+fn apply_reload(
+    current: &pipeline::Pipeline,
+    changed: Vec<String>,
+    next: &config::Config,
+    origins: &mut BTreeMap<String, config::Config>,
+) -> Vec<String> {
+    let mut kept = vec![];
+
+    for name in changed {
+        let Some(origin) = origins.get(&name) else {
+            continue;
+        };
+
+        let (builder, fallback) = match (pipeline_of(next, &name), pipeline_of(origin, &name)) {
+            (Ok(x), Ok(y)) => (x, y),
+            (Err(err), _) | (_, Err(err)) => {
+                warn!(
+                    ?err,
+                    pipeline = name,
+                    "can't reload pipeline, keeping it as it is"
+                );
+                kept.push(name);
+                continue;
+            }
+        };
+
+        let detached = match current.detach(&name, RELOAD_DRAIN_TIMEOUT) {
+            Ok(x) => x,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    pipeline = name,
+                    "can't reload pipeline, keeping it as it is"
+                );
+                kept.push(name);
+                continue;
+            }
+        };
+
+        match detached.attach(builder) {
+            Ok(()) => {
+                origins.insert(name, next.clone());
+            }
+            Err((err, detached)) => {
+                warn!(
+                    ?err,
+                    pipeline = name,
+                    "can't attach the new stages, restoring the previous ones"
+                );
+
+                // without any stages, the source fails on the next event and
+                // the daemon stops as it does for any other failed stage
+                if let Err((err, _)) = detached.attach(fallback) {
+                    error!(?err, pipeline = name, "can't restore the previous stages");
+                }
+
+                kept.push(name);
+            }
+        }
+    }
+
+    kept
+}
+
+pub fn run(args: &Args) -> Result<(), Error> {
+    if !args.tui {
+        setup_tracing();
+    }
 
-    let sink = config.sink.bootstrapper(&ctx)?;
+    let raw = ConfigRoot::load(&args.config).map_err(Error::config)?;
+    let mut config = ConfigRoot::from_raw(raw.clone()).map_err(Error::config)?;
 
-    let cursor = cursor.bootstrapper(&ctx)?;
+    let metrics = config.metrics.take();
+    let admin = config.admin.take();
+    let mut snapshot = Snapshot::of(&config);
 
-    let retries = define_gasket_policy(config.retries.as_ref());
+    let mut origins: BTreeMap<_, _> = config
+        .pipelines
+        .iter()
+        .map(|x| (x.name.clone(), raw.clone()))
+        .collect();
 
-    let daemon = connect_stages(source, filters, sink, cursor, retries)?;
+    let pipeline = Arc::new(config.into_builder().build()?);
 
     info!("oura is running");
 
-    let daemon = Arc::new(daemon);
-
     let tokio_rt = tokio::runtime::Builder::new_multi_thread()
         .enable_io()
         .enable_time()
         .build()
         .unwrap();
 
-    let prometheus = tokio_rt.spawn(serve_prometheus(daemon.clone(), config.metrics));
-    let tui = tokio_rt.spawn(console::render(daemon.clone(), args.tui));
+    let mut watcher = Watcher::new(&args.config);
+    let sighup = tokio_rt.spawn(Watcher::listen_sighup(watcher.requested()));
 
-    daemon.block();
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let signals = tokio_rt.spawn(listen_shutdown(shutdown.clone()));
+
+    let prometheus = tokio_rt.spawn(serve_prometheus(pipeline.clone(), metrics));
+    let admin_api = tokio_rt.spawn(serve_admin(pipeline.clone(), admin));
+    let tui = tokio_rt.spawn(console::render(pipeline.clone(), args.tui));
+
+    loop {
+        std::thread::sleep(Duration::from_millis(1500));
+
+        if pipeline.has_stopped() {
+            break;
+        }
+
+        if shutdown.load(Ordering::SeqCst) {
+            info!("draining pipelines before shutting down");
+
+            let timeout = Duration::from_secs(args.shutdown_timeout);
+
+            if !pipeline.drain(timeout) {
+                warn!("pipelines didn't drain in time, some events will be processed again");
+            }
+
+            break;
+        }
+
+        if !watcher.should_reload() {
+            continue;
+        }
+
+        let (raw, next) = match prepare_reload(args, &snapshot) {
+            Ok(Some(x)) => x,
+            Ok(None) => continue,
+            Err(err) => {
+                warn!(?err, "refusing to reload config");
+                continue;
+            }
+        };
+
+        let changed = snapshot.changed(&next);
+        let kept = apply_reload(&pipeline, changed, &raw, &mut origins);
+
+        snapshot = next.keeping(&snapshot, &kept);
+        info!("config reloaded");
+    }
 
     info!("oura is stopping");
 
-    daemon.teardown();
+    pipeline.teardown();
+
+    // give the cursors a chance to persist the latest points
+    if !pipeline.wait(TEARDOWN_TIMEOUT) {
+        warn!("some stages didn't stop in time");
+    }
+
     prometheus.abort();
+    admin_api.abort();
     tui.abort();
+    sighup.abort();
+    signals.abort();
 
     Ok(())
 }
@@ -197,4 +475,8 @@ pub struct Args {
     /// display the terminal UI
     #[clap(long, action)]
     tui: bool,
+
+    /// seconds to wait for in-flight events when stopped by a signal
+    #[clap(long, value_parser, default_value_t = 30)]
+    shutdown_timeout: u64,
 }
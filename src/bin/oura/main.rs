@@ -1,8 +1,12 @@
 use clap::Parser;
 use std::process;
 
+mod admin;
 mod console;
 mod daemon;
+mod prometheus;
+mod reload;
+mod validate;
 
 #[derive(Parser)]
 #[clap(name = "Oura")]
@@ -10,6 +14,7 @@ mod daemon;
 #[clap(author, version, about, long_about = None)]
 enum Oura {
     Daemon(daemon::Args),
+    Validate(validate::Args),
 }
 
 fn main() {
@@ -17,6 +22,14 @@ fn main() {
 
     let result = match args {
         Oura::Daemon(x) => daemon::run(&x),
+        Oura::Validate(x) => {
+            if let Err(report) = validate::run(&x) {
+                eprintln!("{report:?}");
+                process::exit(1);
+            }
+
+            Ok(())
+        }
     };
 
     if let Err(err) = &result {
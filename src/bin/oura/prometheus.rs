@@ -0,0 +1,140 @@
+use gasket::metrics::Reading;
+use oura::pipeline::Pipeline;
+use prometheus_exporter_base::{
+    prelude::ServerOptions, render_prometheus, MetricType, PrometheusInstance, PrometheusMetric,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::{net::SocketAddr, sync::Arc};
+
+fn sanitize_stage_name(raw: &str) -> String {
+    raw.replace('-', "_")
+}
+
+/// A reading, labelled with the pipeline of the stage and the index of the
+/// stage among the ones with the same name in that pipeline
+struct Value {
+    pipeline: String,
+    instance: String,
+    value: i128,
+}
+
+/// Readings of a single metric, across every stage that reports it
+struct Family {
+    kind: MetricType,
+    help: String,
+    values: Vec<Value>,
+}
+
+/// Numbers the stages that share a name within the same pipeline, so that
+/// each of them ends up in its own series
+fn instances<'a>(stages: impl Iterator<Item = (&'a str, &'a str)>) -> Vec<usize> {
+    let mut seen = HashMap::<(&str, &str), usize>::new();
+
+    stages
+        .map(|key| {
+            let count = seen.entry(key).or_default();
+            *count += 1;
+            *count - 1
+        })
+        .collect()
+}
+
+fn collect(pipeline: &Pipeline) -> BTreeMap<String, Family> {
+    let mut families = BTreeMap::<String, Family>::new();
+
+    let stages = pipeline.named_stages();
+    let instances = instances(stages.iter().map(|(name, x)| (name.as_str(), x.name())));
+
+    for ((name, tether), instance) in stages.iter().zip(instances) {
+        let Ok(readings) = tether.read_metrics() else {
+            continue;
+        };
+
+        for (key, reading) in readings {
+            let (kind, label, value) = match reading {
+                Reading::Count(x) => (MetricType::Counter, "counter", x as i128),
+                Reading::Gauge(x) => (MetricType::Gauge, "gauge", x as i128),
+                _ => continue,
+            };
+
+            let metric = format!("{}_{}", sanitize_stage_name(tether.name()), key);
+
+            families
+                .entry(metric)
+                .or_insert_with(|| Family {
+                    kind,
+                    help: format!("{} {} for stage {}", key, label, tether.name()),
+                    values: vec![],
+                })
+                .values
+                .push(Value {
+                    pipeline: name.clone(),
+                    instance: instance.to_string(),
+                    value,
+                });
+        }
+    }
+
+    families
+}
+
+fn render(families: BTreeMap<String, Family>) -> String {
+    let mut out = String::new();
+
+    for (metric, family) in families {
+        let mut pc = PrometheusMetric::build()
+            .with_name(&metric)
+            .with_metric_type(family.kind)
+            .with_help(&family.help)
+            .build();
+
+        for value in family.values.iter() {
+            pc.render_and_append_instance(
+                &PrometheusInstance::new()
+                    .with_label("pipeline", value.pipeline.as_str())
+                    .with_label("instance", value.instance.as_str())
+                    .with_value(value.value)
+                    .with_current_timestamp()
+                    .expect("error getting the current UNIX epoch"),
+            );
+        }
+
+        out.push_str(&pc.render());
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Serves the metrics of every stage, labelled with the name of its pipeline
+/// and its instance
+pub async fn serve(addr: SocketAddr, pipeline: Arc<Pipeline>) {
+    let server_options = ServerOptions {
+        addr,
+        authorization: prometheus_exporter_base::prelude::Authorization::None,
+    };
+
+    render_prometheus(server_options, pipeline, |_, pipeline| async move {
+        Ok(render(collect(&pipeline)))
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_same_named_stages_per_pipeline() {
+        let stages = [
+            ("a", "source"),
+            ("a", "sink-noop"),
+            ("a", "sink-noop"),
+            ("b", "sink-noop"),
+            ("a", "sink-stdout"),
+            ("a", "sink-noop"),
+        ];
+
+        assert_eq!(instances(stages.into_iter()), vec![0, 0, 1, 0, 0, 2]);
+    }
+}
@@ -0,0 +1,242 @@
+use oura::framework::*;
+use serde_json::Value as JsonValue;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+use crate::daemon::ConfigRoot;
+
+/// Detects when the config of the daemon needs to be reloaded
+///
+/// A reload is requested when any of the config files changes on disk or
+/// when the process receives a SIGHUP.
+pub struct Watcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    requested: Arc<AtomicBool>,
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
+
+impl Watcher {
+    pub fn new(explicit_file: &Option<PathBuf>) -> Self {
+        let files = [
+            Some(PathBuf::from("/etc/oura/daemon.toml")),
+            Some(PathBuf::from("oura.toml")),
+            explicit_file.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|x| {
+            let time = modified(&x);
+            (x, time)
+        })
+        .collect();
+
+        Self {
+            files,
+            requested: Default::default(),
+        }
+    }
+
+    /// Flags a reload each time the process receives a SIGHUP
+    pub async fn listen_sighup(requested: Arc<AtomicBool>) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(x) => x,
+                Err(err) => {
+                    warn!(%err, "can't listen for SIGHUP");
+                    return;
+                }
+            };
+
+            while hangup.recv().await.is_some() {
+                info!("received SIGHUP");
+                requested.store(true, Ordering::SeqCst);
+            }
+        }
+
+        #[cfg(not(unix))]
+        drop(requested);
+    }
+
+    pub fn requested(&self) -> Arc<AtomicBool> {
+        self.requested.clone()
+    }
+
+    /// True if a reload was requested since the last call
+    pub fn should_reload(&mut self) -> bool {
+        let mut changed = self.requested.swap(false, Ordering::SeqCst);
+
+        for (path, last) in self.files.iter_mut() {
+            let current = modified(path);
+
+            if current != *last {
+                info!(path = %path.display(), "config file changed");
+                *last = current;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
+
+/// The parts of a running config needed to validate a reload
+pub struct Snapshot(Vec<(String, JsonValue, Option<String>)>);
+
+impl Snapshot {
+    pub fn of(config: &ConfigRoot) -> Self {
+        let pipelines = config
+            .pipelines
+            .iter()
+            .map(|x| (x.name.clone(), x.resolved.clone(), x.source_group.clone()))
+            .collect();
+
+        Self(pipelines)
+    }
+
+    /// Checks that the next config can replace this one in place
+    ///
+    /// Filters, sinks and routes can change, but the set of pipelines and
+    /// the stages that keep running across a reload (sources, cursors and
+    /// dead-letter sinks) need to stay the same. Returns false if nothing
+    /// changed at all.
+    pub fn compare(&self, next: &Snapshot) -> Result<bool, Error> {
+        let names = |x: &Snapshot| {
+            x.0.iter()
+                .map(|(name, ..)| name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        if names(self) != names(next) {
+            return Err(Error::config(
+                "pipelines can't be added or removed on reload",
+            ));
+        }
+
+        let mut changed = false;
+
+        for ((name, current, group), (_, other, other_group)) in self.0.iter().zip(next.0.iter()) {
+            for key in [
+                "source",
+                "chain",
+                "intersect",
+                "finalize",
+                "cursor",
+                "dead_letter",
+            ] {
+                if current[key] != other[key] {
+                    return Err(Error::config(format!(
+                        "pipeline `{name}`: the {key} can't change on reload"
+                    )));
+                }
+            }
+
+            if group != other_group {
+                return Err(Error::config(format!(
+                    "pipeline `{name}`: shared sources can't change on reload"
+                )));
+            }
+
+            changed |= current != other;
+        }
+
+        Ok(changed)
+    }
+
+    /// Names of the pipelines whose config is different in the next one
+    pub fn changed(&self, next: &Snapshot) -> Vec<String> {
+        self.0
+            .iter()
+            .zip(next.0.iter())
+            .filter(|((_, current, _), (_, other, _))| current != other)
+            .map(|((name, ..), _)| name.clone())
+            .collect()
+    }
+
+    /// Takes the entries of the given pipelines from the previous snapshot,
+    /// for pipelines that couldn't be reloaded
+    pub fn keeping(mut self, previous: &Snapshot, names: &[String]) -> Self {
+        for (entry, old) in self.0.iter_mut().zip(previous.0.iter()) {
+            if names.contains(&entry.0) {
+                *entry = old.clone();
+            }
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn snapshot(pipelines: &[(&str, JsonValue)]) -> Snapshot {
+        let all = pipelines
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone(), None))
+            .collect();
+
+        Snapshot(all)
+    }
+
+    #[test]
+    fn only_filters_and_sinks_can_change() {
+        let base = json!({
+            "source": { "type": "N2N", "peers": ["relay:3001"] },
+            "filters": [{ "type": "Select", "predicate": "addr1" }],
+            "sink": { "type": "Stdout" },
+        });
+
+        let current = snapshot(&[("a", base.clone())]);
+
+        assert!(!current.compare(&snapshot(&[("a", base.clone())])).unwrap());
+
+        let mut filters = base.clone();
+        filters["filters"][0]["predicate"] = json!("addr2");
+        assert!(current.compare(&snapshot(&[("a", filters)])).unwrap());
+
+        let mut source = base.clone();
+        source["source"]["peers"] = json!(["other:3001"]);
+        assert!(current.compare(&snapshot(&[("a", source)])).is_err());
+
+        let mut chain = base.clone();
+        chain["chain"] = json!({ "type": "preprod" });
+        assert!(current.compare(&snapshot(&[("a", chain)])).is_err());
+
+        let mut cursor = base.clone();
+        cursor["cursor"] = json!({ "type": "File", "path": "cursor.json" });
+        assert!(current.compare(&snapshot(&[("a", cursor)])).is_err());
+
+        let renamed = snapshot(&[("b", base)]);
+        assert!(current.compare(&renamed).is_err());
+    }
+
+    #[test]
+    fn lists_changed_pipelines() {
+        let base = json!({
+            "source": { "type": "N2N", "peers": ["relay:3001"] },
+            "sink": { "type": "Stdout" },
+        });
+
+        let mut other = base.clone();
+        other["sink"] = json!({ "type": "Noop" });
+
+        let current = snapshot(&[("a", base.clone()), ("b", base.clone())]);
+        let next = snapshot(&[("a", base.clone()), ("b", other.clone())]);
+
+        assert_eq!(current.changed(&next), vec!["b".to_string()]);
+
+        let kept = next.keeping(&current, &["b".to_string()]);
+        assert!(current.changed(&kept).is_empty());
+    }
+}
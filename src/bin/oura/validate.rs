@@ -0,0 +1,526 @@
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use oura::{framework::*, pipeline};
+use serde_json::Value as JsonValue;
+use std::path::PathBuf;
+use thiserror::Error;
+
+use crate::daemon::ConfigRoot;
+
+/// Stage types that are only available when a feature is compiled in
+const FEATURE_GATED: &[(&str, &str, &str, bool)] = &[
+    ("source", "U5C", "u5c", cfg!(feature = "u5c")),
+    ("source", "S3", "aws", cfg!(feature = "aws")),
+    ("source", "Mithril", "mithril", cfg!(feature = "mithril")),
+    ("filter", "WasmPlugin", "wasm", cfg!(feature = "wasm")),
+    ("sink", "Rabbitmq", "rabbitmq", cfg!(feature = "rabbitmq")),
+    ("sink", "Kafka", "kafka", cfg!(feature = "kafka")),
+    ("sink", "AwsSqs", "aws", cfg!(feature = "aws")),
+    ("sink", "AwsLambda", "aws", cfg!(feature = "aws")),
+    ("sink", "AwsS3", "aws", cfg!(feature = "aws")),
+    ("sink", "GcpPubSub", "gcp", cfg!(feature = "gcp")),
+    ("sink", "GcpCloudFunction", "gcp", cfg!(feature = "gcp")),
+    ("sink", "Redis", "redis", cfg!(feature = "redis")),
+    (
+        "sink",
+        "ElasticSearch",
+        "elasticsearch",
+        cfg!(feature = "elasticsearch"),
+    ),
+    ("sink", "SqlDb", "sql", cfg!(feature = "sql")),
+    ("cursor", "Redis", "redis", cfg!(feature = "redis")),
+];
+
+/// Stage options that are only available when a feature is compiled in
+const OPTION_GATED: &[(&str, &str, &str, &str, bool)] = &[(
+    "filter",
+    "ParseCbor",
+    "utxo_store",
+    "utxo-store",
+    cfg!(feature = "utxo-store"),
+)];
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("{message}")]
+#[diagnostic(code(oura::config))]
+struct ConfigDiagnostic {
+    message: String,
+
+    #[source_code]
+    src: Option<NamedSource<String>>,
+
+    #[label("{label}")]
+    span: Option<SourceSpan>,
+
+    label: String,
+
+    #[help]
+    help: Option<String>,
+}
+
+/// The file that most likely holds the relevant config, used for diagnostics
+struct ConfigSource {
+    path: PathBuf,
+    text: String,
+}
+
+impl ConfigSource {
+    fn find(explicit: &Option<PathBuf>) -> Option<Self> {
+        let candidates = [
+            explicit.clone(),
+            Some(PathBuf::from("oura.toml")),
+            Some(PathBuf::from("/etc/oura/daemon.toml")),
+        ];
+
+        candidates.into_iter().flatten().find_map(|path| {
+            let text = std::fs::read_to_string(&path).ok()?;
+            Some(Self { path, text })
+        })
+    }
+
+    /// Finds the span of the first quoted occurrence of a value
+    fn locate(&self, needle: &str) -> Option<SourceSpan> {
+        let quoted = format!("\"{needle}\"");
+
+        let (offset, len) = match self.text.find(&quoted) {
+            Some(x) => (x, quoted.len()),
+            None => (self.text.find(needle)?, needle.len()),
+        };
+
+        Some((offset, len).into())
+    }
+}
+
+fn diagnostic(
+    source: Option<&ConfigSource>,
+    message: String,
+    needle: Option<&str>,
+    help: Option<String>,
+) -> ConfigDiagnostic {
+    let span = source.zip(needle).and_then(|(s, n)| s.locate(n));
+
+    let src = match (source, span) {
+        (Some(s), Some(_)) => Some(NamedSource::new(s.path.to_string_lossy(), s.text.clone())),
+        _ => None,
+    };
+
+    ConfigDiagnostic {
+        label: needle.map(|x| format!("`{x}`")).unwrap_or_default(),
+        message,
+        src,
+        span,
+        help,
+    }
+}
+
+/// Extracts the first `quoted` word of a serde error message
+fn first_quoted(message: &str) -> Option<&str> {
+    let start = message.find('`')? + 1;
+    let len = message[start..].find('`')?;
+    Some(&message[start..start + len])
+}
+
+fn error_message(err: &Error) -> String {
+    match err {
+        Error::Config(x) => x.clone(),
+        Error::Custom(x) => x.clone(),
+        Error::Parse(x) => format!("parse error: {x}"),
+    }
+}
+
+/// The config of each stage, with the kind of stage
+fn stage_values(config: &JsonValue) -> Vec<(&'static str, &JsonValue)> {
+    let mut stages = vec![("source", &config["source"])];
+
+    for filter in config["filters"].as_array().into_iter().flatten() {
+        stages.push(("filter", filter));
+    }
+
+    stages.push(("sink", &config["sink"]));
+
+    for sink in config["sinks"].as_array().into_iter().flatten() {
+        stages.push(("sink", sink));
+    }
+
+    for branch in config["route"]["branches"].as_array().into_iter().flatten() {
+        for filter in branch["filters"].as_array().into_iter().flatten() {
+            stages.push(("filter", filter));
+        }
+
+        stages.push(("sink", &branch["sink"]));
+    }
+
+    stages.push(("cursor", &config["cursor"]));
+    stages.push(("sink", &config["dead_letter"]["sink"]));
+
+    stages
+}
+
+/// The type of each stage in the config, with the kind of stage
+fn stage_types(config: &JsonValue) -> Vec<(&'static str, &str)> {
+    stage_values(config)
+        .into_iter()
+        .filter_map(|(kind, value)| Some((kind, value.get("type")?.as_str()?)))
+        .collect()
+}
+
+fn check_features(config: &JsonValue, source: Option<&ConfigSource>) -> miette::Result<()> {
+    for (kind, name) in stage_types(config) {
+        let gated = FEATURE_GATED
+            .iter()
+            .find(|(k, n, ..)| *k == kind && *n == name);
+
+        if let Some((_, _, feature, false)) = gated {
+            return Err(diagnostic(
+                source,
+                format!("{kind} type `{name}` is not available in this build"),
+                Some(name),
+                Some(format!(
+                    "rebuild oura with the `{feature}` feature enabled (eg: `cargo install oura --features {feature}`)"
+                )),
+            )
+            .into());
+        }
+    }
+
+    for (kind, value) in stage_values(config) {
+        let gated = OPTION_GATED.iter().find(|(k, n, option, ..)| {
+            *k == kind && value["type"] == *n && value.get(option).is_some()
+        });
+
+        if let Some((_, name, option, feature, false)) = gated {
+            return Err(diagnostic(
+                source,
+                format!("{kind} option `{option}` of `{name}` is not available in this build"),
+                Some(option),
+                Some(format!(
+                    "rebuild oura with the `{feature}` feature enabled (eg: `cargo install oura --features {feature}`)"
+                )),
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// A stage of the pipeline graph
+struct Node {
+    id: String,
+    kind: &'static str,
+    label: String,
+    depth: usize,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<(String, String, bool)>,
+}
+
+impl Graph {
+    fn node(&mut self, id: String, kind: &'static str, label: String, depth: usize) -> String {
+        self.nodes.push(Node {
+            id: id.clone(),
+            kind,
+            label,
+            depth,
+        });
+
+        id
+    }
+
+    fn edge(&mut self, from: &str, to: &str) {
+        self.edges.push((from.to_owned(), to.to_owned(), false));
+    }
+
+    fn cursor_edge(&mut self, from: &str, to: &str) {
+        self.edges.push((from.to_owned(), to.to_owned(), true));
+    }
+
+    /// Builds the graph of a pipeline, prefixing the id of each node
+    fn from_config(config: &JsonValue, prefix: &str) -> Self {
+        let type_of = |x: &JsonValue| x["type"].as_str().unwrap_or("?").to_owned();
+
+        let mut graph = Graph::default();
+
+        let mut prev = graph.node(
+            format!("{prefix}source"),
+            "source",
+            type_of(&config["source"]),
+            0,
+        );
+
+        for (idx, filter) in config["filters"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let id = graph.node(
+                format!("{prefix}filter_{idx}"),
+                "filter",
+                type_of(filter),
+                0,
+            );
+            graph.edge(&prev, &id);
+            prev = id;
+        }
+
+        let cursor_type = match config.get("cursor") {
+            Some(x) if !x.is_null() => type_of(x),
+            _ => "Memory".into(),
+        };
+
+        let mut sinks = vec![];
+
+        if let Some(route) = config.get("route").filter(|x| !x.is_null()) {
+            let router = graph.node(format!("{prefix}route"), "route", "Route".into(), 0);
+            graph.edge(&prev, &router);
+
+            let branches = route["branches"].as_array().into_iter().flatten();
+
+            for (idx, branch) in branches.enumerate() {
+                let name = branch["name"].as_str().unwrap_or("?");
+
+                let label = match branch.get("predicate") {
+                    Some(x) if !x.is_null() => format!("{name}: {x}"),
+                    _ => format!("{name} (default)"),
+                };
+
+                let id = graph.node(format!("{prefix}branch_{idx}"), "branch", label, 1);
+                graph.edge(&router, &id);
+
+                let mut prev = id;
+
+                let filters = branch["filters"].as_array().into_iter().flatten();
+
+                for (fidx, filter) in filters.enumerate() {
+                    let id = graph.node(
+                        format!("{prefix}branch_{idx}_filter_{fidx}"),
+                        "filter",
+                        type_of(filter),
+                        2,
+                    );
+                    graph.edge(&prev, &id);
+                    prev = id;
+                }
+
+                let id = graph.node(
+                    format!("{prefix}branch_{idx}_sink"),
+                    "sink",
+                    type_of(&branch["sink"]),
+                    2,
+                );
+                graph.edge(&prev, &id);
+                sinks.push(id);
+            }
+        } else {
+            let all = config
+                .get("sink")
+                .filter(|x| !x.is_null())
+                .into_iter()
+                .chain(config["sinks"].as_array().into_iter().flatten());
+
+            for (idx, sink) in all.enumerate() {
+                let id = graph.node(format!("{prefix}sink_{idx}"), "sink", type_of(sink), 0);
+                graph.edge(&prev, &id);
+                sinks.push(id);
+            }
+        }
+
+        let mut feeders = sinks;
+
+        if feeders.len() > 1 || config.get("route").is_some_and(|x| !x.is_null()) {
+            let fanin = graph.node(
+                format!("{prefix}cursor_fanin"),
+                "cursor-fanin",
+                "Fanin".into(),
+                0,
+            );
+
+            for sink in feeders.iter() {
+                graph.cursor_edge(sink, &fanin);
+            }
+
+            feeders = vec![fanin];
+        }
+
+        let cursor = graph.node(format!("{prefix}cursor"), "cursor", cursor_type, 0);
+
+        for feeder in feeders.iter() {
+            graph.cursor_edge(feeder, &cursor);
+        }
+
+        if let Some(dead_letter) = config.get("dead_letter").filter(|x| !x.is_null()) {
+            let relay = graph.node(
+                format!("{prefix}dead_letter"),
+                "dead-letter",
+                "DeadLetter".into(),
+                0,
+            );
+
+            let sink = graph.node(
+                format!("{prefix}dead_letter_sink"),
+                "sink",
+                type_of(&dead_letter["sink"]),
+                1,
+            );
+            graph.edge(&relay, &sink);
+        }
+
+        graph
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        for node in self.nodes.iter() {
+            let indent = "  ".repeat(node.depth);
+            out.push_str(&format!("{indent}[{}] {}\n", node.kind, node.label));
+        }
+
+        out
+    }
+
+    fn write_dot(&self, out: &mut String, indent: &str) {
+        for node in self.nodes.iter() {
+            let label = format!("{}\\n{}", node.kind, node.label.replace('"', "\\\""));
+            out.push_str(&format!("{indent}{} [label=\"{label}\"];\n", node.id));
+        }
+
+        out.push('\n');
+
+        for (from, to, cursor) in self.edges.iter() {
+            match cursor {
+                true => out.push_str(&format!("{indent}{from} -> {to} [style=dashed];\n")),
+                false => out.push_str(&format!("{indent}{from} -> {to};\n")),
+            }
+        }
+    }
+}
+
+/// Renders the graphs in dot format, each named pipeline as a cluster
+fn to_dot(graphs: &[(Option<String>, Graph)]) -> String {
+    let mut out = String::from("digraph oura {\n  rankdir=LR;\n  node [shape=box];\n\n");
+
+    for (idx, (name, graph)) in graphs.iter().enumerate() {
+        match name {
+            Some(name) => {
+                out.push_str(&format!("  subgraph cluster_{idx} {{\n"));
+                out.push_str(&format!("    label=\"{}\";\n\n", name.replace('"', "\\\"")));
+                graph.write_dot(&mut out, "    ");
+                out.push_str("  }\n");
+            }
+            None => graph.write_dot(&mut out, "  "),
+        }
+    }
+
+    out.push_str("}\n");
+
+    out
+}
+
+/// The resolved config of each pipeline, named only if the file has many
+fn pipeline_values(resolved: &JsonValue, multi: bool) -> Vec<(Option<String>, &JsonValue)> {
+    match multi {
+        true => resolved["pipelines"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| (Some(name.clone()), value))
+            .collect(),
+        false => vec![(None, resolved)],
+    }
+}
+
+pub fn run(args: &Args) -> miette::Result<()> {
+    let source = ConfigSource::find(&args.config);
+    let source = source.as_ref();
+
+    let raw = ConfigRoot::load(&args.config)
+        .map_err(|err| diagnostic(source, err.to_string(), None, None))?;
+
+    let resolved: JsonValue = raw
+        .clone()
+        .try_deserialize()
+        .map_err(|err| diagnostic(source, err.to_string(), None, None))?;
+
+    let pipelines = pipeline_values(&resolved, ConfigRoot::is_multi(&raw));
+
+    for (_, value) in pipelines.iter() {
+        check_features(value, source)?;
+    }
+
+    let config = ConfigRoot::from_raw(raw).map_err(|err| {
+        let message = err.to_string();
+        // a missing field can't be pointed at, any match would be misleading
+        let needle = match message.starts_with("missing field") {
+            true => None,
+            false => first_quoted(&message).map(str::to_owned),
+        };
+        diagnostic(source, message, needle.as_deref(), None)
+    })?;
+
+    for entry in config.pipelines {
+        pipeline::Builder::from_config(entry.config)
+            .validate()
+            .map_err(|err| {
+                let message = match pipelines.len() {
+                    1 => error_message(&err),
+                    _ => format!("pipeline `{}`: {}", entry.name, error_message(&err)),
+                };
+
+                diagnostic(source, message, None, None)
+            })?;
+    }
+
+    let graphs: Vec<_> = pipelines
+        .iter()
+        .enumerate()
+        .map(|(idx, (name, value))| {
+            let prefix = match name {
+                Some(_) => format!("p{idx}_"),
+                None => String::new(),
+            };
+
+            (name.clone(), Graph::from_config(value, &prefix))
+        })
+        .collect();
+
+    if args.dot {
+        print!("{}", to_dot(&graphs));
+        return Ok(());
+    }
+
+    println!("configuration is valid\n");
+
+    println!("resolved config:");
+    println!("{}\n", serde_json::to_string_pretty(&resolved).unwrap());
+
+    println!("stages:");
+
+    for (name, graph) in graphs.iter() {
+        if let Some(name) = name {
+            println!("pipeline {name}:");
+        }
+
+        println!("{}", graph.to_text());
+    }
+
+    println!("graphviz:");
+    print!("{}", to_dot(&graphs));
+
+    Ok(())
+}
+
+#[derive(clap::Args)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    /// config file to validate
+    #[clap(long, value_parser)]
+    config: Option<PathBuf>,
+
+    /// only print the stage graph in graphviz dot format
+    #[clap(long, action)]
+    dot: bool,
+}
@@ -0,0 +1,438 @@
+//! A stage that merges the cursor notifications of many sinks
+//!
+//! Each sink reports the points it has processed independently. This stage
+//! keeps track of the progress of each of them and only forwards the oldest
+//! one, so that the persisted cursor never moves past data that some sink
+//! hasn't processed yet.
+//!
+//! When sinks sit behind a router, not every sink receives every event. The
+//! router reports which sink got each event (a dispatch) and which ones didn't
+//! (a skip), so that idle sinks don't hold back the cursor forever.
+//!
+//! The filters of a branch can drop events or turn one event into many, so the
+//! ports between them report each event a filter produces and each event it's
+//! done with. A branch is idle once every event that entered it was either
+//! acked by the sink or dropped along the way. Filters that hold events for a
+//! while (like the confirmation depth) aren't tracked, they count as a single
+//! step between their neighbours.
+
+use gasket::framework::*;
+use gasket::messaging::tokio::{mpsc_channel, ChannelRecvAdapter, ChannelSendAdapter};
+use gasket::messaging::{InputPort, Message, OutputPort, RecvAdapter, SendAdapter};
+use pallas::network::miniprotocols::Point;
+
+use crate::framework::*;
+
+pub type SinkIdx = usize;
+
+#[derive(Debug, Clone)]
+pub enum Signal {
+    /// The sink processed an event for this point
+    Ack(Point),
+    /// An event for this point was routed to the sink
+    Dispatch(Point),
+    /// An event for this point was routed somewhere else
+    Skip(Point),
+    /// A filter of the branch sent an event downstream
+    Produce,
+    /// A filter of the branch is done with an event it received
+    Consume,
+}
+
+pub type SignalPort = OutputPort<(SinkIdx, Signal)>;
+
+/// Send adapter that tags each point with the index of the sink that sent it
+struct TaggedSendAdapter {
+    idx: SinkIdx,
+    inner: ChannelSendAdapter<(SinkIdx, Signal)>,
+}
+
+#[async_trait::async_trait]
+impl SendAdapter<Point> for TaggedSendAdapter {
+    async fn send(&mut self, msg: Message<Point>) -> Result<(), gasket::error::Error> {
+        let tagged = Message::from((self.idx, Signal::Ack(msg.payload)));
+        self.inner.send(tagged).await
+    }
+}
+
+/// Send adapter that reports each event sent by a branch filter
+struct ProduceSendAdapter<P> {
+    idx: SinkIdx,
+    signals: ChannelSendAdapter<(SinkIdx, Signal)>,
+    inner: ChannelSendAdapter<P>,
+}
+
+#[async_trait::async_trait]
+impl<P> SendAdapter<P> for ProduceSendAdapter<P>
+where
+    P: Send + Sync,
+{
+    async fn send(&mut self, msg: Message<P>) -> Result<(), gasket::error::Error> {
+        // the signal goes first so that the ack of the event can't overtake it
+        let signal = Message::from((self.idx, Signal::Produce));
+        self.signals.send(signal).await?;
+
+        self.inner.send(msg).await
+    }
+}
+
+/// Receive adapter that reports when a branch filter is done with an event
+///
+/// Stages only ask for the next event once they're done with the previous
+/// one, so that's when the previous event is reported.
+struct ConsumeRecvAdapter<P> {
+    idx: SinkIdx,
+    signals: ChannelSendAdapter<(SinkIdx, Signal)>,
+    inner: ChannelRecvAdapter<P>,
+    busy: bool,
+}
+
+#[async_trait::async_trait]
+impl<P> RecvAdapter<P> for ConsumeRecvAdapter<P>
+where
+    P: Send + Sync + Clone,
+{
+    async fn recv(&mut self) -> Result<Message<P>, gasket::error::Error> {
+        if self.busy {
+            let signal = Message::from((self.idx, Signal::Consume));
+            self.signals.send(signal).await?;
+            self.busy = false;
+        }
+
+        let msg = self.inner.recv().await?;
+        self.busy = true;
+
+        Ok(msg)
+    }
+}
+
+/// Connects the ports between the stages of a router branch
+///
+/// Returned by [`Stage::connect_sinks`], it reports the events that go in and
+/// out of the filters of each branch.
+#[derive(Clone)]
+pub struct BranchTracker {
+    signals: ChannelSendAdapter<(SinkIdx, Signal)>,
+}
+
+impl BranchTracker {
+    /// Connects two ports of the branch with the given index
+    ///
+    /// Each side is tracked only if it belongs to a filter that processes
+    /// events as they come, the router and the sink report on their own.
+    pub fn connect<P>(
+        &self,
+        idx: SinkIdx,
+        output: (&mut OutputPort<P>, bool),
+        input: (&mut InputPort<P>, bool),
+        cap: usize,
+    ) where
+        P: Send + Sync + Clone + 'static,
+    {
+        let (sender, receiver) = mpsc_channel(cap);
+
+        match output {
+            (port, true) => port.connect(ProduceSendAdapter {
+                idx,
+                signals: self.signals.clone(),
+                inner: sender,
+            }),
+            (port, false) => port.connect(sender),
+        }
+
+        match input {
+            (port, true) => port.connect(ConsumeRecvAdapter {
+                idx,
+                signals: self.signals.clone(),
+                inner: receiver,
+                busy: false,
+            }),
+            (port, false) => port.connect(receiver),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+struct Progress {
+    /// Events that entered the branch and weren't acked or dropped yet
+    pending: usize,
+    /// The latest point the router reported for this sink
+    latest: Option<Point>,
+    safe: Option<Point>,
+}
+
+impl Progress {
+    fn is_idle(&self) -> bool {
+        self.pending == 0
+    }
+
+    fn apply(&mut self, signal: &Signal) {
+        match signal {
+            Signal::Ack(x) => {
+                self.pending = self.pending.saturating_sub(1);
+                self.safe = Some(x.clone());
+            }
+            Signal::Dispatch(x) => {
+                self.pending += 1;
+                self.latest = Some(x.clone());
+            }
+            Signal::Skip(x) => {
+                // a skip only becomes safe once there's nothing in-flight
+                // for this sink, otherwise we need to wait for the acks
+                self.latest = Some(x.clone());
+            }
+            Signal::Produce => self.pending += 1,
+            Signal::Consume => self.pending = self.pending.saturating_sub(1),
+        }
+
+        if self.is_idle() && self.latest.is_some() {
+            self.safe = self.latest.clone();
+        }
+    }
+}
+
+/// Returns the point that is safe for all sinks, if any
+fn find_committed(sinks: &[Progress]) -> Option<Point> {
+    let mut committed: Option<&Point> = None;
+
+    for sink in sinks {
+        let point = sink.safe.as_ref()?;
+
+        committed = match committed {
+            Some(x) if x.slot_or_default() <= point.slot_or_default() => Some(x),
+            _ => Some(point),
+        };
+    }
+
+    committed.cloned()
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Self)
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<(SinkIdx, Signal)>, WorkerError> {
+        let msg = stage.input.recv().await.or_panic()?;
+        Ok(WorkSchedule::Unit(msg.payload))
+    }
+
+    async fn execute(
+        &mut self,
+        unit: &(SinkIdx, Signal),
+        stage: &mut Stage,
+    ) -> Result<(), WorkerError> {
+        let (idx, signal) = unit;
+
+        let progress = stage
+            .sinks
+            .get_mut(*idx)
+            .ok_or_else(|| Error::custom(format!("unknown sink index {idx}")))
+            .or_panic()?;
+
+        progress.apply(signal);
+
+        stage.ops_count.inc(1);
+
+        let committed = find_committed(&stage.sinks);
+
+        if committed.is_some() && committed != stage.committed {
+            let point = committed.clone().unwrap();
+            stage.committed_slot.set(point.slot_or_default() as i64);
+            stage.output.send(point.into()).await.or_panic()?;
+            stage.committed = committed;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default, Stage)]
+#[stage(name = "cursor-fanin", unit = "(SinkIdx, Signal)", worker = "Worker")]
+pub struct Stage {
+    sinks: Vec<Progress>,
+    committed: Option<Point>,
+
+    pub input: InputPort<(SinkIdx, Signal)>,
+    pub output: OutputPort<Point>,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    committed_slot: gasket::metrics::Gauge,
+}
+
+impl Stage {
+    /// Connects the cursor port of each sink to this stage
+    ///
+    /// The order of the ports defines the index used to track each sink. If
+    /// the sinks sit behind a router, its signal port needs to be connected
+    /// too, using the same indexes for each sink, and the returned tracker
+    /// used to connect the stages of each branch.
+    pub fn connect_sinks(
+        &mut self,
+        ports: Vec<&mut SinkCursorPort>,
+        router: Option<&mut SignalPort>,
+        cap: usize,
+    ) -> BranchTracker {
+        let (sender, receiver) = mpsc_channel(cap);
+        self.input.connect(receiver);
+
+        self.sinks = vec![Progress::default(); ports.len()];
+
+        for (idx, port) in ports.into_iter().enumerate() {
+            port.connect(TaggedSendAdapter {
+                idx,
+                inner: sender.clone(),
+            });
+        }
+
+        if let Some(router) = router {
+            router.connect(sender.clone());
+        }
+
+        BranchTracker { signals: sender }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    fn apply(sinks: &mut [Progress], idx: SinkIdx, signal: Signal) -> Option<u64> {
+        sinks[idx].apply(&signal);
+        find_committed(sinks).map(|x| x.slot_or_default())
+    }
+
+    #[test]
+    fn waits_for_every_sink() {
+        let mut sinks = vec![Progress::default(); 3];
+
+        assert_eq!(apply(&mut sinks, 0, Signal::Ack(point(5))), None);
+        assert_eq!(apply(&mut sinks, 1, Signal::Ack(point(3))), None);
+        assert_eq!(apply(&mut sinks, 2, Signal::Ack(point(4))), Some(3));
+    }
+
+    #[test]
+    fn commits_the_oldest_ack() {
+        let mut sinks = vec![Progress::default(); 2];
+
+        apply(&mut sinks, 0, Signal::Ack(point(1)));
+        assert_eq!(apply(&mut sinks, 1, Signal::Ack(point(1))), Some(1));
+
+        // a fast sink doesn't move the cursor past the slow one
+        assert_eq!(apply(&mut sinks, 0, Signal::Ack(point(2))), Some(1));
+        assert_eq!(apply(&mut sinks, 0, Signal::Ack(point(3))), Some(1));
+        assert_eq!(apply(&mut sinks, 1, Signal::Ack(point(2))), Some(2));
+        assert_eq!(apply(&mut sinks, 1, Signal::Ack(point(3))), Some(3));
+    }
+
+    #[test]
+    fn follows_rollbacks() {
+        let mut sinks = vec![Progress::default(); 2];
+
+        apply(&mut sinks, 0, Signal::Ack(point(5)));
+        apply(&mut sinks, 1, Signal::Ack(point(5)));
+
+        // once every sink processed the rollback, the cursor moves back
+        assert_eq!(apply(&mut sinks, 0, Signal::Ack(point(2))), Some(2));
+        assert_eq!(apply(&mut sinks, 1, Signal::Ack(point(2))), Some(2));
+    }
+
+    #[test]
+    fn skips_idle_branches() {
+        let mut sinks = vec![Progress::default(); 2];
+
+        apply(&mut sinks, 0, Signal::Dispatch(point(1)));
+        assert_eq!(apply(&mut sinks, 1, Signal::Skip(point(1))), None);
+        assert_eq!(apply(&mut sinks, 0, Signal::Ack(point(1))), Some(1));
+
+        // the skip only counts once the in-flight event is acked
+        apply(&mut sinks, 0, Signal::Skip(point(2)));
+        apply(&mut sinks, 1, Signal::Dispatch(point(2)));
+        assert_eq!(apply(&mut sinks, 0, Signal::Skip(point(3))), Some(1));
+        assert_eq!(apply(&mut sinks, 1, Signal::Skip(point(3))), Some(1));
+        assert_eq!(apply(&mut sinks, 1, Signal::Ack(point(2))), Some(3));
+    }
+
+    #[test]
+    fn settles_dropped_dispatches() {
+        let mut sinks = vec![Progress::default(); 2];
+
+        apply(&mut sinks, 1, Signal::Skip(point(1)));
+
+        // a filter of the branch drops the event
+        apply(&mut sinks, 0, Signal::Dispatch(point(1)));
+        assert_eq!(apply(&mut sinks, 0, Signal::Consume), Some(1));
+
+        apply(&mut sinks, 0, Signal::Dispatch(point(2)));
+        apply(&mut sinks, 1, Signal::Skip(point(2)));
+        assert_eq!(apply(&mut sinks, 0, Signal::Skip(point(3))), Some(1));
+        assert_eq!(apply(&mut sinks, 0, Signal::Consume), Some(2));
+    }
+
+    #[test]
+    fn waits_for_split_events() {
+        let mut sinks = vec![Progress::default()];
+
+        // a filter of the branch turns the event into two
+        apply(&mut sinks, 0, Signal::Dispatch(point(1)));
+        apply(&mut sinks, 0, Signal::Produce);
+        apply(&mut sinks, 0, Signal::Produce);
+        apply(&mut sinks, 0, Signal::Consume);
+
+        assert_eq!(apply(&mut sinks, 0, Signal::Ack(point(1))), Some(1));
+        assert_eq!(apply(&mut sinks, 0, Signal::Skip(point(2))), Some(1));
+        assert_eq!(apply(&mut sinks, 0, Signal::Ack(point(1))), Some(2));
+    }
+
+    #[test]
+    fn tracks_branch_filters() {
+        let mut stage = Stage::default();
+        let mut cursor = SinkCursorPort::default();
+        let tracker = stage.connect_sinks(vec![&mut cursor], None, 10);
+
+        let mut router = FilterOutputPort::default();
+        let mut filter_in = FilterInputPort::default();
+        let mut filter_out = FilterOutputPort::default();
+        let mut sink = FilterInputPort::default();
+
+        tracker.connect(0, (&mut router, false), (&mut filter_in, true), 10);
+        tracker.connect(0, (&mut filter_out, true), (&mut sink, false), 10);
+
+        let signals = futures::executor::block_on(async {
+            let event = ChainEvent::Reset(point(1));
+            router.send(event.clone().into()).await.unwrap();
+            router.send(event.clone().into()).await.unwrap();
+
+            // the filter forwards the first event and drops the second
+            filter_in.recv().await.unwrap();
+            filter_out.send(event.into()).await.unwrap();
+            filter_in.recv().await.unwrap();
+            sink.recv().await.unwrap();
+
+            let mut signals = vec![];
+
+            for _ in 0..2 {
+                let (_, signal) = stage.input.recv().await.unwrap().payload;
+                signals.push(signal);
+            }
+
+            signals
+        });
+
+        assert!(matches!(signals[..], [Signal::Produce, Signal::Consume]));
+    }
+}
@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use gasket::framework::*;
 use pallas::network::miniprotocols::Point;
@@ -7,6 +8,8 @@ use tokio::select;
 
 use crate::framework::*;
 
+use super::SharedBreadcrumbs;
+
 fn breadcrumbs_to_data(crumbs: &Breadcrumbs) -> Vec<(u64, String)> {
     crumbs
         .points()
@@ -35,21 +38,53 @@ pub enum Unit {
     Flush,
 }
 
-#[derive(Default)]
-pub struct Worker {}
+pub struct Worker {
+    path: PathBuf,
+    breadcrumbs: SharedBreadcrumbs,
+    drained: bool,
+}
+
+impl Worker {
+    fn flush(&self) -> Result<(), WorkerError> {
+        let file = std::fs::File::options()
+            .write(true)
+            .create(true)
+            .append(false)
+            .truncate(true)
+            .open(&self.path)
+            .or_panic()?;
+
+        let data = breadcrumbs_to_data(&self.breadcrumbs.lock().unwrap());
+        serde_json::to_writer_pretty(&file, &data).or_panic()?;
+
+        Ok(())
+    }
+}
 
 #[async_trait::async_trait(?Send)]
 impl gasket::framework::Worker<Stage> for Worker {
-    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
-        Ok(Default::default())
+    async fn bootstrap(stage: &Stage) -> Result<Self, WorkerError> {
+        Ok(Self {
+            path: stage.path.clone(),
+            breadcrumbs: stage.breadcrumbs.clone(),
+            drained: false,
+        })
     }
 
     async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Unit>, WorkerError> {
+        if self.drained {
+            return Ok(WorkSchedule::Done);
+        }
+
         select! {
-            msg = stage.track.recv() => {
-                let msg = msg.or_panic()?;
-                Ok(WorkSchedule::Unit(Unit::Track(msg.payload)))
-            }
+            msg = stage.track.recv() => match msg {
+                Ok(msg) => Ok(WorkSchedule::Unit(Unit::Track(msg.payload))),
+                // the upstream stages are gone, persist what we have and finish
+                Err(_) => {
+                    self.drained = true;
+                    Ok(WorkSchedule::Unit(Unit::Flush))
+                }
+            },
             msg = stage.flush.recv() => {
                 msg.or_panic()?;
                 Ok(WorkSchedule::Unit(Unit::Flush))
@@ -59,23 +94,18 @@ impl gasket::framework::Worker<Stage> for Worker {
 
     async fn execute(&mut self, unit: &Unit, stage: &mut Stage) -> Result<(), WorkerError> {
         match unit {
-            Unit::Track(x) => stage.breadcrumbs.track(x.clone()),
-            Unit::Flush => {
-                let file = std::fs::File::options()
-                    .write(true)
-                    .create(true)
-                    .append(false)
-                    .truncate(true)
-                    .open(&stage.path)
-                    .or_panic()?;
-
-                let data = breadcrumbs_to_data(&stage.breadcrumbs);
-                serde_json::to_writer_pretty(&file, &data).or_panic()?;
-            }
+            Unit::Track(x) => stage.breadcrumbs.lock().unwrap().track(x.clone()),
+            Unit::Flush => self.flush()?,
         }
 
         Ok(())
     }
+
+    /// Persists the latest points once more, in case the stage is dismissed
+    /// before the next flush interval
+    async fn teardown(&mut self) -> Result<(), WorkerError> {
+        self.flush()
+    }
 }
 
 #[derive(Stage)]
@@ -83,7 +113,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 pub struct Stage {
     path: std::path::PathBuf,
 
-    breadcrumbs: Breadcrumbs,
+    pub(super) breadcrumbs: SharedBreadcrumbs,
 
     pub track: gasket::messaging::InputPort<Point>,
 
@@ -141,7 +171,7 @@ impl Config {
 
         let stage = Stage {
             path: self.define_path()?,
-            breadcrumbs: ctx.breadcrumbs.clone(),
+            breadcrumbs: Arc::new(Mutex::new(ctx.breadcrumbs.clone())),
             tracked_slot: Default::default(),
             flush_count: Default::default(),
             track: Default::default(),
@@ -1,9 +1,12 @@
 use gasket::framework::*;
 use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
+use std::sync::{Arc, Mutex};
 
 use crate::framework::*;
 
+use super::SharedBreadcrumbs;
+
 #[derive(Default)]
 pub struct Worker {}
 
@@ -19,7 +22,7 @@ impl gasket::framework::Worker<Stage> for Worker {
     }
 
     async fn execute(&mut self, unit: &Point, stage: &mut Stage) -> Result<(), WorkerError> {
-        stage.breadcrumbs.track(unit.clone());
+        stage.breadcrumbs.lock().unwrap().track(unit.clone());
         Ok(())
     }
 }
@@ -27,7 +30,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "cursor", unit = "Point", worker = "Worker")]
 pub struct Stage {
-    breadcrumbs: Breadcrumbs,
+    pub(super) breadcrumbs: SharedBreadcrumbs,
 
     pub track: gasket::messaging::InputPort<Point>,
 
@@ -45,7 +48,7 @@ impl Config {
 
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
-            breadcrumbs: ctx.breadcrumbs.clone(),
+            breadcrumbs: Arc::new(Mutex::new(ctx.breadcrumbs.clone())),
             tracked_slot: Default::default(),
             track: Default::default(),
         };
@@ -1,9 +1,11 @@
 use gasket::{messaging::InputPort, runtime::Tether};
 use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
+use std::sync::{Arc, Mutex};
 
 use crate::framework::*;
 
+pub mod fanin;
 pub mod file;
 pub mod memory;
 
@@ -12,6 +14,9 @@ mod redis;
 
 pub type MaxBreadcrums = usize;
 
+/// Breadcrumbs of a cursor stage, readable while the stage is running
+pub type SharedBreadcrumbs = Arc<Mutex<Breadcrumbs>>;
+
 pub enum Bootstrapper {
     Memory(memory::Stage),
     File(file::Stage),
@@ -31,6 +36,17 @@ impl Bootstrapper {
         }
     }
 
+    /// Handle to the points tracked by the stage, kept up to date once spawned
+    pub fn breadcrumbs(&self) -> SharedBreadcrumbs {
+        match self {
+            Bootstrapper::Memory(x) => x.breadcrumbs.clone(),
+            Bootstrapper::File(x) => x.breadcrumbs.clone(),
+
+            #[cfg(feature = "redis")]
+            Bootstrapper::Redis(x) => x.breadcrumbs.clone(),
+        }
+    }
+
     pub fn spawn(self, policy: gasket::runtime::Policy) -> Tether {
         match self {
             Bootstrapper::Memory(x) => gasket::runtime::spawn_stage(x, policy),
@@ -6,11 +6,14 @@ use r2d2_redis::{
     RedisConnectionManager,
 };
 use serde::Deserialize;
+use std::sync::{Arc, Mutex};
 use tokio::select;
 use tracing::debug;
 
 use crate::framework::*;
 
+use super::SharedBreadcrumbs;
+
 fn breadcrumbs_to_data(crumbs: &Breadcrumbs) -> Vec<(u64, String)> {
     crumbs
         .points()
@@ -42,6 +45,22 @@ pub enum Unit {
 pub struct Worker {
     pool: Pool<RedisConnectionManager>,
     key: String,
+    breadcrumbs: SharedBreadcrumbs,
+    drained: bool,
+}
+
+impl Worker {
+    fn flush(&self) -> Result<(), WorkerError> {
+        let data = breadcrumbs_to_data(&self.breadcrumbs.lock().unwrap());
+        let mut conn = self.pool.get().or_restart()?;
+
+        let data_to_write = serde_json::to_string(&data).or_panic()?;
+        conn.set::<_, _, ()>(&self.key, &data_to_write)
+            .map_err(Error::custom)
+            .or_panic()?;
+
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait(?Send)]
@@ -53,15 +72,25 @@ impl gasket::framework::Worker<Stage> for Worker {
         Ok(Self {
             pool,
             key: stage.key.clone(),
+            breadcrumbs: stage.breadcrumbs.clone(),
+            drained: false,
         })
     }
 
     async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<Unit>, WorkerError> {
+        if self.drained {
+            return Ok(WorkSchedule::Done);
+        }
+
         select! {
-            msg = stage.track.recv() => {
-                let msg = msg.or_panic()?;
-                Ok(WorkSchedule::Unit(Unit::Track(msg.payload)))
-            }
+            msg = stage.track.recv() => match msg {
+                Ok(msg) => Ok(WorkSchedule::Unit(Unit::Track(msg.payload))),
+                // the upstream stages are gone, persist what we have and finish
+                Err(_) => {
+                    self.drained = true;
+                    Ok(WorkSchedule::Unit(Unit::Flush))
+                }
+            },
             msg = stage.flush.recv() => {
                 msg.or_panic()?;
                 Ok(WorkSchedule::Unit(Unit::Flush))
@@ -71,20 +100,18 @@ impl gasket::framework::Worker<Stage> for Worker {
 
     async fn execute(&mut self, unit: &Unit, stage: &mut Stage) -> Result<(), WorkerError> {
         match unit {
-            Unit::Track(x) => stage.breadcrumbs.track(x.clone()),
-            Unit::Flush => {
-                let data = breadcrumbs_to_data(&stage.breadcrumbs);
-                let mut conn = self.pool.get().or_restart()?;
-
-                let data_to_write = serde_json::to_string(&data).or_panic()?;
-                conn.set(&self.key, &data_to_write)
-                    .map_err(Error::custom)
-                    .or_panic()?;
-            }
+            Unit::Track(x) => stage.breadcrumbs.lock().unwrap().track(x.clone()),
+            Unit::Flush => self.flush()?,
         }
 
         Ok(())
     }
+
+    /// Persists the latest points once more, in case the stage is dismissed
+    /// before the next flush interval
+    async fn teardown(&mut self) -> Result<(), WorkerError> {
+        self.flush()
+    }
 }
 
 #[derive(Stage)]
@@ -93,7 +120,7 @@ pub struct Stage {
     key: String,
     url: String,
 
-    breadcrumbs: Breadcrumbs,
+    pub(super) breadcrumbs: SharedBreadcrumbs,
 
     pub track: gasket::messaging::InputPort<Point>,
 
@@ -151,7 +178,7 @@ impl Config {
         let stage = Stage {
             key: self.key.clone(),
             url: self.url.clone(),
-            breadcrumbs: ctx.breadcrumbs.clone(),
+            breadcrumbs: Arc::new(Mutex::new(ctx.breadcrumbs.clone())),
             tracked_slot: Default::default(),
             flush_count: Default::default(),
             track: Default::default(),
@@ -0,0 +1,393 @@
+//! A filter that holds events until their block is buried deep enough
+//!
+//! Events are queued by block and only released once the chain has advanced
+//! a given number of blocks past them. Filters upstream might drop the events
+//! of most blocks, so the advance of the chain is measured against the blocks
+//! that the source sent downstream, see [`ChainTip`]. Rollbacks that don't go deeper than
+//! the queue just drop the affected events, so downstream stages never see
+//! them. The same goes for undo events of blocks that are still held, they
+//! cancel the held events instead of being forwarded.
+
+use gasket::framework::*;
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::framework::*;
+
+/// Derives the security parameter (k) of the chain from its genesis values
+///
+/// The shelley epoch length is `10k / f` and every public network uses an
+/// active slot coefficient (f) of 0.05.
+fn security_param(chain: &GenesisValues) -> usize {
+    chain.shelley_epoch_length as usize / 200
+}
+
+/// How long to wait for new events before checking the tip of the chain again
+const RELEASE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct Queue {
+    blocks: VecDeque<(Point, Vec<ChainEvent>)>,
+    released: Option<Point>,
+}
+
+impl Queue {
+    fn push(&mut self, point: &Point, event: ChainEvent) {
+        match self.blocks.back_mut() {
+            Some((last, events)) if last == point => events.push(event),
+            _ => self.blocks.push_back((point.clone(), vec![event])),
+        }
+    }
+
+    /// Pops the blocks that are buried deeper than the required depth
+    ///
+    /// A block is buried once the source sent enough blocks after it, whether
+    /// those blocks carried any event or not. The held blocks are a lower
+    /// bound of that, for when the source doesn't record its blocks.
+    fn release(&mut self, depth: usize, tip: &ChainTip) -> Vec<ChainEvent> {
+        let mut out = vec![];
+
+        while let Some((oldest, _)) = self.blocks.front() {
+            let buried =
+                self.blocks.len() > depth || tip.depth_of(oldest).is_some_and(|x| x >= depth);
+
+            if !buried {
+                break;
+            }
+
+            let (point, events) = self.blocks.pop_front().unwrap();
+            out.extend(events);
+            self.released = Some(point);
+        }
+
+        out
+    }
+
+    /// Drops the latest held event of the block, if the block is held
+    ///
+    /// Undo events come in the reverse order of the events they undo, so the
+    /// latest event of the block is the one being undone.
+    fn cancel(&mut self, point: &Point) -> bool {
+        let Some(idx) = self.blocks.iter().position(|(x, _)| x == point) else {
+            return false;
+        };
+
+        let (_, events) = &mut self.blocks[idx];
+        events.pop();
+
+        if events.is_empty() {
+            self.blocks.remove(idx);
+        }
+
+        true
+    }
+
+    /// Drops the queued blocks newer than the rollback point
+    ///
+    /// Returns true if the rollback goes further back than the events that
+    /// were already released and needs to be propagated downstream. In that
+    /// case, the rollback point becomes the latest released one.
+    fn rollback(&mut self, point: &Point) -> bool {
+        let was_empty = self.blocks.is_empty();
+
+        while let Some((last, _)) = self.blocks.back() {
+            if last.slot_or_default() <= point.slot_or_default() {
+                break;
+            }
+
+            self.blocks.pop_back();
+        }
+
+        let propagate = match &self.released {
+            Some(released) => released.slot_or_default() > point.slot_or_default(),
+            // nothing was ever released, this is the initial intersection
+            None => was_empty,
+        };
+
+        if propagate {
+            self.released = Some(point.clone());
+        }
+
+        propagate
+    }
+
+    fn held_events(&self) -> usize {
+        self.blocks.iter().map(|(_, x)| x.len()).sum()
+    }
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Default::default())
+    }
+
+    /// Schedules the next event, or a release of the held blocks if no event
+    /// arrives for a while, since the chain might have advanced regardless
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<Option<ChainEvent>>, WorkerError> {
+        if stage.queue.blocks.is_empty() {
+            let msg = stage.input.recv().await.or_panic()?;
+            return Ok(WorkSchedule::Unit(Some(msg.payload)));
+        }
+
+        match tokio::time::timeout(RELEASE_INTERVAL, stage.input.recv()).await {
+            Ok(msg) => Ok(WorkSchedule::Unit(Some(msg.or_panic()?.payload))),
+            Err(_) => Ok(WorkSchedule::Unit(None)),
+        }
+    }
+
+    async fn execute(
+        &mut self,
+        unit: &Option<ChainEvent>,
+        stage: &mut Stage,
+    ) -> Result<(), WorkerError> {
+        match unit {
+            Some(ChainEvent::Apply(point, record)) => {
+                let event = ChainEvent::Apply(point.clone(), record.clone());
+                stage.queue.push(point, event);
+                stage.release().await?;
+            }
+            Some(unit @ ChainEvent::Undo(point, _)) => {
+                if stage.queue.cancel(point) {
+                    stage.dropped_count.inc(1);
+                } else {
+                    stage.output.send(unit.clone().into()).await.or_panic()?;
+                }
+            }
+            Some(unit @ ChainEvent::Reset(point)) => {
+                let dropped = stage.queue.held_events();
+                let propagate = stage.queue.rollback(point);
+                let dropped = dropped - stage.queue.held_events();
+
+                stage.dropped_count.inc(dropped as u64);
+
+                if propagate {
+                    if stage.queue.released.is_some() {
+                        warn!(?point, "rollback deeper than confirmation depth");
+                    }
+
+                    stage.output.send(unit.clone().into()).await.or_panic()?;
+                }
+            }
+            None => stage.release().await?,
+        }
+
+        stage.held_depth.set(stage.queue.blocks.len() as i64);
+        stage.held_events.set(stage.queue.held_events() as i64);
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
+
+#[derive(Stage)]
+#[stage(
+    name = "filter-confirmation-depth",
+    unit = "Option<ChainEvent>",
+    worker = "Worker"
+)]
+pub struct Stage {
+    depth: usize,
+    queue: Queue,
+    tip: ChainTip,
+
+    pub input: FilterInputPort,
+    pub output: FilterOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    dropped_count: gasket::metrics::Counter,
+
+    #[metric]
+    held_depth: gasket::metrics::Gauge,
+
+    #[metric]
+    held_events: gasket::metrics::Gauge,
+}
+
+impl Stage {
+    async fn release(&mut self) -> Result<(), WorkerError> {
+        for event in self.queue.release(self.depth, &self.tip) {
+            self.output.send(event.into()).await.or_panic()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default, Deserialize)]
+pub struct Config {
+    /// Number of blocks that need to be built on top of a block before its
+    /// events are released. Defaults to the security parameter of the chain.
+    pub depth: Option<usize>,
+}
+
+impl Config {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let depth = self
+            .depth
+            .unwrap_or_else(|| security_param(&ctx.chain.clone().into()));
+
+        ctx.tip.keep(depth);
+
+        let stage = Stage {
+            depth,
+            queue: Default::default(),
+            tip: ctx.tip.clone(),
+            input: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+            dropped_count: Default::default(),
+            held_depth: Default::default(),
+            held_events: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    fn apply(queue: &mut Queue, slot: u64, depth: usize) -> Vec<u64> {
+        let event = ChainEvent::Apply(point(slot), Record::GenericJson(slot.into()));
+        queue.push(&point(slot), event);
+
+        queue
+            .release(depth, &ChainTip::default())
+            .iter()
+            .map(|x| x.point().slot_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn releases_after_depth() {
+        let mut queue = Queue::default();
+
+        assert!(queue.rollback(&point(0)));
+
+        assert!(apply(&mut queue, 1, 2).is_empty());
+        assert!(apply(&mut queue, 2, 2).is_empty());
+        assert_eq!(apply(&mut queue, 3, 2), vec![1]);
+        assert_eq!(apply(&mut queue, 4, 2), vec![2]);
+    }
+
+    #[test]
+    fn releases_against_the_tip() {
+        let mut queue = Queue::default();
+        let tip = ChainTip::default();
+        tip.keep(3);
+
+        let mut push = |slot: u64| {
+            let event = ChainEvent::Apply(point(slot), Record::GenericJson(slot.into()));
+            queue.push(&point(slot), event);
+        };
+
+        // only blocks 2 and 9 carry events, the rest were dropped upstream
+        push(2);
+        push(9);
+
+        let released = |queue: &mut Queue, tip: &ChainTip| -> Vec<u64> {
+            queue
+                .release(3, tip)
+                .iter()
+                .map(|x| x.point().slot_or_default())
+                .collect()
+        };
+
+        for slot in 1..=10 {
+            tip.apply(&point(slot));
+        }
+
+        assert_eq!(released(&mut queue, &tip), vec![2]);
+
+        tip.apply(&point(11));
+        assert!(released(&mut queue, &tip).is_empty());
+
+        // the chain advances without any new event reaching the filter
+        tip.apply(&point(12));
+        assert_eq!(released(&mut queue, &tip), vec![9]);
+    }
+
+    #[test]
+    fn holds_rolled_back_blocks() {
+        let mut queue = Queue::default();
+        let tip = ChainTip::default();
+        tip.keep(2);
+
+        for slot in 1..=5 {
+            tip.apply(&point(slot));
+        }
+
+        // the source rolled back before the block reached the filter
+        tip.rollback(&point(3));
+
+        let event = ChainEvent::Apply(point(4), Record::GenericJson(4.into()));
+        queue.push(&point(4), event);
+
+        assert!(queue.release(2, &tip).is_empty());
+    }
+
+    #[test]
+    fn absorbs_shallow_rollbacks() {
+        let mut queue = Queue::default();
+
+        apply(&mut queue, 1, 2);
+        apply(&mut queue, 2, 2);
+        apply(&mut queue, 3, 2);
+
+        assert!(!queue.rollback(&point(2)));
+        assert_eq!(queue.held_events(), 1);
+
+        assert!(queue.rollback(&point(0)));
+        assert_eq!(queue.held_events(), 0);
+    }
+
+    #[test]
+    fn cancels_held_events() {
+        let mut queue = Queue::default();
+
+        apply(&mut queue, 1, 2);
+        apply(&mut queue, 2, 2);
+        apply(&mut queue, 3, 2);
+
+        // the block was released already, the undo needs to go downstream
+        assert!(!queue.cancel(&point(1)));
+
+        assert!(queue.cancel(&point(3)));
+        assert_eq!(queue.held_events(), 1);
+        assert_eq!(apply(&mut queue, 4, 2), Vec::<u64>::new());
+        assert_eq!(apply(&mut queue, 5, 2), vec![2]);
+    }
+
+    #[test]
+    fn resets_released_on_deep_rollbacks() {
+        let mut queue = Queue::default();
+
+        for slot in 1..=5 {
+            apply(&mut queue, slot, 2);
+        }
+
+        assert!(queue.rollback(&point(1)));
+
+        // blocks 2 and 3 were undone downstream, nothing newer than 1 is out
+        apply(&mut queue, 2, 2);
+        assert!(!queue.rollback(&point(1)));
+    }
+}
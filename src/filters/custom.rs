@@ -0,0 +1,90 @@
+//! A filter stage that wraps user-supplied filter logic
+//!
+//! Applications embedding oura as a library can implement the [`Filter`]
+//! trait and plug it into a pipeline through `pipeline::Builder`. This stage
+//! takes care of the gasket plumbing so that implementors only need to deal
+//! with events.
+
+use gasket::framework::*;
+
+use crate::framework::dead_letter::DeadLetters;
+use crate::framework::*;
+
+/// Logic of a custom filter
+///
+/// Each event that reaches the stage is handed to `apply` and the returned
+/// events are sent downstream, in order. Returning an empty vec drops the
+/// event. Returning an error stops the pipeline, unless the pipeline has a
+/// dead-letter sink, in which case the event is sent there instead.
+///
+/// Implementations need to use `#[async_trait::async_trait(?Send)]`.
+#[async_trait::async_trait(?Send)]
+pub trait Filter: Send {
+    async fn apply(&mut self, event: ChainEvent) -> Result<Vec<ChainEvent>, Error>;
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Default::default())
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        let msg = stage.input.recv().await.or_panic()?;
+
+        Ok(WorkSchedule::Unit(msg.payload))
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let out = match stage.filter.apply(unit.clone()).await {
+            Ok(x) => x,
+            Err(err) => {
+                stage
+                    .dead_letters
+                    .divert("filter-custom", err, unit)
+                    .await?;
+
+                return Ok(());
+            }
+        };
+
+        for event in out {
+            stage.output.send(event.into()).await.or_panic()?;
+        }
+
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
+
+#[derive(Stage)]
+#[stage(name = "filter-custom", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    filter: Box<dyn Filter>,
+    pub(crate) dead_letters: DeadLetters,
+
+    pub input: FilterInputPort,
+    pub output: FilterOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+}
+
+impl Stage {
+    pub fn new(filter: impl Filter + 'static) -> Self {
+        Self {
+            filter: Box::new(filter),
+            dead_letters: Default::default(),
+            input: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+        }
+    }
+}
@@ -1,12 +1,17 @@
 use gasket::runtime::Tether;
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
+pub mod confirmation_depth;
+pub mod custom;
 pub mod into_json;
 pub mod legacy_v1;
 pub mod noop;
 pub mod parse_cbor;
+pub mod rollback_buffer;
+pub mod route;
 pub mod select;
 pub mod split_block;
 
@@ -15,11 +20,14 @@ pub mod wasm_plugin;
 
 pub enum Bootstrapper {
     Noop(noop::Stage),
+    ConfirmationDepth(confirmation_depth::Stage),
     SplitBlock(split_block::Stage),
     IntoJson(into_json::Stage),
     LegacyV1(legacy_v1::Stage),
     ParseCbor(parse_cbor::Stage),
+    RollbackBuffer(rollback_buffer::Stage),
     Select(select::Stage),
+    Custom(custom::Stage),
 
     #[cfg(feature = "wasm")]
     WasmPlugin(wasm_plugin::Stage),
@@ -29,11 +37,14 @@ impl Bootstrapper {
     pub fn borrow_input(&mut self) -> &mut FilterInputPort {
         match self {
             Bootstrapper::Noop(p) => &mut p.input,
+            Bootstrapper::ConfirmationDepth(p) => &mut p.input,
             Bootstrapper::SplitBlock(p) => &mut p.input,
             Bootstrapper::IntoJson(p) => &mut p.input,
             Bootstrapper::LegacyV1(p) => &mut p.input,
             Bootstrapper::ParseCbor(p) => &mut p.input,
+            Bootstrapper::RollbackBuffer(p) => &mut p.input,
             Bootstrapper::Select(p) => &mut p.input,
+            Bootstrapper::Custom(p) => &mut p.input,
 
             #[cfg(feature = "wasm")]
             Bootstrapper::WasmPlugin(p) => &mut p.input,
@@ -43,25 +54,49 @@ impl Bootstrapper {
     pub fn borrow_output(&mut self) -> &mut FilterOutputPort {
         match self {
             Bootstrapper::Noop(p) => &mut p.output,
+            Bootstrapper::ConfirmationDepth(p) => &mut p.output,
             Bootstrapper::SplitBlock(p) => &mut p.output,
             Bootstrapper::IntoJson(p) => &mut p.output,
             Bootstrapper::LegacyV1(p) => &mut p.output,
             Bootstrapper::ParseCbor(p) => &mut p.output,
+            Bootstrapper::RollbackBuffer(p) => &mut p.output,
             Bootstrapper::Select(p) => &mut p.output,
+            Bootstrapper::Custom(p) => &mut p.output,
 
             #[cfg(feature = "wasm")]
             Bootstrapper::WasmPlugin(p) => &mut p.output,
         }
     }
 
+    /// Whether the stage holds on to events instead of processing them as
+    /// they come
+    pub fn holds_events(&self) -> bool {
+        matches!(self, Bootstrapper::ConfirmationDepth(_))
+    }
+
+    /// Hands the dead-letter handle to stages that were built outside of the
+    /// pipeline
+    pub(crate) fn with_dead_letters(self, handle: &DeadLetters) -> Self {
+        match self {
+            Bootstrapper::Custom(mut x) => {
+                x.dead_letters = handle.clone();
+                Bootstrapper::Custom(x)
+            }
+            x => x,
+        }
+    }
+
     pub fn spawn(self, policy: gasket::runtime::Policy) -> Tether {
         match self {
             Bootstrapper::Noop(x) => gasket::runtime::spawn_stage(x, policy),
+            Bootstrapper::ConfirmationDepth(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::SplitBlock(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::IntoJson(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::LegacyV1(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::ParseCbor(x) => gasket::runtime::spawn_stage(x, policy),
+            Bootstrapper::RollbackBuffer(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::Select(x) => gasket::runtime::spawn_stage(x, policy),
+            Bootstrapper::Custom(x) => gasket::runtime::spawn_stage(x, policy),
 
             #[cfg(feature = "wasm")]
             Bootstrapper::WasmPlugin(x) => gasket::runtime::spawn_stage(x, policy),
@@ -73,10 +108,12 @@ impl Bootstrapper {
 #[serde(tag = "type")]
 pub enum Config {
     Noop(noop::Config),
+    ConfirmationDepth(confirmation_depth::Config),
     SplitBlock(split_block::Config),
     IntoJson(into_json::Config),
     LegacyV1(legacy_v1::Config),
     ParseCbor(parse_cbor::Config),
+    RollbackBuffer(rollback_buffer::Config),
     Select(select::Config),
 
     #[cfg(feature = "wasm")]
@@ -87,10 +124,14 @@ impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Bootstrapper, Error> {
         match self {
             Config::Noop(c) => Ok(Bootstrapper::Noop(c.bootstrapper(ctx)?)),
+            Config::ConfirmationDepth(c) => {
+                Ok(Bootstrapper::ConfirmationDepth(c.bootstrapper(ctx)?))
+            }
             Config::SplitBlock(c) => Ok(Bootstrapper::SplitBlock(c.bootstrapper(ctx)?)),
             Config::IntoJson(c) => Ok(Bootstrapper::IntoJson(c.bootstrapper(ctx)?)),
             Config::LegacyV1(c) => Ok(Bootstrapper::LegacyV1(c.bootstrapper(ctx)?)),
             Config::ParseCbor(c) => Ok(Bootstrapper::ParseCbor(c.bootstrapper(ctx)?)),
+            Config::RollbackBuffer(c) => Ok(Bootstrapper::RollbackBuffer(c.bootstrapper(ctx)?)),
             Config::Select(c) => Ok(Bootstrapper::Select(c.bootstrapper(ctx)?)),
 
             #[cfg(feature = "wasm")]
@@ -99,6 +140,20 @@ impl Config {
     }
 }
 
+/// Checks that the filters work with the records they receive, returning
+/// whether the records are parsed after them
+pub(crate) fn check_parsed(filters: &[Config], mut parsed: bool) -> Result<bool, Error> {
+    for filter in filters {
+        match filter {
+            Config::ParseCbor(_) => parsed = true,
+            Config::Select(x) if parsed => x.check_parsed()?,
+            _ => (),
+        }
+    }
+
+    Ok(parsed)
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config::LegacyV1(Default::default())
@@ -0,0 +1,213 @@
+//! A filter that turns raw cbor blocks and txs into the corresponding parsed
+//! representation
+
+use gasket::framework::*;
+use serde::Deserialize;
+
+use pallas::interop::utxorpc::{self as interop};
+use pallas::ledger::traverse as trv;
+
+use crate::framework::*;
+
+#[cfg(feature = "utxo-store")]
+pub mod utxo_store;
+
+/// Source of the outputs spent by the parsed txs
+///
+/// Without a utxo store, inputs are left unresolved.
+#[derive(Clone, Default)]
+struct Ledger {
+    #[cfg(feature = "utxo-store")]
+    store: Option<utxo_store::Store>,
+}
+
+impl Ledger {
+    /// Tracks the outputs of the txs applied at the given slot while they're
+    /// mapped, see [`utxo_store::Store::apply`]
+    fn apply<T>(
+        &self,
+        _slot: u64,
+        _txs: &[trv::MultiEraTx],
+        map: impl FnOnce() -> T,
+    ) -> Result<T, Error> {
+        #[cfg(feature = "utxo-store")]
+        if let Some(store) = &self.store {
+            return store.apply(_slot, _txs, map);
+        }
+
+        Ok(map())
+    }
+
+    fn rollback(&self, _slot: u64) -> Result<(), Error> {
+        #[cfg(feature = "utxo-store")]
+        if let Some(store) = &self.store {
+            store.rollback(_slot)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl interop::LedgerContext for Ledger {
+    fn get_utxos(&self, _refs: &[interop::TxoRef]) -> Option<interop::UtxoMap> {
+        #[cfg(feature = "utxo-store")]
+        if let Some(store) = &self.store {
+            return store.get_utxos(_refs);
+        }
+
+        None
+    }
+}
+
+/// Decodes the cbor of a tx of any era
+///
+/// Plain decoding tries the Babbage format first, which ignores the governance
+/// data of Conway txs, so the Conway format is tried before it. The select
+/// filter decodes raw txs with this too, so both stages see the same tx.
+pub fn decode_tx(cbor: &[u8]) -> Result<trv::MultiEraTx<'_>, trv::Error> {
+    trv::MultiEraTx::decode_for_era(trv::Era::Conway, cbor)
+        .or_else(|_| trv::MultiEraTx::decode(cbor))
+}
+
+/// The record produced for each block
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Output {
+    /// A single `ParsedBlock` with every tx of the block
+    Block,
+    /// A `ParsedTx` for each tx of the block
+    Tx,
+}
+
+#[derive(Default, Stage)]
+#[stage(name = "filter-parse-cbor", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    pub input: FilterInputPort,
+    pub output: FilterOutputPort,
+
+    ledger: Ledger,
+    mapper: interop::Mapper<Ledger>,
+    /// Blocks are passed through untouched when not set
+    mode: Option<Output>,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+}
+
+impl Stage {
+    /// Maps the txs of a block, tracking their outputs in the ledger when the
+    /// block is being applied
+    fn map_block(
+        &self,
+        slot: u64,
+        cbor: &[u8],
+        apply: bool,
+        mode: Output,
+    ) -> Result<Vec<Record>, WorkerError> {
+        let block = trv::MultiEraBlock::decode(cbor).or_panic()?;
+        let txs = block.txs();
+
+        let map = || match mode {
+            Output::Block => vec![Record::ParsedBlock(self.mapper.map_block(&block))],
+            Output::Tx => txs
+                .iter()
+                .map(|tx| Record::ParsedTx(self.mapper.map_tx(tx)))
+                .collect(),
+        };
+
+        match apply {
+            true => self.ledger.apply(slot, &txs, map).or_panic(),
+            false => Ok(map()),
+        }
+    }
+
+    fn map_tx(&self, slot: u64, cbor: &[u8], apply: bool) -> Result<Record, WorkerError> {
+        let tx = decode_tx(cbor).or_panic()?;
+        let txs = std::slice::from_ref(&tx);
+
+        let map = || Record::ParsedTx(self.mapper.map_tx(&tx));
+
+        match apply {
+            true => self.ledger.apply(slot, txs, map).or_panic(),
+            false => Ok(map()),
+        }
+    }
+
+    fn map_record(
+        &self,
+        slot: u64,
+        record: Record,
+        apply: bool,
+    ) -> Result<Vec<Record>, WorkerError> {
+        match record {
+            Record::CborBlock(cbor) => match self.mode {
+                Some(mode) => self.map_block(slot, &cbor, apply, mode),
+                None => Ok(vec![Record::CborBlock(cbor)]),
+            },
+            Record::CborTx(cbor) => Ok(vec![self.map_tx(slot, &cbor, apply)?]),
+            x => Ok(vec![x]),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+impl From<&Stage> for Worker {
+    fn from(_: &Stage) -> Self {
+        Self
+    }
+}
+
+gasket::impl_splitter!(|_worker: Worker, stage: Stage, unit: ChainEvent| => {
+    let slot = unit.point().slot_or_default();
+
+    let output = match unit {
+        ChainEvent::Apply(..) => unit
+            .clone()
+            .try_map_record_to_many(|r| stage.map_record(slot, r, true))?,
+        ChainEvent::Undo(..) => {
+            // restore the spent inputs so that the undone txs can resolve them
+            stage.ledger.rollback(slot.saturating_sub(1)).or_panic()?;
+
+            unit.clone()
+                .try_map_record_to_many(|r| stage.map_record(slot, r, false))?
+        }
+        ChainEvent::Reset(_) => {
+            stage.ledger.rollback(slot).or_panic()?;
+            vec![unit.clone()]
+        }
+    };
+
+    stage.ops_count.inc(1);
+
+    output
+});
+
+#[derive(Default, Deserialize)]
+pub struct Config {
+    pub output: Option<Output>,
+
+    #[cfg(feature = "utxo-store")]
+    pub utxo_store: Option<utxo_store::Config>,
+}
+
+impl Config {
+    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+        let ledger = Ledger {
+            #[cfg(feature = "utxo-store")]
+            store: match self.utxo_store {
+                Some(_) if _ctx.dry_run => None,
+                x => x.map(|x| x.bootstrapper(_ctx)).transpose()?,
+            },
+        };
+
+        let stage = Stage {
+            mapper: interop::Mapper::new(ledger.clone()),
+            ledger,
+            mode: self.output,
+            ..Default::default()
+        };
+
+        Ok(stage)
+    }
+}
@@ -0,0 +1,375 @@
+//! Embedded store of the unspent outputs seen in the chain
+//!
+//! The store is maintained from the same blocks and txs that go through the
+//! filter, so it only knows the outputs produced since the pipeline started
+//! syncing. Each change is recorded along with the slot where it happened,
+//! which allows the store to be rolled back when the chain switches forks.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use pallas::interop::utxorpc::{self as interop};
+use pallas::ledger::traverse as trv;
+use redb::{Database, ReadableTable, TableDefinition, WriteTransaction};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::framework::*;
+
+/// A txo ref encoded as the tx hash followed by the big-endian output index
+type RefKey = [u8; 36];
+
+/// An output encoded as the era tag followed by its cbor
+type EraCbor<'a> = (u16, &'a [u8]);
+
+const UTXOS: TableDefinition<&RefKey, EraCbor> = TableDefinition::new("utxos");
+const PRODUCED: TableDefinition<(u64, &RefKey), ()> = TableDefinition::new("produced");
+const CONSUMED: TableDefinition<(u64, &RefKey), EraCbor> = TableDefinition::new("consumed");
+
+/// Lowest ref, used to bound ranges of the slot-keyed tables
+const MIN_REF: RefKey = [0; 36];
+
+/// Roughly 36 hours of mainnet slots, beyond the longest possible rollback
+const DEFAULT_MAX_HISTORY: u64 = 129_600;
+
+fn ref_key(hash: &[u8], index: u32) -> RefKey {
+    let mut key = [0; 36];
+    key[..32].copy_from_slice(hash);
+    key[32..].copy_from_slice(&index.to_be_bytes());
+    key
+}
+
+fn lookup(
+    table: &impl ReadableTable<&'static RefKey, EraCbor<'static>>,
+    refs: &[interop::TxoRef],
+) -> Result<interop::UtxoMap, Error> {
+    let mut out = interop::UtxoMap::new();
+
+    for (hash, index) in refs {
+        let found = table
+            .get(&ref_key(hash.as_ref(), *index))
+            .map_err(Error::custom)?;
+
+        if let Some(x) = found {
+            let (era, cbor) = x.value();
+
+            if let Ok(era) = trv::Era::try_from(era) {
+                out.insert((*hash, *index), (era, cbor.to_vec()));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+#[derive(Clone)]
+pub struct Store {
+    db: Arc<Database>,
+    max_history: u64,
+    /// Transaction of the block being applied, read while its txs are mapped
+    pending: Arc<Mutex<Option<WriteTransaction>>>,
+}
+
+impl Store {
+    pub fn open(path: impl Into<PathBuf>, max_history: u64) -> Result<Self, Error> {
+        let db = Database::create(path.into()).map_err(Error::custom)?;
+
+        // create the tables upfront so that readers can always open them
+        let wx = db.begin_write().map_err(Error::custom)?;
+        wx.open_table(UTXOS).map_err(Error::custom)?;
+        wx.open_table(PRODUCED).map_err(Error::custom)?;
+        wx.open_table(CONSUMED).map_err(Error::custom)?;
+        wx.commit().map_err(Error::custom)?;
+
+        Ok(Self {
+            db: Arc::new(db),
+            max_history,
+            pending: Default::default(),
+        })
+    }
+
+    fn read_utxos(&self, refs: &[interop::TxoRef]) -> Result<interop::UtxoMap, Error> {
+        let pending = self.pending.lock().unwrap();
+
+        if let Some(wx) = pending.as_ref() {
+            let table = wx.open_table(UTXOS).map_err(Error::custom)?;
+            return lookup(&table, refs);
+        }
+
+        let rx = self.db.begin_read().map_err(Error::custom)?;
+        let table = rx.open_table(UTXOS).map_err(Error::custom)?;
+
+        lookup(&table, refs)
+    }
+
+    /// Tracks the outputs produced and consumed by the txs at the given slot
+    /// while they're mapped
+    ///
+    /// The produced outputs can be read while mapping, so that inputs
+    /// spending outputs of a previous tx in the same block can be resolved.
+    /// Every change of the txs is written in a single transaction.
+    pub fn apply<T>(
+        &self,
+        slot: u64,
+        txs: &[trv::MultiEraTx],
+        map: impl FnOnce() -> T,
+    ) -> Result<T, Error> {
+        let outputs = txs.iter().flat_map(|tx| {
+            let hash = tx.hash();
+
+            tx.produces().into_iter().map(move |(index, output)| {
+                let key = ref_key(hash.as_ref(), index as u32);
+                (key, u16::from(output.era()), output.encode())
+            })
+        });
+
+        let inputs = txs.iter().flat_map(|tx| {
+            tx.consumes()
+                .into_iter()
+                .map(|input| ref_key(input.hash().as_ref(), input.index() as u32))
+        });
+
+        self.write(slot, outputs, inputs, map)
+    }
+
+    fn write<T>(
+        &self,
+        slot: u64,
+        outputs: impl Iterator<Item = (RefKey, u16, Vec<u8>)>,
+        refs: impl Iterator<Item = RefKey>,
+        map: impl FnOnce() -> T,
+    ) -> Result<T, Error> {
+        let wx = self.db.begin_write().map_err(Error::custom)?;
+        Self::insert_outputs(&wx, slot, outputs)?;
+
+        *self.pending.lock().unwrap() = Some(wx);
+        let out = map();
+        let wx = self.pending.lock().unwrap().take();
+
+        let wx = wx.ok_or_else(|| Error::custom("utxo store transaction is gone"))?;
+        self.remove_outputs(&wx, slot, refs)?;
+        wx.commit().map_err(Error::custom)?;
+
+        Ok(out)
+    }
+
+    fn insert_outputs(
+        wx: &WriteTransaction,
+        slot: u64,
+        outputs: impl Iterator<Item = (RefKey, u16, Vec<u8>)>,
+    ) -> Result<(), Error> {
+        let mut utxos = wx.open_table(UTXOS).map_err(Error::custom)?;
+        let mut produced = wx.open_table(PRODUCED).map_err(Error::custom)?;
+
+        for (key, era, cbor) in outputs {
+            utxos
+                .insert(&key, (era, cbor.as_slice()))
+                .map_err(Error::custom)?;
+
+            produced.insert((slot, &key), ()).map_err(Error::custom)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the outputs, keeping them around until the slot is too old to
+    /// be rolled back
+    fn remove_outputs(
+        &self,
+        wx: &WriteTransaction,
+        slot: u64,
+        refs: impl Iterator<Item = RefKey>,
+    ) -> Result<(), Error> {
+        let mut utxos = wx.open_table(UTXOS).map_err(Error::custom)?;
+        let mut produced = wx.open_table(PRODUCED).map_err(Error::custom)?;
+        let mut consumed = wx.open_table(CONSUMED).map_err(Error::custom)?;
+
+        for key in refs {
+            let removed = utxos.remove(&key).map_err(Error::custom)?;
+
+            if let Some(x) = removed {
+                let (era, cbor) = x.value();
+
+                consumed
+                    .insert((slot, &key), (era, cbor))
+                    .map_err(Error::custom)?;
+            }
+        }
+
+        if let Some(limit) = slot.checked_sub(self.max_history) {
+            let range = ..(limit, &MIN_REF);
+
+            produced
+                .retain_in(range, |_, _| false)
+                .map_err(Error::custom)?;
+
+            consumed
+                .retain_in(range, |_, _| false)
+                .map_err(Error::custom)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reverts every change that happened after the given slot
+    pub fn rollback(&self, slot: u64) -> Result<(), Error> {
+        let wx = self.db.begin_write().map_err(Error::custom)?;
+
+        {
+            let mut utxos = wx.open_table(UTXOS).map_err(Error::custom)?;
+            let mut produced = wx.open_table(PRODUCED).map_err(Error::custom)?;
+            let mut consumed = wx.open_table(CONSUMED).map_err(Error::custom)?;
+
+            let from = (slot + 1, &MIN_REF);
+
+            // consumed outputs are restored first, so that outputs both
+            // produced and consumed after the slot end up removed
+            for entry in consumed.range(from..).map_err(Error::custom)? {
+                let (key, value) = entry.map_err(Error::custom)?;
+                let (_, key) = key.value();
+                let (era, cbor) = value.value();
+
+                utxos.insert(key, (era, cbor)).map_err(Error::custom)?;
+            }
+
+            for entry in produced.range(from..).map_err(Error::custom)? {
+                let (key, _) = entry.map_err(Error::custom)?;
+                let (_, key) = key.value();
+
+                utxos.remove(key).map_err(Error::custom)?;
+            }
+
+            consumed
+                .retain_in(from.., |_, _| false)
+                .map_err(Error::custom)?;
+
+            produced
+                .retain_in(from.., |_, _| false)
+                .map_err(Error::custom)?;
+        }
+
+        wx.commit().map_err(Error::custom)
+    }
+}
+
+impl interop::LedgerContext for Store {
+    fn get_utxos(&self, refs: &[interop::TxoRef]) -> Option<interop::UtxoMap> {
+        match self.read_utxos(refs) {
+            Ok(x) => Some(x),
+            Err(err) => {
+                warn!(%err, "can't read utxos from the store");
+                None
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub path: String,
+
+    /// How many slots of changes are kept to support rollbacks
+    pub max_history: Option<u64>,
+}
+
+impl Config {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Store, Error> {
+        let max_history = self.max_history.unwrap_or(DEFAULT_MAX_HISTORY);
+        Store::open(ctx.current_dir.join(&self.path), max_history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(max_history: u64) -> (Store, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(dir.path().join("utxos.redb"), max_history).unwrap();
+
+        (store, dir)
+    }
+
+    fn output(hash: u8, index: u32) -> (RefKey, u16, Vec<u8>) {
+        (ref_key(&[hash; 32], index), 6, vec![hash, index as u8])
+    }
+
+    fn resolve(store: &Store, hash: u8, index: u32) -> Option<Vec<u8>> {
+        let txo = ([hash; 32].into(), index);
+
+        store
+            .read_utxos(&[txo])
+            .unwrap()
+            .remove(&txo)
+            .map(|(_, cbor)| cbor)
+    }
+
+    fn produce(store: &Store, slot: u64, outputs: Vec<(RefKey, u16, Vec<u8>)>) {
+        store
+            .write(slot, outputs.into_iter(), std::iter::empty(), || ())
+            .unwrap();
+    }
+
+    fn consume(store: &Store, slot: u64, refs: Vec<RefKey>) {
+        store
+            .write(slot, std::iter::empty(), refs.into_iter(), || ())
+            .unwrap();
+    }
+
+    #[test]
+    fn resolves_outputs_of_the_same_block() {
+        let (store, _dir) = store(DEFAULT_MAX_HISTORY);
+
+        let outputs = [output(1, 0)].into_iter();
+        let refs = [ref_key(&[1; 32], 0)].into_iter();
+
+        let seen = store
+            .write(10, outputs, refs, || resolve(&store, 1, 0))
+            .unwrap();
+
+        assert_eq!(seen, Some(vec![1, 0]));
+        assert_eq!(resolve(&store, 1, 0), None);
+
+        store.rollback(0).unwrap();
+        assert_eq!(resolve(&store, 1, 0), None);
+    }
+
+    #[test]
+    fn rolls_back_spent_and_produced_outputs() {
+        let (store, _dir) = store(DEFAULT_MAX_HISTORY);
+
+        produce(&store, 10, vec![output(1, 0), output(1, 1)]);
+        produce(&store, 20, vec![output(2, 0)]);
+        consume(&store, 20, vec![ref_key(&[1; 32], 0), ref_key(&[2; 32], 0)]);
+
+        assert_eq!(resolve(&store, 1, 0), None);
+        assert_eq!(resolve(&store, 1, 1), Some(vec![1, 1]));
+        assert_eq!(resolve(&store, 2, 0), None);
+
+        store.rollback(10).unwrap();
+
+        assert_eq!(resolve(&store, 1, 0), Some(vec![1, 0]));
+        assert_eq!(resolve(&store, 1, 1), Some(vec![1, 1]));
+        assert_eq!(resolve(&store, 2, 0), None);
+
+        store.rollback(0).unwrap();
+
+        assert_eq!(resolve(&store, 1, 0), None);
+        assert_eq!(resolve(&store, 1, 1), None);
+    }
+
+    #[test]
+    fn forgets_changes_beyond_the_history() {
+        let (store, _dir) = store(100);
+
+        produce(&store, 10, vec![output(1, 0), output(1, 1)]);
+        consume(&store, 20, vec![ref_key(&[1; 32], 0)]);
+        consume(&store, 200, vec![]);
+
+        // the changes are too old to be reverted, the outputs stay as they are
+        store.rollback(0).unwrap();
+
+        assert_eq!(resolve(&store, 1, 0), None);
+        assert_eq!(resolve(&store, 1, 1), Some(vec![1, 1]));
+    }
+}
@@ -0,0 +1,198 @@
+//! A filter that turns rollbacks into undo events for each affected record
+//!
+//! Sources only report a rollback as a reset to a previous point. This filter
+//! remembers the records of the latest blocks and, when a reset arrives,
+//! emits an undo event for each record newer than the rollback point (newest
+//! first) before forwarding the reset itself.
+
+use gasket::framework::*;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::framework::rollback::RollbackBuffer;
+use crate::framework::*;
+
+/// The security parameter of mainnet, the max number of blocks that can be
+/// rolled back
+const DEFAULT_MAX_DEPTH: usize = 2160;
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Default::default())
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        let msg = stage.input.recv().await.or_panic()?;
+
+        Ok(WorkSchedule::Unit(msg.payload))
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        match unit {
+            ChainEvent::Apply(point, record) => {
+                stage.buffer.apply(point, record.clone());
+            }
+            ChainEvent::Undo(..) => (),
+            ChainEvent::Reset(point) => {
+                if let Some(oldest) = stage.buffer.oldest() {
+                    if oldest.slot_or_default() > point.slot_or_default() {
+                        warn!(
+                            ?point,
+                            ?oldest,
+                            "rollback goes beyond the buffer, some records won't be undone"
+                        );
+                    }
+                }
+
+                for (point, record) in stage.buffer.rollback(point) {
+                    let undo = ChainEvent::Undo(point, record);
+                    stage.output.send(undo.into()).await.or_panic()?;
+                    stage.undo_count.inc(1);
+                }
+            }
+        }
+
+        stage.output.send(unit.clone().into()).await.or_panic()?;
+
+        stage.depth.set(stage.buffer.depth() as i64);
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
+
+#[derive(Stage)]
+#[stage(
+    name = "filter-rollback-buffer",
+    unit = "ChainEvent",
+    worker = "Worker"
+)]
+pub struct Stage {
+    buffer: RollbackBuffer,
+
+    pub input: FilterInputPort,
+    pub output: FilterOutputPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    undo_count: gasket::metrics::Counter,
+
+    #[metric]
+    depth: gasket::metrics::Gauge,
+}
+
+#[derive(Default, Deserialize)]
+pub struct Config {
+    /// Max number of blocks to keep in the buffer
+    pub max_depth: Option<usize>,
+}
+
+impl Config {
+    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+        let max_depth = self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+
+        if max_depth == 0 {
+            return Err(Error::config("rollback buffer max_depth can't be zero"));
+        }
+
+        let stage = Stage {
+            buffer: RollbackBuffer::new(max_depth),
+            input: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+            undo_count: Default::default(),
+            depth: Default::default(),
+        };
+
+        Ok(stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::framework::Worker as _;
+    use gasket::messaging::tokio::connect_ports;
+    use pallas::network::miniprotocols::Point;
+
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    fn apply(slot: u64, value: u64) -> ChainEvent {
+        ChainEvent::Apply(point(slot), Record::GenericJson(value.into()))
+    }
+
+    /// Summarizes an event as its kind, slot and record value
+    fn describe(event: &ChainEvent) -> (&'static str, u64, Option<u64>) {
+        let value = |r: &Record| match r {
+            Record::GenericJson(x) => x.as_u64(),
+            _ => None,
+        };
+
+        match event {
+            ChainEvent::Apply(p, r) => ("apply", p.slot_or_default(), value(r)),
+            ChainEvent::Undo(p, r) => ("undo", p.slot_or_default(), value(r)),
+            ChainEvent::Reset(p) => ("reset", p.slot_or_default(), None),
+        }
+    }
+
+    #[test]
+    fn emits_undos_before_the_reset() {
+        let mut stage = Stage {
+            buffer: RollbackBuffer::new(10),
+            input: Default::default(),
+            output: Default::default(),
+            ops_count: Default::default(),
+            undo_count: Default::default(),
+            depth: Default::default(),
+        };
+
+        let mut output = FilterInputPort::default();
+        connect_ports(&mut stage.output, &mut output, 20);
+
+        let events = futures::executor::block_on(async {
+            let mut worker = Worker::bootstrap(&stage).await.unwrap();
+
+            let units = [
+                apply(1, 1),
+                apply(2, 2),
+                apply(2, 3),
+                apply(3, 4),
+                ChainEvent::Reset(point(1)),
+            ];
+
+            for unit in units.iter() {
+                worker.execute(unit, &mut stage).await.unwrap();
+            }
+
+            let mut events = vec![];
+
+            for _ in 0..8 {
+                events.push(describe(&output.recv().await.unwrap().payload));
+            }
+
+            events
+        });
+
+        assert_eq!(
+            events[4..],
+            [
+                ("undo", 3, Some(4)),
+                ("undo", 2, Some(3)),
+                ("undo", 2, Some(2)),
+                ("reset", 1, None),
+            ]
+        );
+    }
+}
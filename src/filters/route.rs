@@ -0,0 +1,292 @@
+//! A stage that routes each event to the branches whose predicate matches
+//!
+//! Each branch is a sub-chain of filters that ends in a sink. Events are
+//! evaluated against the predicate of every branch and forwarded to all of
+//! the matching ones. Events that don't match any predicate go to the default
+//! branch (the one without a predicate), if there's one. Resets are forwarded
+//! to every branch.
+
+use gasket::framework::*;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::cursor::fanin::{Signal, SignalPort};
+use crate::framework::dead_letter::DeadLetters;
+use crate::framework::*;
+use crate::{filters, sinks};
+
+use super::select::eval::{self, MatchOutcome, Predicate, StringOrStruct};
+
+/// The branches that match the record, none if the outcome is uncertain
+fn find_targets(stage: &Stage, record: &Record) -> Option<Vec<usize>> {
+    let mut targets = vec![];
+
+    for (idx, predicate) in stage.predicates.iter().enumerate() {
+        let predicate = match predicate {
+            Some(x) => x,
+            None => continue,
+        };
+
+        match eval::eval(record, predicate) {
+            MatchOutcome::Positive => targets.push(idx),
+            MatchOutcome::Negative => (),
+            MatchOutcome::Uncertain => {
+                if !stage.skip_uncertain {
+                    return None;
+                }
+            }
+        }
+    }
+
+    if targets.is_empty() {
+        targets.extend(stage.default);
+    }
+
+    Some(targets)
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Default::default())
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        let msg = stage.input.recv().await.or_panic()?;
+
+        Ok(WorkSchedule::Unit(msg.payload))
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let targets = match unit {
+            ChainEvent::Apply(_, r) => find_targets(stage, r),
+            ChainEvent::Undo(_, r) => find_targets(stage, r),
+            ChainEvent::Reset(_) => Some((0..stage.outputs.len()).collect()),
+        };
+
+        // uncertain events are skipped by every branch once dead-lettered
+        let targets = match targets {
+            Some(x) => {
+                if x.is_empty() {
+                    stage.unmatched_count.inc(1);
+                }
+
+                x
+            }
+            None => {
+                stage
+                    .dead_letters
+                    .divert("route", "uncertain match outcome", unit)
+                    .await?;
+
+                vec![]
+            }
+        };
+
+        let point = unit.point();
+
+        for idx in 0..stage.outputs.len() {
+            if targets.contains(&idx) {
+                // the dispatch signal needs to be sent before the event so that
+                // the cursor never sees an ack for an event it doesn't know of
+                let signal = (idx, Signal::Dispatch(point.clone()));
+                stage.signals.send(signal.into()).await.or_panic()?;
+
+                let output = &mut stage.outputs[idx];
+                output.send(unit.clone().into()).await.or_panic()?;
+            } else {
+                let signal = (idx, Signal::Skip(point.clone()));
+                stage.signals.send(signal.into()).await.or_panic()?;
+            }
+        }
+
+        stage.ops_count.inc(1);
+
+        Ok(())
+    }
+}
+
+#[derive(Stage)]
+#[stage(name = "route", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    predicates: Vec<Option<Predicate>>,
+    default: Option<usize>,
+    skip_uncertain: bool,
+    dead_letters: DeadLetters,
+
+    pub input: FilterInputPort,
+    pub outputs: Vec<FilterOutputPort>,
+    pub signals: SignalPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    unmatched_count: gasket::metrics::Counter,
+}
+
+/// The stages that make up a single branch of the router
+pub struct Branch {
+    pub name: String,
+    pub filters: Vec<filters::Bootstrapper>,
+    pub sink: sinks::Bootstrapper,
+}
+
+#[derive(Deserialize)]
+pub struct BranchConfig {
+    pub name: String,
+    pub predicate: Option<StringOrStruct<Predicate>>,
+    pub filters: Option<Vec<filters::Config>>,
+    pub sink: sinks::Config,
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub branches: Vec<BranchConfig>,
+
+    #[serde(default)]
+    pub skip_uncertain: bool,
+}
+
+impl Config {
+    /// Checks the predicates and filters of every branch against the records
+    /// that reach the route
+    pub(crate) fn check_parsed(&self, parsed: bool) -> Result<(), Error> {
+        for branch in &self.branches {
+            if let Some(predicate) = branch.predicate.as_ref().filter(|_| parsed) {
+                super::select::check_parsed(predicate)?;
+            }
+
+            filters::check_parsed(branch.filters.as_deref().unwrap_or_default(), parsed)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn bootstrapper(self, ctx: &Context) -> Result<(Stage, Vec<Branch>), Error> {
+        if self.branches.is_empty() {
+            return Err(Error::config("at least one route branch is required"));
+        }
+
+        let mut predicates = vec![];
+        let mut default = None;
+        let mut branches = vec![];
+
+        for (idx, branch) in self.branches.into_iter().enumerate() {
+            info!(branch = branch.name, predicate = ?branch.predicate, "route branch");
+
+            if branch.predicate.is_none() {
+                if default.is_some() {
+                    return Err(Error::config(
+                        "only one route branch can omit the predicate",
+                    ));
+                }
+
+                default = Some(idx);
+            }
+
+            if let Some(predicate) = &branch.predicate {
+                predicate.bootstrap(ctx)?;
+            }
+
+            predicates.push(branch.predicate.map(StringOrStruct::unwrap));
+
+            let filters = branch
+                .filters
+                .into_iter()
+                .flatten()
+                .map(|x| x.bootstrapper(ctx))
+                .collect::<Result<_, _>>()?;
+
+            branches.push(Branch {
+                name: branch.name,
+                filters,
+                sink: branch.sink.bootstrapper(ctx)?,
+            });
+        }
+
+        let stage = Stage {
+            outputs: branches.iter().map(|_| Default::default()).collect(),
+            predicates,
+            default,
+            skip_uncertain: self.skip_uncertain,
+            dead_letters: ctx.dead_letters.clone(),
+            input: Default::default(),
+            signals: Default::default(),
+            ops_count: Default::default(),
+            unmatched_count: Default::default(),
+        };
+
+        Ok((stage, branches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn stage(predicates: &[Option<&str>], skip_uncertain: bool) -> Stage {
+        let predicates: Vec<_> = predicates
+            .iter()
+            .map(|x| x.map(|x| x.parse::<Predicate>().unwrap()))
+            .collect();
+
+        Stage {
+            outputs: predicates.iter().map(|_| Default::default()).collect(),
+            default: predicates.iter().position(Option::is_none),
+            predicates,
+            skip_uncertain,
+            dead_letters: Default::default(),
+            input: Default::default(),
+            signals: Default::default(),
+            ops_count: Default::default(),
+            unmatched_count: Default::default(),
+        }
+    }
+
+    fn record(kind: &str) -> Record {
+        Record::GenericJson(json!({ "kind": kind }))
+    }
+
+    #[test]
+    fn routes_to_matching_branches() {
+        let stage = stage(&[Some("$.kind"), Some("$.other"), Some("$.kind")], false);
+
+        assert_eq!(find_targets(&stage, &record("mint")), Some(vec![0, 2]));
+        assert_eq!(
+            find_targets(&stage, &Record::GenericJson(json!({}))),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn routes_unmatched_to_default() {
+        let stage = stage(&[Some("$.other"), None], false);
+
+        assert_eq!(find_targets(&stage, &record("mint")), Some(vec![1]));
+        assert_eq!(
+            find_targets(&stage, &Record::GenericJson(json!({ "other": 1 }))),
+            Some(vec![0])
+        );
+    }
+
+    #[test]
+    fn handles_uncertain_outcomes() {
+        // address patterns can't be evaluated on json records
+        let address = "addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x";
+
+        let strict = stage(&[Some(address), Some("$.kind")], false);
+        assert_eq!(find_targets(&strict, &record("mint")), None);
+
+        let lenient = stage(&[Some(address), Some("$.kind")], true);
+        assert_eq!(find_targets(&lenient, &record("mint")), Some(vec![1]));
+    }
+}
@@ -124,7 +124,22 @@ impl FromBech32 for AddressPattern {
     fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
         match hrp {
             "addr" | "addr_test" | "stake" => Address::from_bytes(&content).ok().map(From::from),
-            // TODO: add vk prefixes
+            "addr_vkh" => Some(Self {
+                payment_part: Some(content.into()),
+                payment_is_script: Some(false),
+                ..Default::default()
+            }),
+            "stake_vkh" => Some(Self {
+                delegation_part: Some(content.into()),
+                delegation_is_script: Some(false),
+                ..Default::default()
+            }),
+            // script hashes are matched as the payment part of script addresses
+            "script" => Some(Self {
+                payment_part: Some(content.into()),
+                payment_is_script: Some(true),
+                ..Default::default()
+            }),
             _ => None,
         }
     }
@@ -202,4 +217,24 @@ mod tests {
         ));
         assert_eq!(possitives, vec![1, 2, 3]);
     }
+
+    #[test]
+    fn key_hash_match() {
+        let pattern = |x: &str| Pattern::from(AddressPattern::from_str(x).unwrap());
+
+        let possitives = testing::find_positive_test_vectors(pattern(
+            "addr_vkh1jjfnzhxe966a33psfenm0ct2udkkr569qf55v4uprgkgu8zsvmg",
+        ));
+        assert_eq!(possitives, vec![1, 2, 3]);
+
+        let possitives = testing::find_positive_test_vectors(pattern(
+            "stake_vkh1xdak9nllvsp6q636e0p5lrzxqq7xnlne5d3gemafc3e9z3v4vud",
+        ));
+        assert_eq!(possitives, vec![1, 3]);
+
+        let possitives = testing::find_positive_test_vectors(pattern(
+            "script1jjfnzhxe966a33psfenm0ct2udkkr569qf55v4uprgkgumhf3lf",
+        ));
+        assert!(possitives.is_empty());
+    }
 }
@@ -14,7 +14,7 @@ pub struct AssetPattern {
     pub name: Option<FlexBytes>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub name_text: Option<TextPattern>,
+    pub name_text: Option<StringOrStruct<TextPattern>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub coin: Option<CoinPattern>,
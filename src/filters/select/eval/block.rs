@@ -0,0 +1,436 @@
+use pallas::crypto::hash::Hasher;
+use pallas::interop::utxorpc::spec::cardano::{BlockBody, BlockHeader};
+use pallas::interop::utxorpc::{self as interop};
+use pallas::ledger::traverse::MultiEraBlock;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::*;
+
+pub type SlotPattern = NumericPattern<u64>;
+
+pub type HeightPattern = NumericPattern<u64>;
+
+pub type EraPattern = NumericPattern<u8>;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PoolPattern {
+    id: FlexBytes,
+}
+
+impl FromBech32 for PoolPattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            "pool" => Some(Self { id: content.into() }),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for PoolPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(x) = Self::from_bech32(s) {
+            return Ok(x);
+        }
+
+        let id = FlexBytes::from_hex(s)?;
+        Ok(Self { id })
+    }
+}
+
+impl PatternOf<&[u8]> for PoolPattern {
+    fn is_match(&self, subject: &[u8]) -> MatchOutcome {
+        self.id.is_match(subject)
+    }
+}
+
+/// The data of a block that block patterns are evaluated against
+///
+/// The txs are either the parsed or the raw version of the txs in the block.
+/// Parsed blocks don't carry the era, issuer or size of the block, so patterns
+/// on those are uncertain for them.
+pub struct BlockSubject<'a, T> {
+    pub slot: u64,
+    pub hash: &'a [u8],
+    pub height: u64,
+    pub era: Option<u8>,
+    pub issuer: Option<Vec<u8>>,
+    pub size: Option<u64>,
+    pub txs: &'a [T],
+}
+
+impl<'a> BlockSubject<'a, ParsedTx> {
+    pub fn from_parsed(block: &'a ParsedBlock) -> Option<Self> {
+        let header = block.header.as_ref()?;
+
+        let txs = match &block.body {
+            Some(x) => x.tx.as_slice(),
+            None => &[],
+        };
+
+        Some(Self {
+            slot: header.slot,
+            hash: header.hash.as_ref(),
+            height: header.height,
+            era: None,
+            issuer: None,
+            size: None,
+            txs,
+        })
+    }
+}
+
+impl<'a, 'b> BlockSubject<'a, RawTx<'b>> {
+    /// Takes the header data from the raw block, along with its raw txs
+    pub fn from_cbor(
+        raw: &MultiEraBlock,
+        cbor: &[u8],
+        txs: &'a [RawTx<'b>],
+        hash: &'a [u8],
+    ) -> Self {
+        // pool ids are the hash of the cold key that issues the blocks
+        let issuer = raw
+            .header()
+            .issuer_vkey()
+            .map(|x| Hasher::<224>::hash(x).to_vec());
+
+        Self {
+            slot: raw.slot(),
+            hash,
+            height: raw.number(),
+            era: Some(u16::from(raw.era()) as u8),
+            issuer,
+            size: Some(cbor.len() as u64),
+            txs,
+        }
+    }
+}
+
+/// Matches a pattern against a value that might be unknown for the subject
+fn is_known_match<S, P>(pattern: &Option<P>, subject: Option<S>) -> MatchOutcome
+where
+    P: PatternOf<S>,
+{
+    match (pattern, subject) {
+        (None, _) => MatchOutcome::Positive,
+        (Some(_), None) => MatchOutcome::Uncertain,
+        (Some(x), Some(subject)) => x.is_match(subject),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BlockPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<FlexBytes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    slot: Option<SlotPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<HeightPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    era: Option<EraPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    issuer: Option<StringOrStruct<PoolPattern>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<NumericPattern<u64>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tx_count: Option<NumericPattern<u64>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    txs: Vec<TxPattern>,
+}
+
+impl<T> PatternOf<&BlockSubject<'_, T>> for BlockPattern
+where
+    for<'x> TxPattern: PatternOf<&'x T>,
+{
+    fn is_match(&self, subject: &BlockSubject<T>) -> MatchOutcome {
+        let a = self.hash.is_match(subject.hash);
+
+        let b = self.slot.is_match(subject.slot);
+
+        let c = self.height.is_match(subject.height);
+
+        let d = is_known_match(&self.era, subject.era);
+
+        let e = is_known_match(&self.issuer, subject.issuer.as_deref());
+
+        let f = is_known_match(&self.size, subject.size);
+
+        let g = self.tx_count.is_match(subject.txs.len() as u64);
+
+        let h = self.txs.iter().map(|x| x.is_any_match(subject.txs.iter()));
+
+        let h = MatchOutcome::fold_all_of(h);
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f, g, h].into_iter())
+    }
+}
+
+/// Evaluates the predicate against a block
+///
+/// Block patterns are matched against the block itself, any other pattern is
+/// positive if at least one of the txs in the block matches it.
+pub fn eval_block_subject<T>(block: &BlockSubject<T>, predicate: &Predicate) -> MatchOutcome
+where
+    for<'x> Pattern: PatternOf<&'x T>,
+    for<'x> TxPattern: PatternOf<&'x T>,
+{
+    match predicate {
+        Predicate::Not(x) => !eval_block_subject(block, x),
+        Predicate::AnyOf(x) => {
+            let o = x.iter().map(|x| eval_block_subject(block, x));
+            MatchOutcome::fold_any_of(o)
+        }
+        Predicate::AllOf(x) => {
+            let o = x.iter().map(|x| eval_block_subject(block, x));
+            MatchOutcome::fold_all_of(o)
+        }
+        Predicate::Match(x) => match x.deref() {
+            Pattern::Block(x) => x.is_match(block),
+            x => x.is_any_match(block.txs.iter()),
+        },
+    }
+}
+
+/// Evaluates the predicate against a single tx of a block
+///
+/// Block patterns are still matched against the whole block, so the outcome
+/// tells if the tx takes part in the block matching the predicate.
+pub fn eval_block_tx<T>(block: &BlockSubject<T>, tx: &T, predicate: &Predicate) -> MatchOutcome
+where
+    for<'x> Pattern: PatternOf<&'x T>,
+    for<'x> TxPattern: PatternOf<&'x T>,
+{
+    match predicate {
+        Predicate::Not(x) => !eval_block_tx(block, tx, x),
+        Predicate::AnyOf(x) => {
+            let o = x.iter().map(|x| eval_block_tx(block, tx, x));
+            MatchOutcome::fold_any_of(o)
+        }
+        Predicate::AllOf(x) => {
+            let o = x.iter().map(|x| eval_block_tx(block, tx, x));
+            MatchOutcome::fold_all_of(o)
+        }
+        Predicate::Match(x) => match x.deref() {
+            Pattern::Block(x) => x.is_match(block),
+            x => x.is_match(tx),
+        },
+    }
+}
+
+/// Builds a parsed block that only keeps the txs that match the predicate
+///
+/// Txs with an uncertain outcome are kept, only the ones that certainly don't
+/// match are dropped. Only the kept txs go through `map`.
+fn project_block_subject<T>(
+    block: &BlockSubject<T>,
+    header: Option<BlockHeader>,
+    predicate: &Predicate,
+    map: impl Fn(&T) -> ParsedTx,
+) -> ParsedBlock
+where
+    for<'x> Pattern: PatternOf<&'x T>,
+    for<'x> TxPattern: PatternOf<&'x T>,
+{
+    let tx = block
+        .txs
+        .iter()
+        .filter(|tx| eval_block_tx(block, tx, predicate) != MatchOutcome::Negative)
+        .map(map)
+        .collect();
+
+    ParsedBlock {
+        header,
+        body: Some(BlockBody { tx }),
+    }
+}
+
+pub fn project_parsed_block(block: &ParsedBlock, predicate: &Predicate) -> ParsedBlock {
+    match BlockSubject::from_parsed(block) {
+        Some(subject) => {
+            project_block_subject(&subject, block.header.clone(), predicate, Clone::clone)
+        }
+        None => block.clone(),
+    }
+}
+
+/// Ledger context that leaves every input unresolved
+#[derive(Clone, Default)]
+pub struct NoLedger;
+
+impl interop::LedgerContext for NoLedger {
+    fn get_utxos(&self, _refs: &[interop::TxoRef]) -> Option<interop::UtxoMap> {
+        None
+    }
+}
+
+/// Evaluates the predicate against the raw txs of the cbor block, without
+/// mapping them into their parsed version
+pub fn eval_cbor_block(cbor: &[u8], predicate: &Predicate) -> MatchOutcome {
+    let raw = match MultiEraBlock::decode(cbor) {
+        Ok(x) => x,
+        Err(_) => return MatchOutcome::Uncertain,
+    };
+
+    let txs: Vec<_> = raw.txs().into_iter().map(RawTx::from).collect();
+    let hash = raw.hash();
+
+    let subject = BlockSubject::from_cbor(&raw, cbor, &txs, hash.as_ref());
+
+    eval_block_subject(&subject, predicate)
+}
+
+/// Decodes the cbor block and projects it into a parsed block with the txs
+/// that match the predicate
+///
+/// The cbor of a block can't be trimmed without invalidating its body hash, so
+/// the projection of a cbor block is a parsed block. Txs are matched in their
+/// raw form and only the kept ones are mapped.
+pub fn project_cbor_block(cbor: &[u8], predicate: &Predicate) -> Option<ParsedBlock> {
+    let raw = MultiEraBlock::decode(cbor).ok()?;
+
+    let txs: Vec<_> = raw.txs().into_iter().map(RawTx::from).collect();
+    let hash = raw.hash();
+
+    let subject = BlockSubject::from_cbor(&raw, cbor, &txs, hash.as_ref());
+
+    let header = BlockHeader {
+        slot: raw.slot(),
+        hash: hash.to_vec().into(),
+        height: raw.number(),
+    };
+
+    let mapper = interop::Mapper::<NoLedger>::default();
+
+    Some(project_block_subject(
+        &subject,
+        Some(header),
+        predicate,
+        |x| mapper.map_tx(x.tx()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed_block(slot: u64) -> ParsedBlock {
+        ParsedBlock {
+            header: Some(BlockHeader {
+                slot,
+                hash: vec![0xab; 32].into(),
+                height: slot / 20,
+            }),
+            body: Some(BlockBody {
+                tx: testing::test_vectors(),
+            }),
+        }
+    }
+
+    fn eval(slot: u64, predicate: &str) -> MatchOutcome {
+        let predicate = serde_json::from_str::<StringOrStruct<Predicate>>(predicate).unwrap();
+        eval_block(&parsed_block(slot), &predicate)
+    }
+
+    #[test]
+    fn block_header_patterns() {
+        let predicate = r#"{ "match": { "block": { "slot": { "between": [100, 200] } } } }"#;
+
+        assert_eq!(eval(150, predicate), MatchOutcome::Positive);
+        assert_eq!(eval(250, predicate), MatchOutcome::Negative);
+
+        let predicate =
+            r#"{ "match": { "block": { "height": { "gte": 10 }, "tx_count": { "exact": 4 } } } }"#;
+
+        assert_eq!(eval(200, predicate), MatchOutcome::Positive);
+        assert_eq!(eval(100, predicate), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn parsed_blocks_lack_issuer() {
+        let predicate = r#"{ "match": { "block": { "issuer": "pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy" } } }"#;
+
+        assert_eq!(eval(100, predicate), MatchOutcome::Uncertain);
+    }
+
+    #[test]
+    fn block_with_tx_patterns() {
+        let predicate = r##"{
+            "all": [
+                { "match": { "block": { "slot": { "lte": 500 } } } },
+                "#127"
+            ]
+        }"##;
+
+        assert_eq!(eval(100, predicate), MatchOutcome::Positive);
+        assert_eq!(eval(600, predicate), MatchOutcome::Negative);
+
+        let predicate =
+            r##"{ "match": { "block": { "txs": [{ "metadata": [{ "label": 8888 }] }] } } }"##;
+
+        assert_eq!(eval(100, predicate), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn block_patterns_on_txs() {
+        let predicate = serde_json::from_str::<Predicate>(
+            r#"{ "not": { "match": { "block": { "slot": { "lte": 500 } } } } }"#,
+        )
+        .unwrap();
+
+        for tx in testing::test_vectors() {
+            assert_eq!(eval_subject(&tx, &predicate), MatchOutcome::Uncertain);
+        }
+    }
+
+    fn project(slot: u64, predicate: &str) -> usize {
+        let predicate = serde_json::from_str::<StringOrStruct<Predicate>>(predicate).unwrap();
+        let block = project_parsed_block(&parsed_block(slot), &predicate);
+
+        assert_eq!(block.header, parsed_block(slot).header);
+
+        block.body.unwrap().tx.len()
+    }
+
+    #[test]
+    fn project_block_txs() {
+        let expected = testing::find_positive_test_vectors(Pattern::from_str("#127").unwrap());
+        assert!(!expected.is_empty() && expected.len() < 4);
+
+        assert_eq!(project(100, r##""#127""##), expected.len());
+
+        let predicate = r#"{ "match": { "block": { "slot": { "lte": 500 } } } }"#;
+        assert_eq!(project(100, predicate), 4);
+
+        let predicate = r##"{
+            "all": [
+                { "match": { "block": { "slot": { "lte": 500 } } } },
+                "#127"
+            ]
+        }"##;
+
+        assert_eq!(project(100, predicate), expected.len());
+        assert_eq!(project(600, predicate), 0);
+    }
+
+    #[test]
+    fn parse_pool_pattern() {
+        let pattern =
+            PoolPattern::from_str("pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy")
+                .unwrap();
+
+        let expected =
+            PoolPattern::from_str("0f292fcaa02b8b2f9b3c8f9fd8e0bb21abedb692a6d5058df3ef2735")
+                .unwrap();
+
+        assert_eq!(pattern, expected);
+    }
+}
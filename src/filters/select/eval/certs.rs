@@ -0,0 +1,469 @@
+use pallas::interop::utxorpc::spec::cardano::{
+    certificate, d_rep, stake_credential, Certificate, DRep, StakeCredential, Withdrawal,
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::*;
+
+/// The hash of a key or script credential
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CredentialPattern {
+    hash: FlexBytes,
+}
+
+/// Takes the hash out of a governance credential
+///
+/// CIP-129 ids prefix the hash with a header byte, while the ones from CIP-105
+/// are the plain hash.
+fn governance_hash(content: Vec<u8>) -> Option<Vec<u8>> {
+    match content.len() {
+        28 => Some(content),
+        29 => Some(content[1..].to_vec()),
+        _ => None,
+    }
+}
+
+impl FromBech32 for CredentialPattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            // stake addresses are the credential prefixed by a header byte
+            "stake" | "stake_test" => Some(Self {
+                hash: content.get(1..)?.to_vec().into(),
+            }),
+            "addr_vkh" | "stake_vkh" | "script" => Some(Self {
+                hash: content.into(),
+            }),
+            "drep" | "drep_script" | "cc_hot" | "cc_hot_script" | "cc_cold" | "cc_cold_script" => {
+                Some(Self {
+                    hash: governance_hash(content)?.into(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for CredentialPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(x) = Self::from_bech32(s) {
+            return Ok(x);
+        }
+
+        let hash = FlexBytes::from_hex(s)?;
+        Ok(Self { hash })
+    }
+}
+
+impl PatternOf<&[u8]> for CredentialPattern {
+    fn is_match(&self, subject: &[u8]) -> MatchOutcome {
+        self.hash.is_match(subject)
+    }
+}
+
+impl PatternOf<&StakeCredential> for CredentialPattern {
+    fn is_match(&self, subject: &StakeCredential) -> MatchOutcome {
+        match &subject.stake_credential {
+            Some(stake_credential::StakeCredential::AddrKeyHash(x)) => self.is_match(x.as_ref()),
+            Some(stake_credential::StakeCredential::ScriptHash(x)) => self.is_match(x.as_ref()),
+            None => MatchOutcome::Negative,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CertificateKind {
+    StakeRegistration,
+    StakeDeregistration,
+    StakeDelegation,
+    VoteDelegation,
+    PoolRegistration,
+    PoolRetirement,
+    DrepRegistration,
+    DrepDeregistration,
+    DrepUpdate,
+    CommitteeHotAuthorization,
+    CommitteeColdResignation,
+}
+
+/// The parts of a certificate that patterns can match
+#[derive(Default)]
+struct CertificateParts<'a> {
+    kinds: &'a [CertificateKind],
+    stake: Option<&'a StakeCredential>,
+    pool: Option<&'a [u8]>,
+    drep: Option<&'a [u8]>,
+    committee: Option<&'a [u8]>,
+}
+
+fn drep_hash(drep: Option<&DRep>) -> Option<&[u8]> {
+    match drep?.drep.as_ref()? {
+        d_rep::Drep::AddrKeyHash(x) => Some(x.as_ref()),
+        d_rep::Drep::ScriptHash(x) => Some(x.as_ref()),
+        _ => None,
+    }
+}
+
+fn credential_hash(credential: Option<&StakeCredential>) -> Option<&[u8]> {
+    match credential?.stake_credential.as_ref()? {
+        stake_credential::StakeCredential::AddrKeyHash(x) => Some(x.as_ref()),
+        stake_credential::StakeCredential::ScriptHash(x) => Some(x.as_ref()),
+    }
+}
+
+fn certificate_parts(subject: &Certificate) -> CertificateParts<'_> {
+    use certificate::Certificate as C;
+    use CertificateKind::*;
+
+    match subject.certificate.as_ref() {
+        Some(C::StakeRegistration(x)) => CertificateParts {
+            kinds: &[StakeRegistration],
+            stake: Some(x),
+            ..Default::default()
+        },
+        Some(C::RegCert(x)) => CertificateParts {
+            kinds: &[StakeRegistration],
+            stake: x.stake_credential.as_ref(),
+            ..Default::default()
+        },
+        Some(C::StakeDeregistration(x)) => CertificateParts {
+            kinds: &[StakeDeregistration],
+            stake: Some(x),
+            ..Default::default()
+        },
+        Some(C::UnregCert(x)) => CertificateParts {
+            kinds: &[StakeDeregistration],
+            stake: x.stake_credential.as_ref(),
+            ..Default::default()
+        },
+        Some(C::StakeDelegation(x)) => CertificateParts {
+            kinds: &[StakeDelegation],
+            stake: x.stake_credential.as_ref(),
+            pool: Some(x.pool_keyhash.as_ref()),
+            ..Default::default()
+        },
+        Some(C::VoteDelegCert(x)) => CertificateParts {
+            kinds: &[VoteDelegation],
+            stake: x.stake_credential.as_ref(),
+            drep: drep_hash(x.drep.as_ref()),
+            ..Default::default()
+        },
+        Some(C::StakeVoteDelegCert(x)) => CertificateParts {
+            kinds: &[StakeDelegation, VoteDelegation],
+            stake: x.stake_credential.as_ref(),
+            pool: Some(x.pool_keyhash.as_ref()),
+            drep: drep_hash(x.drep.as_ref()),
+            ..Default::default()
+        },
+        Some(C::StakeRegDelegCert(x)) => CertificateParts {
+            kinds: &[StakeRegistration, StakeDelegation],
+            stake: x.stake_credential.as_ref(),
+            pool: Some(x.pool_keyhash.as_ref()),
+            ..Default::default()
+        },
+        Some(C::VoteRegDelegCert(x)) => CertificateParts {
+            kinds: &[StakeRegistration, VoteDelegation],
+            stake: x.stake_credential.as_ref(),
+            drep: drep_hash(x.drep.as_ref()),
+            ..Default::default()
+        },
+        Some(C::StakeVoteRegDelegCert(x)) => CertificateParts {
+            kinds: &[StakeRegistration, StakeDelegation, VoteDelegation],
+            stake: x.stake_credential.as_ref(),
+            pool: Some(x.pool_keyhash.as_ref()),
+            drep: drep_hash(x.drep.as_ref()),
+            ..Default::default()
+        },
+        Some(C::PoolRegistration(x)) => CertificateParts {
+            kinds: &[PoolRegistration],
+            pool: Some(x.operator.as_ref()),
+            ..Default::default()
+        },
+        Some(C::PoolRetirement(x)) => CertificateParts {
+            kinds: &[PoolRetirement],
+            pool: Some(x.pool_keyhash.as_ref()),
+            ..Default::default()
+        },
+        Some(C::RegDrepCert(x)) => CertificateParts {
+            kinds: &[DrepRegistration],
+            drep: credential_hash(x.drep_credential.as_ref()),
+            ..Default::default()
+        },
+        Some(C::UnregDrepCert(x)) => CertificateParts {
+            kinds: &[DrepDeregistration],
+            drep: credential_hash(x.drep_credential.as_ref()),
+            ..Default::default()
+        },
+        Some(C::UpdateDrepCert(x)) => CertificateParts {
+            kinds: &[DrepUpdate],
+            drep: credential_hash(x.drep_credential.as_ref()),
+            ..Default::default()
+        },
+        Some(C::AuthCommitteeHotCert(x)) => CertificateParts {
+            kinds: &[CommitteeHotAuthorization],
+            committee: credential_hash(x.committee_cold_credential.as_ref()),
+            ..Default::default()
+        },
+        Some(C::ResignCommitteeColdCert(x)) => CertificateParts {
+            kinds: &[CommitteeColdResignation],
+            committee: credential_hash(x.committee_cold_credential.as_ref()),
+            ..Default::default()
+        },
+        _ => CertificateParts::default(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CertificatePattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<CertificateKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stake: Option<StringOrStruct<CredentialPattern>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pool: Option<StringOrStruct<PoolPattern>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    drep: Option<StringOrStruct<CredentialPattern>>,
+
+    /// The cold credential of a constitutional committee member
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    committee: Option<StringOrStruct<CredentialPattern>>,
+}
+
+impl FromBech32 for CertificatePattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            "pool" => Some(Self {
+                pool: Some(PoolPattern::from_bech32_parts(hrp, content)?.into()),
+                ..Default::default()
+            }),
+            "drep" | "drep_script" => Some(Self {
+                drep: Some(CredentialPattern::from_bech32_parts(hrp, content)?.into()),
+                ..Default::default()
+            }),
+            "cc_cold" | "cc_cold_script" => Some(Self {
+                committee: Some(CredentialPattern::from_bech32_parts(hrp, content)?.into()),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl PatternOf<&Certificate> for CertificatePattern {
+    fn is_match(&self, subject: &Certificate) -> MatchOutcome {
+        let parts = certificate_parts(subject);
+
+        let a = match self.kind {
+            Some(x) => MatchOutcome::if_true(parts.kinds.contains(&x)),
+            None => MatchOutcome::Positive,
+        };
+
+        // a pattern on a part the certificate doesn't have is a mismatch
+        let b = match &self.stake {
+            Some(x) => x.is_any_match(parts.stake.into_iter()),
+            None => MatchOutcome::Positive,
+        };
+
+        let c = match &self.pool {
+            Some(x) => x.is_any_match(parts.pool.into_iter()),
+            None => MatchOutcome::Positive,
+        };
+
+        let d = match &self.drep {
+            Some(x) => x.is_any_match(parts.drep.into_iter()),
+            None => MatchOutcome::Positive,
+        };
+
+        let e = match &self.committee {
+            Some(x) => x.is_any_match(parts.committee.into_iter()),
+            None => MatchOutcome::Positive,
+        };
+
+        MatchOutcome::fold_all_of([a, b, c, d, e].into_iter())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WithdrawalPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reward_account: Option<StringOrStruct<CredentialPattern>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    coin: Option<CoinPattern>,
+}
+
+impl FromStr for WithdrawalPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            reward_account: Some(CredentialPattern::from_str(s)?.into()),
+            ..Default::default()
+        })
+    }
+}
+
+/// Matches the credential of a reward account, skipping its header byte
+pub fn reward_account_match(
+    pattern: &Option<StringOrStruct<CredentialPattern>>,
+    subject: &[u8],
+) -> MatchOutcome {
+    match pattern {
+        Some(x) => x.is_any_match(subject.get(1..).into_iter()),
+        None => MatchOutcome::Positive,
+    }
+}
+
+impl PatternOf<&Withdrawal> for WithdrawalPattern {
+    fn is_match(&self, subject: &Withdrawal) -> MatchOutcome {
+        let a = reward_account_match(&self.reward_account, subject.reward_account.as_ref());
+
+        let b = self.coin.is_match(subject.coin);
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::interop::utxorpc::spec::cardano::{StakeDelegationCert, StakeVoteRegDelegCert, Tx};
+
+    use super::*;
+
+    const POOL: &str = "pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy";
+    const POOL_HEX: &str = "0f292fcaa02b8b2f9b3c8f9fd8e0bb21abedb692a6d5058df3ef2735";
+    const STAKE: &str = "stake178phkx6acpnf78fuvxn0mkew3l0fd058hzquvz7w36x4gtcccycj5";
+    const STAKE_HEX: &str = "c37b1b5dc0669f1d3c61a6fddb2e8fde96be87b881c60bce8e8d542f";
+
+    fn key_credential(hex: &str) -> StakeCredential {
+        StakeCredential {
+            stake_credential: stake_credential::StakeCredential::AddrKeyHash(
+                hex::decode(hex).unwrap().into(),
+            )
+            .into(),
+        }
+    }
+
+    fn certs_tx() -> Tx {
+        let delegation = certificate::Certificate::StakeDelegation(StakeDelegationCert {
+            stake_credential: key_credential(STAKE_HEX).into(),
+            pool_keyhash: hex::decode(POOL_HEX).unwrap().into(),
+        });
+
+        let combined = certificate::Certificate::StakeVoteRegDelegCert(StakeVoteRegDelegCert {
+            stake_credential: key_credential(&"ab".repeat(28)).into(),
+            pool_keyhash: vec![0xcd; 28].into(),
+            drep: DRep {
+                drep: d_rep::Drep::AddrKeyHash(vec![0xef; 28].into()).into(),
+            }
+            .into(),
+            coin: 2_000_000,
+        });
+
+        Tx {
+            certificates: [delegation, combined]
+                .into_iter()
+                .map(|x| Certificate {
+                    certificate: x.into(),
+                    ..Default::default()
+                })
+                .collect(),
+            withdrawals: vec![Withdrawal {
+                reward_account: [vec![0xe1], hex::decode(STAKE_HEX).unwrap()]
+                    .concat()
+                    .into(),
+                coin: 5_000_000,
+                redeemer: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn outcome(predicate: &str) -> MatchOutcome {
+        let predicate = serde_json::from_str::<StringOrStruct<Predicate>>(predicate).unwrap();
+        eval_subject(&certs_tx(), &predicate)
+    }
+
+    #[test]
+    fn parse_stake_credential() {
+        let pattern = CredentialPattern::from_str(STAKE).unwrap();
+        assert_eq!(pattern, CredentialPattern::from_str(STAKE_HEX).unwrap());
+    }
+
+    #[test]
+    fn delegation_match() {
+        let predicate = format!(
+            r#"{{ "match": {{ "certificate": {{ "kind": "stake_delegation", "pool": "{POOL}" }} }} }}"#
+        );
+        assert_eq!(outcome(&predicate), MatchOutcome::Positive);
+
+        let predicate = format!(
+            r#"{{ "match": {{ "certificate": {{ "kind": "pool_retirement", "pool": "{POOL}" }} }} }}"#
+        );
+        assert_eq!(outcome(&predicate), MatchOutcome::Negative);
+
+        let predicate = format!(r#"{{ "match": {{ "certificate": {{ "stake": "{STAKE}" }} }} }}"#);
+        assert_eq!(outcome(&predicate), MatchOutcome::Positive);
+    }
+
+    #[test]
+    fn combined_certificate_match() {
+        let drep = "ef".repeat(28);
+
+        let predicate = format!(
+            r#"{{ "match": {{ "certificate": {{ "kind": "vote_delegation", "drep": "{drep}" }} }} }}"#
+        );
+        assert_eq!(outcome(&predicate), MatchOutcome::Positive);
+
+        let predicate = r#"{ "match": { "certificate": { "kind": "stake_registration" } } }"#;
+        assert_eq!(outcome(predicate), MatchOutcome::Positive);
+
+        let predicate = r#"{ "match": { "certificate": { "kind": "drep_registration" } } }"#;
+        assert_eq!(outcome(predicate), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn bech32_shorthand_match() {
+        assert_eq!(outcome(&format!(r#""{POOL}""#)), MatchOutcome::Positive);
+
+        // the same drep as a CIP-129 and as a CIP-105 id
+        let predicate = r#""drep1yth7lml0alh7lml0alh7lml0alh7lml0alh7lml0alh7lmcy406he""#;
+        assert_eq!(outcome(predicate), MatchOutcome::Positive);
+
+        let predicate = r#""drep1alh7lml0alh7lml0alh7lml0alh7lml0alh7lml0alh77dygk5f""#;
+        assert_eq!(outcome(predicate), MatchOutcome::Positive);
+
+        let predicate = r#""cc_cold1z2amhwamhwamhwamhwamhwamhwamhwamhwamhwamhwamhwczxsytf""#;
+        assert_eq!(outcome(predicate), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn tx_pattern_match() {
+        let predicate = format!(
+            r#"{{ "match": {{ "tx": {{ "certificates": [{{ "pool": "{POOL}" }}], "withdrawals": [{{ "coin": {{ "gte": 1000000 }} }}] }} }} }}"#
+        );
+        assert_eq!(outcome(&predicate), MatchOutcome::Positive);
+
+        let predicate = format!(
+            r#"{{ "match": {{ "tx": {{ "certificates": [{{ "kind": "pool_retirement", "pool": "{POOL}" }}] }} }} }}"#
+        );
+        assert_eq!(outcome(&predicate), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn withdrawal_match() {
+        let predicate = format!(r#"{{ "match": {{ "withdrawal": "{STAKE}" }} }}"#);
+        assert_eq!(outcome(&predicate), MatchOutcome::Positive);
+
+        let predicate = format!(
+            r#"{{ "match": {{ "withdrawal": {{ "reward_account": "{STAKE}", "coin": {{ "gte": 10000000 }} }} }} }}"#
+        );
+        assert_eq!(outcome(&predicate), MatchOutcome::Negative);
+    }
+}
@@ -0,0 +1,253 @@
+//! Patterns for the Conway governance data of a tx
+//!
+//! Votes and proposals aren't part of the utxorpc mapping of a tx, so these
+//! patterns are matched against the ledger primitives and only raw cbor
+//! records can be matched. A predicate with vote or proposal patterns is
+//! rejected at bootstrap when it's placed after a `ParseCbor` stage, see
+//! [`Predicate::has_governance_patterns`]; any parsed record that still
+//! reaches it results in an uncertain outcome.
+
+use pallas::ledger::primitives::conway::{
+    GovAction, GovActionId, ProposalProcedure, Vote, Voter, VotingProcedure, VotingProcedures,
+};
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VoterKind {
+    Committee,
+    Drep,
+    Pool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteKind {
+    Yes,
+    No,
+    Abstain,
+}
+
+/// A single vote of a voter on a governance action
+pub type VoteSubject<'a> = (&'a Voter, &'a GovActionId, &'a VotingProcedure);
+
+/// Flattens the voting procedures of a tx into the individual votes
+pub fn iter_votes(procedures: &VotingProcedures) -> impl Iterator<Item = VoteSubject<'_>> {
+    procedures.iter().flat_map(|(voter, votes)| {
+        votes
+            .iter()
+            .map(move |(action, procedure)| (voter, action, procedure))
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct VotePattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    voter: Option<StringOrStruct<CredentialPattern>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    voter_kind: Option<VoterKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vote: Option<VoteKind>,
+
+    /// The hash of the tx that proposed the voted action
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    action_tx: Option<FlexBytes>,
+}
+
+impl FromBech32 for VotePattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            // committee members vote with their hot credential
+            "cc_hot" | "cc_hot_script" => Some(Self {
+                voter: Some(CredentialPattern::from_bech32_parts(hrp, content)?.into()),
+                voter_kind: Some(VoterKind::Committee),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl PatternOf<VoteSubject<'_>> for VotePattern {
+    fn is_match(&self, subject: VoteSubject) -> MatchOutcome {
+        let (voter, action, procedure) = subject;
+
+        let (kind, hash) = match voter {
+            Voter::ConstitutionalCommitteeKey(x) => (VoterKind::Committee, x),
+            Voter::ConstitutionalCommitteeScript(x) => (VoterKind::Committee, x),
+            Voter::DRepKey(x) => (VoterKind::Drep, x),
+            Voter::DRepScript(x) => (VoterKind::Drep, x),
+            Voter::StakePoolKey(x) => (VoterKind::Pool, x),
+        };
+
+        let a = self.voter.is_match(hash.as_ref());
+
+        let b = match self.voter_kind {
+            Some(x) => MatchOutcome::if_equal(&x, &kind),
+            None => MatchOutcome::Positive,
+        };
+
+        let vote = match procedure.vote {
+            Vote::Yes => VoteKind::Yes,
+            Vote::No => VoteKind::No,
+            Vote::Abstain => VoteKind::Abstain,
+        };
+
+        let c = match self.vote {
+            Some(x) => MatchOutcome::if_equal(&x, &vote),
+            None => MatchOutcome::Positive,
+        };
+
+        let d = self.action_tx.is_match(action.transaction_id.as_ref());
+
+        MatchOutcome::fold_all_of([a, b, c, d].into_iter())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GovActionKind {
+    ParameterChange,
+    HardForkInitiation,
+    TreasuryWithdrawals,
+    NoConfidence,
+    UpdateCommittee,
+    NewConstitution,
+    Information,
+}
+
+impl From<&GovAction> for GovActionKind {
+    fn from(value: &GovAction) -> Self {
+        match value {
+            GovAction::ParameterChange(..) => GovActionKind::ParameterChange,
+            GovAction::HardForkInitiation(..) => GovActionKind::HardForkInitiation,
+            GovAction::TreasuryWithdrawals(..) => GovActionKind::TreasuryWithdrawals,
+            GovAction::NoConfidence(..) => GovActionKind::NoConfidence,
+            GovAction::UpdateCommittee(..) => GovActionKind::UpdateCommittee,
+            GovAction::NewConstitution(..) => GovActionKind::NewConstitution,
+            GovAction::Information => GovActionKind::Information,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProposalPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    action: Option<GovActionKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deposit: Option<CoinPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reward_account: Option<StringOrStruct<CredentialPattern>>,
+}
+
+impl PatternOf<&ProposalProcedure> for ProposalPattern {
+    fn is_match(&self, subject: &ProposalProcedure) -> MatchOutcome {
+        let a = match self.action {
+            Some(x) => MatchOutcome::if_equal(&x, &GovActionKind::from(&subject.gov_action)),
+            None => MatchOutcome::Positive,
+        };
+
+        let b = self.deposit.is_match(subject.deposit);
+
+        let c = reward_account_match(&self.reward_account, subject.reward_account.as_ref());
+
+        MatchOutcome::fold_all_of([a, b, c].into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::codec::utils::{NonEmptyKeyValuePairs, Nullable};
+    use pallas::ledger::primitives::conway::Anchor;
+
+    use super::*;
+
+    fn votes() -> VotingProcedures {
+        let vote = |vote| VotingProcedure {
+            vote,
+            anchor: Nullable::Null,
+        };
+
+        let action = |x: u8| GovActionId {
+            transaction_id: [x; 32].into(),
+            action_index: 0,
+        };
+
+        NonEmptyKeyValuePairs::Def(vec![
+            (
+                Voter::DRepKey([0xaa; 28].into()),
+                NonEmptyKeyValuePairs::Def(vec![(action(1), vote(Vote::Yes))]),
+            ),
+            (
+                Voter::StakePoolKey([0xbb; 28].into()),
+                NonEmptyKeyValuePairs::Def(vec![
+                    (action(1), vote(Vote::No)),
+                    (action(2), vote(Vote::Abstain)),
+                ]),
+            ),
+        ])
+    }
+
+    fn vote_outcome(pattern: &str) -> MatchOutcome {
+        let pattern: VotePattern = serde_json::from_str(pattern).unwrap();
+        pattern.is_any_match(iter_votes(&votes()))
+    }
+
+    #[test]
+    fn vote_match() {
+        let drep = "aa".repeat(28);
+
+        let pattern = format!(r#"{{ "voter": "{drep}", "vote": "yes" }}"#);
+        assert_eq!(vote_outcome(&pattern), MatchOutcome::Positive);
+
+        let pattern = format!(r#"{{ "voter": "{drep}", "vote": "no" }}"#);
+        assert_eq!(vote_outcome(&pattern), MatchOutcome::Negative);
+
+        let action = "02".repeat(32);
+
+        let pattern = format!(r#"{{ "voter_kind": "pool", "action_tx": "{action}" }}"#);
+        assert_eq!(vote_outcome(&pattern), MatchOutcome::Positive);
+
+        let pattern = format!(r#"{{ "voter_kind": "drep", "action_tx": "{action}" }}"#);
+        assert_eq!(vote_outcome(&pattern), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn proposal_match() {
+        let proposal = ProposalProcedure {
+            deposit: 100_000_000_000,
+            reward_account: [vec![0xe1], vec![0xcc; 28]].concat().into(),
+            gov_action: GovAction::Information,
+            anchor: Anchor {
+                url: "https://example.com".into(),
+                content_hash: [0; 32].into(),
+            },
+        };
+
+        let outcome = |pattern: &str| {
+            let pattern: ProposalPattern = serde_json::from_str(pattern).unwrap();
+            pattern.is_match(&proposal)
+        };
+
+        let account = "cc".repeat(28);
+
+        let pattern = format!(r#"{{ "action": "information", "reward_account": "{account}" }}"#);
+        assert_eq!(outcome(&pattern), MatchOutcome::Positive);
+
+        assert_eq!(
+            outcome(r#"{ "action": "treasury_withdrawals" }"#),
+            MatchOutcome::Negative
+        );
+
+        assert_eq!(
+            outcome(r#"{ "deposit": { "lte": 1000000 } }"#),
+            MatchOutcome::Negative
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Patterns for json records, such as the output of plugins or legacy events
+//!
+//! Values are selected with a subset of JSONPath: the `$` root followed by
+//! `.name`, `['name']`, `[index]` (negative counts from the end), the `*`
+//! wildcard and the `..` recursive descent.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value as JsonValue};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use super::*;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Selector {
+    Field(String),
+    Index(i64),
+    Wildcard,
+}
+
+impl Selector {
+    fn select<'a>(&self, value: &'a JsonValue, out: &mut Vec<&'a JsonValue>) {
+        match (self, value) {
+            (Selector::Field(x), JsonValue::Object(map)) => out.extend(map.get(x)),
+            (Selector::Index(x), JsonValue::Array(items)) => {
+                let idx = match *x {
+                    x if x < 0 => items.len().checked_sub(x.unsigned_abs() as usize),
+                    x => Some(x as usize),
+                };
+
+                out.extend(idx.and_then(|x| items.get(x)));
+            }
+            (Selector::Wildcard, JsonValue::Object(map)) => out.extend(map.values()),
+            (Selector::Wildcard, JsonValue::Array(items)) => out.extend(items.iter()),
+            _ => (),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Step {
+    selector: Selector,
+    /// Applies the selector to the value and all of its descendants
+    recursive: bool,
+}
+
+fn push_descendants<'a>(value: &'a JsonValue, out: &mut Vec<&'a JsonValue>) {
+    out.push(value);
+
+    match value {
+        JsonValue::Object(map) => map.values().for_each(|x| push_descendants(x, out)),
+        JsonValue::Array(items) => items.iter().for_each(|x| push_descendants(x, out)),
+        _ => (),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct JsonPath {
+    source: String,
+    steps: Vec<Step>,
+}
+
+fn parse_bracket(content: &str) -> anyhow::Result<Selector> {
+    let content = content.trim();
+
+    if content == "*" {
+        return Ok(Selector::Wildcard);
+    }
+
+    let quoted = ['\'', '"'].iter().find_map(|q| {
+        content
+            .strip_prefix(*q)
+            .and_then(|x| x.strip_suffix(*q))
+            .filter(|_| content.len() > 1)
+    });
+
+    if let Some(name) = quoted {
+        return Ok(Selector::Field(name.to_owned()));
+    }
+
+    match content.parse() {
+        Ok(idx) => Ok(Selector::Index(idx)),
+        Err(_) => anyhow::bail!("invalid selector '[{content}]'"),
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = match s.strip_prefix('$') {
+            Some(x) => x,
+            None => anyhow::bail!("json paths must start with '$'"),
+        };
+
+        let mut steps = vec![];
+
+        while !rest.is_empty() {
+            let recursive = rest.starts_with("..");
+
+            rest = if recursive {
+                &rest[2..]
+            } else if let Some(x) = rest.strip_prefix('.') {
+                x
+            } else if rest.starts_with('[') {
+                rest
+            } else {
+                anyhow::bail!("expected '.' or '[' in json path at '{rest}'");
+            };
+
+            let selector = if let Some(x) = rest.strip_prefix('[') {
+                let end = match x.find(']') {
+                    Some(end) => end,
+                    None => anyhow::bail!("unclosed '[' in json path"),
+                };
+
+                rest = &x[end + 1..];
+                parse_bracket(&x[..end])?
+            } else {
+                let end = rest.find(['.', '[']).unwrap_or(rest.len());
+                let name = &rest[..end];
+                rest = &rest[end..];
+
+                match name {
+                    "" => anyhow::bail!("empty field name in json path"),
+                    "*" => Selector::Wildcard,
+                    x => Selector::Field(x.to_owned()),
+                }
+            };
+
+            steps.push(Step {
+                selector,
+                recursive,
+            });
+        }
+
+        Ok(Self {
+            source: s.to_owned(),
+            steps,
+        })
+    }
+}
+
+impl TryFrom<String> for JsonPath {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::from_str(&value)
+    }
+}
+
+impl From<JsonPath> for String {
+    fn from(value: JsonPath) -> Self {
+        value.source
+    }
+}
+
+impl JsonPath {
+    /// The values of the document that the path points to
+    pub fn select<'a>(&self, root: &'a JsonValue) -> Vec<&'a JsonValue> {
+        let mut current = vec![root];
+
+        for step in self.steps.iter() {
+            let mut next = vec![];
+
+            for value in current {
+                if step.recursive {
+                    let mut descendants = vec![];
+                    push_descendants(value, &mut descendants);
+
+                    for x in descendants {
+                        step.selector.select(x, &mut next);
+                    }
+                } else {
+                    step.selector.select(value, &mut next);
+                }
+            }
+
+            current = next;
+        }
+
+        current
+    }
+}
+
+/// Compares json numbers as integers when both are, so large amounts don't
+/// lose precision
+fn compare_numbers(a: &Number, b: &Number) -> Option<Ordering> {
+    let as_int = |x: &Number| {
+        x.as_i64()
+            .map(i128::from)
+            .or_else(|| x.as_u64().map(i128::from))
+    };
+
+    match (as_int(a), as_int(b)) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JsonPattern {
+    path: JsonPath,
+
+    /// Whether the path points to any value, true unless stated otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exists: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    eq: Option<JsonValue>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gt: Option<Number>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gte: Option<Number>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lt: Option<Number>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lte: Option<Number>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regex: Option<TextRegex>,
+}
+
+impl FromStr for JsonPattern {
+    type Err = anyhow::Error;
+
+    /// Parses a json path as a pattern on the existence of the value
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            path: JsonPath::from_str(s)?,
+            exists: None,
+            eq: None,
+            gt: None,
+            gte: None,
+            lt: None,
+            lte: None,
+            regex: None,
+        })
+    }
+}
+
+impl JsonPattern {
+    fn has_value_operators(&self) -> bool {
+        self.eq.is_some()
+            || self.gt.is_some()
+            || self.gte.is_some()
+            || self.lt.is_some()
+            || self.lte.is_some()
+            || self.regex.is_some()
+    }
+
+    fn is_value_match(&self, subject: &JsonValue) -> MatchOutcome {
+        let a = match (&self.eq, subject) {
+            (None, _) => MatchOutcome::Positive,
+            (Some(JsonValue::Number(x)), JsonValue::Number(y)) => {
+                MatchOutcome::if_equal(&compare_numbers(y, x), &Some(Ordering::Equal))
+            }
+            (Some(x), y) => MatchOutcome::if_equal(x, y),
+        };
+
+        let bounds = [
+            (&self.gt, Ordering::is_gt as fn(Ordering) -> bool),
+            (&self.gte, Ordering::is_ge),
+            (&self.lt, Ordering::is_lt),
+            (&self.lte, Ordering::is_le),
+        ];
+
+        let b = bounds.into_iter().map(|(bound, check)| match bound {
+            None => MatchOutcome::Positive,
+            Some(bound) => match subject.as_number().and_then(|x| compare_numbers(x, bound)) {
+                Some(ordering) => MatchOutcome::if_true(check(ordering)),
+                None => MatchOutcome::Negative,
+            },
+        });
+
+        let b = MatchOutcome::fold_all_of(b);
+
+        let c = match (&self.regex, subject.as_str()) {
+            (None, _) => MatchOutcome::Positive,
+            (Some(x), Some(subject)) => x.is_match(subject),
+            (Some(_), None) => MatchOutcome::Negative,
+        };
+
+        MatchOutcome::fold_all_of([a, b, c].into_iter())
+    }
+}
+
+impl PatternOf<&JsonValue> for JsonPattern {
+    fn is_match(&self, subject: &JsonValue) -> MatchOutcome {
+        let values = self.path.select(subject);
+
+        let a = MatchOutcome::if_equal(&self.exists.unwrap_or(true), &!values.is_empty());
+
+        // any of the selected values is enough
+        let b = match self.has_value_operators() {
+            true => MatchOutcome::fold_any_of(values.into_iter().map(|x| self.is_value_match(x))),
+            false => MatchOutcome::Positive,
+        };
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+impl PatternOf<&JsonValue> for Pattern {
+    fn is_match(&self, subject: &JsonValue) -> MatchOutcome {
+        match self {
+            Pattern::Json(x) => x.is_match(subject),
+            // chain data patterns can't be told apart from arbitrary json
+            _ => MatchOutcome::Uncertain,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::framework::legacy_v1::{CIP25AssetRecord, Event, EventContext, EventData};
+
+    use super::*;
+
+    fn document() -> JsonValue {
+        serde_json::json!({
+            "policy": "7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373",
+            "assets": [
+                { "name": "abc1", "amount": 18446744073709551615u64, "tags": ["art"] },
+                { "name": "abc2", "amount": 5, "price": 1.5 },
+            ],
+            "weird key": { "name": "nested" },
+        })
+    }
+
+    fn select(path: &str) -> Vec<JsonValue> {
+        let path = JsonPath::from_str(path).unwrap();
+        path.select(&document()).into_iter().cloned().collect()
+    }
+
+    #[test]
+    fn parse_paths() {
+        assert!(JsonPath::from_str("$").is_ok());
+        assert!(JsonPath::from_str("$.assets[0].name").is_ok());
+        assert!(JsonPath::from_str("$..name").is_ok());
+        assert!(JsonPath::from_str("$['weird key'].name").is_ok());
+
+        assert!(JsonPath::from_str("assets").is_err());
+        assert!(JsonPath::from_str("$.assets[0").is_err());
+        assert!(JsonPath::from_str("$.assets[x]").is_err());
+        assert!(JsonPath::from_str("$.").is_err());
+        assert!(JsonPath::from_str("$assets").is_err());
+    }
+
+    #[test]
+    fn select_values() {
+        assert_eq!(select("$.assets[1].name"), vec!["abc2"]);
+        assert_eq!(select("$.assets[-1].name"), vec!["abc2"]);
+        assert_eq!(select("$.assets[*].name"), vec!["abc1", "abc2"]);
+        assert_eq!(select("$..name"), vec!["abc1", "abc2", "nested"]);
+        assert_eq!(select("$[\"weird key\"].name"), vec!["nested"]);
+        assert_eq!(select("$.assets[0].tags.*"), vec!["art"]);
+        assert!(select("$.assets[2]").is_empty());
+        assert!(select("$.policy.name").is_empty());
+    }
+
+    fn outcome(pattern: &str) -> MatchOutcome {
+        let predicate: StringOrStruct<Predicate> = serde_json::from_str(pattern).unwrap();
+        eval(&Record::GenericJson(document()), &predicate)
+    }
+
+    #[test]
+    fn json_pattern_match() {
+        assert_eq!(outcome(r#""$.assets[0].tags""#), MatchOutcome::Positive);
+        assert_eq!(outcome(r#""$.assets[1].tags""#), MatchOutcome::Negative);
+
+        let pattern = r#"{ "match": { "json": { "path": "$.assets[1].tags", "exists": false } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Positive);
+
+        let pattern =
+            r#"{ "match": { "json": { "path": "$..amount", "gte": 18446744073709551615 } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Positive);
+
+        let pattern = r#"{ "match": { "json": { "path": "$..amount", "gt": 5, "lt": 100 } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Negative);
+
+        let pattern = r#"{ "match": { "json": { "path": "$..price", "eq": 1.5 } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Positive);
+
+        let pattern =
+            r#"{ "match": { "json": { "path": "$.assets[*].name", "regex": "^abc\\d$" } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Positive);
+
+        let pattern = r#"{ "match": { "json": { "path": "$.policy", "regex": "^abc" } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Negative);
+
+        let pattern = r#"{ "all": ["$.policy", { "not": "$.missing" }] }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Positive);
+
+        // chain data patterns are undecided on json records
+        assert_eq!(outcome(r##""#127""##), MatchOutcome::Uncertain);
+    }
+
+    #[test]
+    fn legacy_event_match() {
+        let event = |policy: &str| {
+            Record::OuraV1Event(Event {
+                context: EventContext::default(),
+                data: EventData::CIP25Asset(CIP25AssetRecord {
+                    version: "1.0".into(),
+                    policy: policy.into(),
+                    asset: "abc1".into(),
+                    name: None,
+                    image: None,
+                    media_type: None,
+                    description: None,
+                    raw_json: JsonValue::Null,
+                }),
+                fingerprint: None,
+            })
+        };
+
+        let predicate: StringOrStruct<Predicate> = serde_json::from_str(
+            r#"{ "match": { "json": { "path": "$.cip25_asset.policy", "eq": "7eae28af" } } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(eval(&event("7eae28af"), &predicate), MatchOutcome::Positive);
+        assert_eq!(eval(&event("1e349c9b"), &predicate), MatchOutcome::Negative);
+    }
+}
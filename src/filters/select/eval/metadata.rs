@@ -1,21 +1,104 @@
+use pallas::interop::utxorpc::spec::cardano::metadatum;
+
 use super::*;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum MetadatumPattern {
-    Text(TextPattern),
+    Text(StringOrStruct<TextPattern>),
     Int(NumericPattern<i64>),
-    // TODO: bytes, array, map
+    Bytes(FlexBytes),
+
+    /// Matches arrays with at least one item matching the inner pattern
+    Array(Box<MetadatumPattern>),
+
+    /// Descends into nested maps following a path of keys
+    Path(MetadatumPathPattern),
 }
 
 impl PatternOf<&Metadatum> for MetadatumPattern {
     fn is_match(&self, subject: &Metadatum) -> MatchOutcome {
         match self {
             MetadatumPattern::Text(x) => x.is_match(subject),
-            MetadatumPattern::Int(_) => todo!(),
+            MetadatumPattern::Int(x) => match subject.metadatum.as_ref() {
+                Some(metadatum::Metadatum::Int(subject)) => x.is_match(*subject),
+                _ => MatchOutcome::Negative,
+            },
+            MetadatumPattern::Bytes(x) => match subject.metadatum.as_ref() {
+                Some(metadatum::Metadatum::Bytes(subject)) => x.is_match(subject.as_ref()),
+                _ => MatchOutcome::Negative,
+            },
+            MetadatumPattern::Array(x) => match subject.metadatum.as_ref() {
+                Some(metadatum::Metadatum::Array(subject)) => x.is_any_match(subject.items.iter()),
+                _ => MatchOutcome::Negative,
+            },
+            MetadatumPattern::Path(x) => x.is_match(subject),
         }
     }
 }
 
+/// Wildcard that matches any key of a map
+const ANY_KEY: &str = "*";
+
+/// Checks a map key against a key of the path
+///
+/// Text keys are compared as they are, bytes keys by their hex encoding and
+/// int keys by their decimal representation.
+fn is_key_match(expected: &str, key: Option<&Metadatum>) -> bool {
+    if expected == ANY_KEY {
+        return true;
+    }
+
+    match key.and_then(|x| x.metadatum.as_ref()) {
+        Some(metadatum::Metadatum::Text(x)) => x == expected,
+        Some(metadatum::Metadatum::Bytes(x)) => hex::encode(x).eq_ignore_ascii_case(expected),
+        Some(metadatum::Metadatum::Int(x)) => x.to_string() == expected,
+        _ => false,
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MetadatumPathPattern {
+    keys: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<Box<MetadatumPattern>>,
+}
+
+impl MetadatumPathPattern {
+    fn is_match_at(&self, depth: usize, subject: &Metadatum) -> MatchOutcome {
+        let key = match self.keys.get(depth) {
+            Some(x) => x,
+            None => {
+                return match &self.value {
+                    Some(x) => x.is_match(subject),
+                    // the absence of a value pattern matches any value at the path
+                    None => MatchOutcome::Positive,
+                };
+            }
+        };
+
+        let map = match subject.metadatum.as_ref() {
+            Some(metadatum::Metadatum::Map(x)) => x,
+            _ => return MatchOutcome::Negative,
+        };
+
+        let outcomes = map
+            .pairs
+            .iter()
+            .filter(|x| is_key_match(key, x.key.as_ref()))
+            .flat_map(|x| x.value.as_ref())
+            .map(|x| self.is_match_at(depth + 1, x));
+
+        MatchOutcome::fold_any_of(outcomes)
+    }
+}
+
+impl PatternOf<&Metadatum> for MetadatumPathPattern {
+    fn is_match(&self, subject: &Metadatum) -> MatchOutcome {
+        self.is_match_at(0, subject)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct MetadataPattern {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -66,8 +149,57 @@ impl PatternOf<&AuxData> for MetadataPattern {
 
 #[cfg(test)]
 mod tests {
+    use pallas::interop::utxorpc::spec::cardano::{MetadatumArray, MetadatumMap, MetadatumPair};
+
     use super::*;
 
+    fn datum(x: metadatum::Metadatum) -> Metadatum {
+        Metadatum {
+            metadatum: x.into(),
+        }
+    }
+
+    fn text(x: &str) -> Metadatum {
+        datum(metadatum::Metadatum::Text(x.into()))
+    }
+
+    fn map(pairs: Vec<(Metadatum, Metadatum)>) -> Metadatum {
+        let pairs = pairs
+            .into_iter()
+            .map(|(k, v)| MetadatumPair {
+                key: k.into(),
+                value: v.into(),
+            })
+            .collect();
+
+        datum(metadatum::Metadatum::Map(MetadatumMap { pairs }))
+    }
+
+    /// A CIP-25 entry, with the policy as a bytes key
+    fn cip25() -> Metadata {
+        let policy = datum(metadatum::Metadatum::Bytes(vec![0xab; 28].into()));
+
+        let files = datum(metadatum::Metadatum::Array(MetadatumArray {
+            items: vec![text("ipfs://a"), text("ipfs://b")],
+        }));
+
+        let asset = map(vec![
+            (text("name"), text("SpaceBud #1234")),
+            (text("rank"), datum(metadatum::Metadatum::Int(42))),
+            (text("files"), files),
+        ]);
+
+        Metadata {
+            label: 721,
+            value: map(vec![(policy, map(vec![(text("SpaceBud1234"), asset)]))]).into(),
+        }
+    }
+
+    fn outcome(pattern: &str) -> MatchOutcome {
+        let pattern: MetadataPattern = serde_json::from_str(pattern).unwrap();
+        pattern.is_match(&cip25())
+    }
+
     #[test]
     fn pattern_parse() {
         let expected = MetadataPattern {
@@ -101,6 +233,71 @@ mod tests {
         assert_eq!(positives, Vec::<usize>::new());
     }
 
+    #[test]
+    fn text_value_match() {
+        let pattern = |value: &str| {
+            let value = serde_json::from_str(value).unwrap();
+
+            Pattern::Metadata(
+                MetadataPattern {
+                    value: Some(value),
+                    ..Default::default()
+                }
+                .into(),
+            )
+        };
+
+        let positives = testing::find_positive_test_vectors(pattern(r#"{ "Text": "lorem" }"#));
+        assert_eq!(positives, vec![1, 2]);
+
+        let positives =
+            testing::find_positive_test_vectors(pattern(r#"{ "Text": { "EndsWith": "sum" } }"#));
+        assert_eq!(positives, vec![1, 3]);
+
+        let positives = testing::find_positive_test_vectors(pattern(
+            r#"{ "Text": { "Regex": "^(lor|ips)" } }"#,
+        ));
+        assert_eq!(positives, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn path_match() {
+        let policy = hex::encode([0xab; 28]);
+
+        let pattern = format!(
+            r#"{{ "label": 721, "value": {{ "Path": {{ "keys": ["{policy}", "SpaceBud1234", "name"], "value": {{ "Text": {{ "Regex": "^SpaceBud" }} }} }} }} }}"#
+        );
+
+        assert_eq!(outcome(&pattern), MatchOutcome::Positive);
+
+        let pattern = r#"{ "value": { "Path": { "keys": ["*", "*", "name"], "value": { "Text": "SpaceBud #1" } } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Negative);
+
+        let pattern = r#"{ "value": { "Path": { "keys": ["*", "*", "rank"] } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Positive);
+
+        let pattern = r#"{ "value": { "Path": { "keys": ["*", "*", "missing"] } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn scalar_and_array_match() {
+        let pattern = r#"{ "value": { "Path": { "keys": ["*", "*", "rank"], "value": { "Int": { "between": [1, 100] } } } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Positive);
+
+        let pattern = r#"{ "value": { "Path": { "keys": ["*", "*", "rank"], "value": { "Int": { "gte": 100 } } } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Negative);
+
+        let pattern = r#"{ "value": { "Path": { "keys": ["*", "*", "files"], "value": { "Array": { "Text": "ipfs://b" } } } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Positive);
+
+        let pattern = r#"{ "value": { "Path": { "keys": ["*", "*", "name"], "value": { "Int": { "exact": 1 } } } } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Negative);
+
+        let pattern = r#"{ "value": { "Bytes": "abcd" } }"#;
+        assert_eq!(outcome(pattern), MatchOutcome::Negative);
+    }
+
     #[test]
     fn fingerprint_match() {
         let pattern = |fp: &str| Pattern::from(AssetPattern::from_str(fp).unwrap());
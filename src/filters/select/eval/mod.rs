@@ -1,7 +1,7 @@
 use std::{ops::Deref, str::FromStr};
 
 use pallas::interop::utxorpc::spec::cardano::{
-    Asset, AuxData, Metadata, Metadatum, Multiasset, TxInput, TxOutput,
+    Asset, AuxData, Datum, Metadata, Metadatum, Multiasset, Redeemer, TxInput, TxOutput,
 };
 use serde::{Deserialize, Serialize};
 use tracing::warn;
@@ -10,18 +10,34 @@ use crate::framework::*;
 
 mod address;
 mod assets;
+mod block;
 mod bytes;
+mod certs;
 mod cip14;
+mod governance;
+mod json;
 mod metadata;
+mod plutus;
+mod raw;
 mod serde_ext;
+mod text;
+mod watchlist;
 
 #[cfg(test)]
 mod testing;
 
 pub use address::*;
 pub use assets::*;
+pub use block::*;
 pub use bytes::*;
+pub use certs::*;
+pub use governance::*;
+pub use json::*;
 pub use metadata::*;
+pub use plutus::*;
+pub use raw::*;
+pub use text::*;
+pub use watchlist::*;
 
 pub use self::serde_ext::{FromBech32, StringOrStruct};
 
@@ -148,6 +164,15 @@ where
     }
 }
 
+impl<S, P> PatternOf<S> for Box<P>
+where
+    P: PatternOf<S>,
+{
+    fn is_match(&self, subject: S) -> MatchOutcome {
+        self.as_ref().is_match(subject)
+    }
+}
+
 impl PatternOf<&[u8]> for Vec<u8> {
     fn is_match(&self, subject: &[u8]) -> MatchOutcome {
         MatchOutcome::if_equal(self.as_ref(), subject)
@@ -183,83 +208,17 @@ pub enum NumericPattern<I: Ord + Eq> {
 
 pub type CoinPattern = NumericPattern<u64>;
 
-impl PatternOf<u64> for CoinPattern {
-    fn is_match(&self, subject: u64) -> MatchOutcome {
+impl<I: Ord + Eq> PatternOf<I> for NumericPattern<I> {
+    fn is_match(&self, subject: I) -> MatchOutcome {
         match self {
-            CoinPattern::Exact(x) => MatchOutcome::if_true(subject == *x),
-            CoinPattern::Gte(x) => MatchOutcome::if_true(subject >= *x),
-            CoinPattern::Lte(x) => MatchOutcome::if_true(subject <= *x),
-            CoinPattern::Between(a, b) => MatchOutcome::if_true(subject >= *a && subject <= *b),
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub enum TextPattern {
-    Exact(String),
-    // TODO: Regex
-}
-
-impl PatternOf<&str> for TextPattern {
-    fn is_match(&self, subject: &str) -> MatchOutcome {
-        match self {
-            TextPattern::Exact(x) => MatchOutcome::if_equal(x.as_str(), subject),
-        }
-    }
-}
-
-impl PatternOf<&[u8]> for TextPattern {
-    fn is_match(&self, subject: &[u8]) -> MatchOutcome {
-        let subject = match String::from_utf8(subject.to_vec()) {
-            Ok(subject) => subject,
-            Err(_) => return MatchOutcome::Uncertain,
-        };
-
-        self.is_match(subject.as_str())
-    }
-}
-
-impl PatternOf<&Metadatum> for TextPattern {
-    fn is_match(&self, subject: &Metadatum) -> MatchOutcome {
-        match subject.metadatum.as_ref() {
-            Some(pallas::interop::utxorpc::spec::cardano::metadatum::Metadatum::Text(subject)) => {
-                self.is_match(subject.as_str())
-            }
-            _ => MatchOutcome::Negative,
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct DatumPattern {
-    hash: Option<Vec<u8>>,
-}
-
-impl FromBech32 for DatumPattern {
-    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
-        match hrp {
-            "datum" => Some(Self {
-                hash: Some(content),
-            }),
-            _ => None,
+            NumericPattern::Exact(x) => MatchOutcome::if_true(subject == *x),
+            NumericPattern::Gte(x) => MatchOutcome::if_true(subject >= *x),
+            NumericPattern::Lte(x) => MatchOutcome::if_true(subject <= *x),
+            NumericPattern::Between(a, b) => MatchOutcome::if_true(subject >= *a && subject <= *b),
         }
     }
 }
 
-impl FromStr for DatumPattern {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::from_bech32(s)
-    }
-}
-
-impl PatternOf<&[u8]> for DatumPattern {
-    fn is_match(&self, subject: &[u8]) -> MatchOutcome {
-        self.hash.is_match(subject)
-    }
-}
-
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ScriptPattern {
     hash: Option<Vec<u8>>,
@@ -293,9 +252,7 @@ impl PatternOf<&TxOutput> for OutputPattern {
 
         let c = MatchOutcome::fold_all_of(c);
 
-        let d = self
-            .datum
-            .is_some_match(subject.datum.as_ref().map(|d| d.hash.as_ref()));
+        let d = self.datum.is_some_match(subject.datum.as_ref());
 
         MatchOutcome::fold_all_of([a, b, c, d].into_iter())
     }
@@ -314,8 +271,9 @@ pub struct InputPattern {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     datum: Option<StringOrStruct<DatumPattern>>,
-    // u5c redeemer structure is not suitable, is lacks a datum hash (and it also contains a
-    // redundant purpose flag) redeemer: Option<DatumPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    redeemer: Option<Box<PlutusDataPattern>>,
 }
 
 impl PatternOf<&TxInput> for InputPattern {
@@ -336,11 +294,11 @@ impl PatternOf<&TxInput> for InputPattern {
 
         let c = MatchOutcome::fold_all_of(c);
 
-        let d = self
-            .datum
-            .is_some_match(as_output.datum.as_ref().map(|x| x.hash.as_ref()));
+        let d = self.datum.is_some_match(as_output.datum.as_ref());
 
-        MatchOutcome::fold_all_of([a, b, c, d].into_iter())
+        let e = self.redeemer.is_some_match(subject.redeemer.as_ref());
+
+        MatchOutcome::fold_all_of([a, b, c, d, e].into_iter())
     }
 }
 
@@ -364,17 +322,23 @@ impl PatternOf<&Multiasset> for MintPattern {
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct TxPattern {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     inputs: Vec<InputPattern>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     outputs: Vec<OutputPattern>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     mint: Vec<MintPattern>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     metadata: Vec<MetadataPattern>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    certificates: Vec<CertificatePattern>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    withdrawals: Vec<WithdrawalPattern>,
     // the u5c struct is not suitable, it lacks hash for the scripts
     // scripts: Vec<ScriptPattern>,
 }
@@ -403,20 +367,22 @@ impl PatternOf<&ParsedTx> for TxPattern {
 
         let d = MatchOutcome::fold_all_of(d);
 
-        MatchOutcome::fold_all_of([a, b, c, d].into_iter())
-    }
-}
+        let e = self
+            .certificates
+            .iter()
+            .map(|x| x.is_any_match(tx.certificates.iter()));
 
-pub type SlotPattern = NumericPattern<u64>;
+        let e = MatchOutcome::fold_all_of(e);
 
-pub type EraPattern = NumericPattern<u8>;
+        let f = self
+            .withdrawals
+            .iter()
+            .map(|x| x.is_any_match(tx.withdrawals.iter()));
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct BlockPattern {
-    hash: Option<Vec<u8>>,
-    slot: Option<SlotPattern>,
-    era: Option<EraPattern>,
-    txs: Vec<TxPattern>,
+        let f = MatchOutcome::fold_all_of(f);
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f].into_iter())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -431,6 +397,13 @@ pub enum Pattern {
     Mint(MintPattern),
     Metadata(StringOrStruct<MetadataPattern>),
     Datum(StringOrStruct<DatumPattern>),
+    Redeemer(PlutusDataPattern),
+    Certificate(CertificatePattern),
+    Withdrawal(StringOrStruct<WithdrawalPattern>),
+    Vote(VotePattern),
+    Proposal(ProposalPattern),
+    Watchlist(WatchlistPattern),
+    Json(Box<JsonPattern>),
 }
 
 impl From<AssetPattern> for Pattern {
@@ -457,6 +430,24 @@ impl From<MetadataPattern> for Pattern {
     }
 }
 
+impl From<CertificatePattern> for Pattern {
+    fn from(value: CertificatePattern) -> Self {
+        Pattern::Certificate(value)
+    }
+}
+
+impl From<VotePattern> for Pattern {
+    fn from(value: VotePattern) -> Self {
+        Pattern::Vote(value)
+    }
+}
+
+impl From<JsonPattern> for Pattern {
+    fn from(value: JsonPattern) -> Self {
+        Pattern::Json(Box::new(value))
+    }
+}
+
 impl FromBech32 for Pattern {
     fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
         match hrp {
@@ -465,6 +456,19 @@ impl FromBech32 for Pattern {
             "addr_test" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
             "stake" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
             "datum" => DatumPattern::from_bech32_parts(hrp, content).map(From::from),
+            "addr_vkh" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
+            "stake_vkh" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
+            "script" => AddressPattern::from_bech32_parts(hrp, content).map(From::from),
+            "pool" => CertificatePattern::from_bech32_parts(hrp, content).map(From::from),
+            "drep" | "drep_script" => {
+                CertificatePattern::from_bech32_parts(hrp, content).map(From::from)
+            }
+            "cc_cold" | "cc_cold_script" => {
+                CertificatePattern::from_bech32_parts(hrp, content).map(From::from)
+            }
+            "cc_hot" | "cc_hot_script" => {
+                VotePattern::from_bech32_parts(hrp, content).map(From::from)
+            }
             _ => None,
         }
     }
@@ -482,6 +486,10 @@ impl FromStr for Pattern {
             return Ok(p.into());
         }
 
+        if let Ok(p) = JsonPattern::from_str(s) {
+            return Ok(p.into());
+        }
+
         anyhow::bail!("can't parse pattern from string");
     }
 }
@@ -506,20 +514,29 @@ fn iter_tx_assets(tx: &ParsedTx) -> impl Iterator<Item = &Multiasset> {
     a.chain(b)
 }
 
-fn iter_tx_datums(tx: &ParsedTx) -> impl Iterator<Item = &[u8]> {
-    let a = tx
-        .outputs
-        .iter()
-        .flat_map(|x| &x.datum)
-        .map(|x| x.hash.as_ref());
+fn iter_tx_datums(tx: &ParsedTx) -> impl Iterator<Item = &Datum> {
+    let a = tx.outputs.iter().flat_map(|x| &x.datum);
 
     a
 }
 
+fn iter_tx_redeemers(tx: &ParsedTx) -> impl Iterator<Item = &Redeemer> {
+    let a = tx.inputs.iter().flat_map(|x| &x.redeemer);
+
+    let b = tx.withdrawals.iter().flat_map(|x| &x.redeemer);
+
+    let c = tx.certificates.iter().flat_map(|x| &x.redeemer);
+
+    let d = tx.mint.iter().flat_map(|x| &x.redeemer);
+
+    a.chain(b).chain(c).chain(d)
+}
+
 impl PatternOf<&ParsedTx> for Pattern {
     fn is_match(&self, subject: &ParsedTx) -> MatchOutcome {
         match self {
-            Pattern::Block(_) => MatchOutcome::Negative,
+            // a tx record doesn't carry the block it belongs to
+            Pattern::Block(_) => MatchOutcome::Uncertain,
             Pattern::Tx(x) => x.is_match(subject),
             Pattern::Address(x) => x.is_any_match(iter_tx_addresses(subject)),
             Pattern::Asset(x) => x.is_any_match(iter_tx_assets(subject)),
@@ -528,6 +545,14 @@ impl PatternOf<&ParsedTx> for Pattern {
             Pattern::Mint(x) => x.is_any_match(subject.mint.iter()),
             Pattern::Metadata(x) => x.is_any_match(subject.auxiliary.iter()),
             Pattern::Datum(x) => x.is_any_match(iter_tx_datums(subject)),
+            Pattern::Redeemer(x) => x.is_any_match(iter_tx_redeemers(subject)),
+            Pattern::Certificate(x) => x.is_any_match(subject.certificates.iter()),
+            Pattern::Withdrawal(x) => x.is_any_match(subject.withdrawals.iter()),
+            // see the governance module, rejected at bootstrap
+            Pattern::Vote(_) => MatchOutcome::Uncertain,
+            Pattern::Proposal(_) => MatchOutcome::Uncertain,
+            Pattern::Watchlist(x) => x.is_match(subject),
+            Pattern::Json(_) => MatchOutcome::Uncertain,
         }
     }
 }
@@ -549,6 +574,32 @@ pub enum Predicate {
 }
 
 impl Predicate {
+    /// Loads the external data of the patterns, such as watchlists
+    pub fn bootstrap(&self, ctx: &Context) -> Result<(), Error> {
+        match self {
+            Predicate::Match(x) => match &**x {
+                Pattern::Watchlist(x) => x.bootstrap(ctx),
+                _ => Ok(()),
+            },
+            Predicate::Not(x) => x.bootstrap(ctx),
+            Predicate::AnyOf(x) | Predicate::AllOf(x) => {
+                x.iter().try_for_each(|x| x.bootstrap(ctx))
+            }
+        }
+    }
+
+    /// Whether any of the patterns checks the governance data of a tx, which
+    /// only raw cbor records carry
+    pub fn has_governance_patterns(&self) -> bool {
+        match self {
+            Predicate::Match(x) => matches!(&**x, Pattern::Vote(_) | Pattern::Proposal(_)),
+            Predicate::Not(x) => x.has_governance_patterns(),
+            Predicate::AnyOf(x) | Predicate::AllOf(x) => {
+                x.iter().any(|x| x.has_governance_patterns())
+            }
+        }
+    }
+
     pub fn any_of(p: Vec<Self>) -> Self {
         Predicate::AnyOf(p.into_iter().map(StringOrStruct).collect())
     }
@@ -556,9 +607,13 @@ impl Predicate {
     pub fn all_of(p: Vec<Self>) -> Self {
         Predicate::AllOf(p.into_iter().map(StringOrStruct).collect())
     }
+}
+
+impl core::ops::Not for Predicate {
+    type Output = Self;
 
-    pub fn not(p: Self) -> Self {
-        Predicate::Not(Box::new(StringOrStruct(p)))
+    fn not(self) -> Self::Output {
+        Predicate::Not(Box::new(StringOrStruct(self)))
     }
 }
 
@@ -577,39 +632,61 @@ impl FromStr for Predicate {
     }
 }
 
-fn eval_tx(tx: &ParsedTx, predicate: &Predicate) -> MatchOutcome {
+/// Evaluates the predicate against a single tx or json document
+fn eval_subject<T>(subject: &T, predicate: &Predicate) -> MatchOutcome
+where
+    for<'x> Pattern: PatternOf<&'x T>,
+{
     match predicate {
-        Predicate::Not(x) => !eval_tx(tx, x),
+        Predicate::Not(x) => !eval_subject(subject, x),
         Predicate::AnyOf(x) => {
-            let o = x.iter().map(|x| eval_tx(tx, x));
+            let o = x.iter().map(|x| eval_subject(subject, x));
             MatchOutcome::fold_any_of(o)
         }
         Predicate::AllOf(x) => {
-            let o = x.iter().map(|x| eval_tx(tx, x));
+            let o = x.iter().map(|x| eval_subject(subject, x));
             MatchOutcome::fold_all_of(o)
         }
-        Predicate::Match(x) => x.is_match(tx),
+        Predicate::Match(x) => x.is_match(subject),
     }
 }
 
 fn eval_block(block: &ParsedBlock, predicate: &Predicate) -> MatchOutcome {
-    let outcomes = block
-        .body
-        .iter()
-        .flat_map(|b| b.tx.iter())
-        .map(|tx| eval_tx(tx, predicate));
-
-    MatchOutcome::fold_any_of(outcomes)
+    match BlockSubject::from_parsed(block) {
+        Some(subject) => eval_block_subject(&subject, predicate),
+        None => MatchOutcome::Uncertain,
+    }
 }
 
 pub fn eval(record: &Record, predicate: &Predicate) -> MatchOutcome {
     match record {
-        Record::ParsedTx(x) => eval_tx(x, predicate),
+        Record::ParsedTx(x) => eval_subject(x, predicate),
         Record::ParsedBlock(x) => eval_block(x, predicate),
-        _ => {
-            warn!("The select filter is valid only with ParsedTx & ParsedBlock records");
-            MatchOutcome::Uncertain
-        }
+        Record::CborTx(x) => eval_cbor_tx(x, predicate),
+        Record::CborBlock(x) => eval_cbor_block(x, predicate),
+        Record::GenericJson(x) => eval_subject(x, predicate),
+        Record::OuraV1Event(x) => match serde_json::to_value(x) {
+            Ok(x) => eval_subject(&x, predicate),
+            Err(err) => {
+                warn!(%err, "can't turn the legacy event into json");
+                MatchOutcome::Uncertain
+            }
+        },
+    }
+}
+
+/// Trims a block record down to the txs that match the predicate
+///
+/// Cbor blocks are projected into parsed blocks, any other record is returned
+/// as it is.
+pub fn project(record: &Record, predicate: &Predicate) -> Record {
+    match record {
+        Record::ParsedBlock(x) => Record::ParsedBlock(project_parsed_block(x, predicate)),
+        Record::CborBlock(x) => match project_cbor_block(x, predicate) {
+            Some(x) => Record::ParsedBlock(x),
+            None => record.clone(),
+        },
+        _ => record.clone(),
     }
 }
 
@@ -639,6 +716,35 @@ mod tests {
 
         let pattern = Pattern::from_str("#8888").unwrap();
         assert!(matches!(pattern, Pattern::Metadata(..)));
+
+        let pattern =
+            Pattern::from_str("addr_vkh1jjfnzhxe966a33psfenm0ct2udkkr569qf55v4uprgkgu8zsvmg")
+                .unwrap();
+        assert!(matches!(pattern, Pattern::Address(..)));
+
+        let pattern =
+            Pattern::from_str("script1jjfnzhxe966a33psfenm0ct2udkkr569qf55v4uprgkgumhf3lf")
+                .unwrap();
+        assert!(matches!(pattern, Pattern::Address(..)));
+
+        let pattern =
+            Pattern::from_str("pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy").unwrap();
+        assert!(matches!(pattern, Pattern::Certificate(..)));
+
+        let pattern =
+            Pattern::from_str("drep1yth7lml0alh7lml0alh7lml0alh7lml0alh7lml0alh7lmcy406he")
+                .unwrap();
+        assert!(matches!(pattern, Pattern::Certificate(..)));
+
+        let pattern =
+            Pattern::from_str("cc_cold1z2amhwamhwamhwamhwamhwamhwamhwamhwamhwamhwamhwczxsytf")
+                .unwrap();
+        assert!(matches!(pattern, Pattern::Certificate(..)));
+
+        let pattern =
+            Pattern::from_str("cc_hot1424242424242424242424242424242424242424242425vfc8yd")
+                .unwrap();
+        assert!(matches!(pattern, Pattern::Vote(..)));
     }
 
     #[test]
@@ -647,6 +753,10 @@ mod tests {
 
         serde_json::from_str::<StringOrStruct<Predicate>>("\"#127\"").unwrap();
 
+        let negated = serde_json::from_str::<Predicate>(r##"{ "not": "#127" }"##).unwrap();
+        let predicate = serde_json::from_str::<StringOrStruct<Predicate>>(r##""#127""##).unwrap();
+        assert_eq!(!predicate.0, negated);
+
         serde_json::from_str::<StringOrStruct<Predicate>>(
             r#"{
                 "all": [
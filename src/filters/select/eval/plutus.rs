@@ -0,0 +1,376 @@
+//! Structural patterns for the contents of datums and redeemers
+
+use pallas::interop::utxorpc::spec::cardano::{
+    big_int, plutus_data, BigInt, Constr, Datum, PlutusData, PlutusDataPair, Redeemer,
+};
+
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PlutusDataPattern {
+    Constr(ConstrPattern),
+    Int(NumericPattern<i64>),
+    Bytes(FlexBytes),
+
+    /// Matches arrays with at least one item matching the inner pattern
+    Array(Box<PlutusDataPattern>),
+
+    /// Matches maps with at least one pair matching the inner pattern
+    Map(PlutusMapPattern),
+}
+
+impl PatternOf<&PlutusData> for PlutusDataPattern {
+    fn is_match(&self, subject: &PlutusData) -> MatchOutcome {
+        match self {
+            PlutusDataPattern::Constr(x) => match subject.plutus_data.as_ref() {
+                Some(plutus_data::PlutusData::Constr(subject)) => x.is_match(subject),
+                _ => MatchOutcome::Negative,
+            },
+            PlutusDataPattern::Int(x) => match subject.plutus_data.as_ref() {
+                Some(plutus_data::PlutusData::BigInt(subject)) => x.is_match(subject),
+                _ => MatchOutcome::Negative,
+            },
+            PlutusDataPattern::Bytes(x) => match subject.plutus_data.as_ref() {
+                Some(plutus_data::PlutusData::BoundedBytes(subject)) => {
+                    x.is_match(subject.as_ref())
+                }
+                _ => MatchOutcome::Negative,
+            },
+            PlutusDataPattern::Array(x) => match subject.plutus_data.as_ref() {
+                Some(plutus_data::PlutusData::Array(subject)) => {
+                    x.is_any_match(subject.items.iter())
+                }
+                _ => MatchOutcome::Negative,
+            },
+            PlutusDataPattern::Map(x) => match subject.plutus_data.as_ref() {
+                Some(plutus_data::PlutusData::Map(subject)) => x.is_any_match(subject.pairs.iter()),
+                _ => MatchOutcome::Negative,
+            },
+        }
+    }
+}
+
+/// Decodes the value of a big int, if it fits in an i64
+///
+/// Big ints are encoded as the big-endian bytes of the absolute value, with
+/// negative ones encoding `-1 - n`.
+fn big_int_value(subject: &BigInt) -> Option<i64> {
+    let unsigned = |bytes: &[u8]| -> Option<u64> {
+        let bytes = match bytes.iter().position(|x| *x != 0) {
+            Some(start) => &bytes[start..],
+            None => &[],
+        };
+
+        if bytes.len() > 8 {
+            return None;
+        }
+
+        Some(bytes.iter().fold(0, |acc, x| (acc << 8) | *x as u64))
+    };
+
+    match subject.big_int.as_ref()? {
+        big_int::BigInt::Int(x) => Some(*x),
+        big_int::BigInt::BigUInt(x) => i64::try_from(unsigned(x)?).ok(),
+        big_int::BigInt::BigNInt(x) => {
+            let n = i64::try_from(unsigned(x)?).ok()?;
+            Some(-1 - n)
+        }
+    }
+}
+
+impl PatternOf<&BigInt> for NumericPattern<i64> {
+    fn is_match(&self, subject: &BigInt) -> MatchOutcome {
+        if let Some(x) = big_int_value(subject) {
+            return self.is_match(x);
+        }
+
+        // values out of the i64 range are beyond any bound of the pattern
+        let positive = matches!(subject.big_int, Some(big_int::BigInt::BigUInt(_)));
+
+        match self {
+            NumericPattern::Gte(_) => MatchOutcome::if_true(positive),
+            NumericPattern::Lte(_) => MatchOutcome::if_true(!positive),
+            _ => MatchOutcome::Negative,
+        }
+    }
+}
+
+/// The index of the constructor, as defined by the tag of the cbor encoding
+fn constructor_index(subject: &Constr) -> u64 {
+    match subject.tag {
+        121..=127 => (subject.tag - 121) as u64,
+        1280..=1400 => (subject.tag - 1280 + 7) as u64,
+        _ => subject.any_constructor,
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FieldPattern {
+    position: usize,
+    value: PlutusDataPattern,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ConstrPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldPattern>,
+}
+
+impl PatternOf<&Constr> for ConstrPattern {
+    fn is_match(&self, subject: &Constr) -> MatchOutcome {
+        let a = self.index.is_match(constructor_index(subject));
+
+        let b = self
+            .fields
+            .iter()
+            .map(|x| match subject.fields.get(x.position) {
+                Some(field) => x.value.is_match(field),
+                None => MatchOutcome::Negative,
+            });
+
+        let b = MatchOutcome::fold_all_of(b);
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PlutusMapPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<Box<PlutusDataPattern>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<Box<PlutusDataPattern>>,
+}
+
+impl PatternOf<&PlutusDataPair> for PlutusMapPattern {
+    fn is_match(&self, subject: &PlutusDataPair) -> MatchOutcome {
+        let a = match (&self.key, &subject.key) {
+            (None, _) => MatchOutcome::Positive,
+            (Some(_), None) => MatchOutcome::Negative,
+            (Some(x), Some(key)) => x.is_match(key),
+        };
+
+        let b = match (&self.value, &subject.value) {
+            (None, _) => MatchOutcome::Positive,
+            (Some(_), None) => MatchOutcome::Negative,
+            (Some(x), Some(value)) => x.is_match(value),
+        };
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DatumPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<Vec<u8>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<Box<PlutusDataPattern>>,
+}
+
+impl FromBech32 for DatumPattern {
+    fn from_bech32_parts(hrp: &str, content: Vec<u8>) -> Option<Self> {
+        match hrp {
+            "datum" => Some(Self {
+                hash: Some(content),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for DatumPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bech32(s)
+    }
+}
+
+impl PatternOf<&Datum> for DatumPattern {
+    fn is_match(&self, subject: &Datum) -> MatchOutcome {
+        let a = self.hash.is_match(subject.hash.as_ref());
+
+        // only inline datums carry their value, the ones referenced by hash
+        // can't match a value pattern
+        let b = match (&self.value, &subject.payload) {
+            (None, _) => MatchOutcome::Positive,
+            (Some(_), None) => MatchOutcome::Negative,
+            (Some(x), Some(payload)) => x.is_match(payload),
+        };
+
+        MatchOutcome::fold_all_of([a, b].into_iter())
+    }
+}
+
+impl PatternOf<&Redeemer> for PlutusDataPattern {
+    fn is_match(&self, subject: &Redeemer) -> MatchOutcome {
+        match &subject.payload {
+            Some(x) => self.is_match(x),
+            None => MatchOutcome::Negative,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::interop::utxorpc::spec::cardano::{PlutusDataArray, PlutusDataMap};
+
+    use super::*;
+
+    fn data(x: plutus_data::PlutusData) -> PlutusData {
+        PlutusData {
+            plutus_data: x.into(),
+        }
+    }
+
+    fn int(x: i64) -> PlutusData {
+        data(plutus_data::PlutusData::BigInt(BigInt {
+            big_int: big_int::BigInt::Int(x).into(),
+        }))
+    }
+
+    fn bytes(x: &[u8]) -> PlutusData {
+        data(plutus_data::PlutusData::BoundedBytes(x.to_vec().into()))
+    }
+
+    fn constr(tag: u32, fields: Vec<PlutusData>) -> PlutusData {
+        data(plutus_data::PlutusData::Constr(Constr {
+            tag,
+            any_constructor: 0,
+            fields,
+        }))
+    }
+
+    /// A DEX order datum: the pool NFT, the owner and the amounts of the order
+    fn order() -> PlutusData {
+        let amounts = data(plutus_data::PlutusData::Array(PlutusDataArray {
+            items: vec![int(1_000_000), int(-5)],
+        }));
+
+        let extra = data(plutus_data::PlutusData::Map(PlutusDataMap {
+            pairs: vec![PlutusDataPair {
+                key: bytes(b"fee").into(),
+                value: int(300).into(),
+            }],
+        }));
+
+        constr(
+            122,
+            vec![
+                constr(121, vec![bytes(&[0xab; 28]), bytes(b"POOL")]),
+                bytes(&[0xcd; 28]),
+                amounts,
+                extra,
+            ],
+        )
+    }
+
+    fn outcome(pattern: &str) -> MatchOutcome {
+        let pattern: PlutusDataPattern = serde_json::from_str(pattern).unwrap();
+        pattern.is_match(&order())
+    }
+
+    #[test]
+    fn constr_match() {
+        assert_eq!(
+            outcome(r#"{ "Constr": { "index": 1 } }"#),
+            MatchOutcome::Positive
+        );
+
+        assert_eq!(
+            outcome(r#"{ "Constr": { "index": 0 } }"#),
+            MatchOutcome::Negative
+        );
+
+        let pool = hex::encode(b"POOL");
+
+        let pattern = format!(
+            r#"{{ "Constr": {{ "fields": [
+                {{ "position": 0, "value": {{ "Constr": {{ "index": 0, "fields": [{{ "position": 1, "value": {{ "Bytes": "{pool}" }} }}] }} }} }},
+                {{ "position": 1, "value": {{ "Bytes": "{}" }} }}
+            ] }} }}"#,
+            "cd".repeat(28)
+        );
+
+        assert_eq!(outcome(&pattern), MatchOutcome::Positive);
+
+        assert_eq!(
+            outcome(
+                r#"{ "Constr": { "fields": [{ "position": 9, "value": { "Int": { "gte": 0 } } }] } }"#
+            ),
+            MatchOutcome::Negative
+        );
+    }
+
+    #[test]
+    fn nested_values_match() {
+        let amounts = |x: &str| {
+            format!(
+                r#"{{ "Constr": {{ "fields": [{{ "position": 2, "value": {{ "Array": {x} }} }}] }} }}"#
+            )
+        };
+
+        assert_eq!(
+            outcome(&amounts(r#"{ "Int": { "gte": 500000 } }"#)),
+            MatchOutcome::Positive
+        );
+
+        assert_eq!(
+            outcome(&amounts(r#"{ "Int": { "between": [10, 100] } }"#)),
+            MatchOutcome::Negative
+        );
+
+        let fee = hex::encode(b"fee");
+
+        let pattern = format!(
+            r#"{{ "Constr": {{ "fields": [{{ "position": 3, "value": {{ "Map": {{ "key": {{ "Bytes": "{fee}" }}, "value": {{ "Int": {{ "exact": 300 }} }} }} }} }}] }} }}"#
+        );
+
+        assert_eq!(outcome(&pattern), MatchOutcome::Positive);
+    }
+
+    #[test]
+    fn big_int_match() {
+        let big = |x: big_int::BigInt| BigInt { big_int: x.into() };
+
+        let pattern = NumericPattern::Exact(256);
+        let subject = big(big_int::BigInt::BigUInt(vec![0, 1, 0].into()));
+        assert_eq!(pattern.is_match(&subject), MatchOutcome::Positive);
+
+        let pattern = NumericPattern::Exact(-257);
+        let subject = big(big_int::BigInt::BigNInt(vec![1, 0].into()));
+        assert_eq!(pattern.is_match(&subject), MatchOutcome::Positive);
+
+        let pattern = NumericPattern::Gte(i64::MAX);
+        let subject = big(big_int::BigInt::BigUInt(vec![0xff; 16].into()));
+        assert_eq!(pattern.is_match(&subject), MatchOutcome::Positive);
+    }
+
+    #[test]
+    fn datum_without_payload() {
+        let pattern: DatumPattern =
+            serde_json::from_str(r#"{ "value": { "Constr": { "index": 1 } } }"#).unwrap();
+
+        let hashed = Datum {
+            hash: vec![0xef; 32].into(),
+            ..Default::default()
+        };
+
+        assert_eq!(pattern.is_match(&hashed), MatchOutcome::Negative);
+
+        let inline = Datum {
+            hash: vec![0xef; 32].into(),
+            payload: order().into(),
+            ..Default::default()
+        };
+
+        assert_eq!(pattern.is_match(&inline), MatchOutcome::Positive);
+    }
+}
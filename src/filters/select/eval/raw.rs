@@ -0,0 +1,448 @@
+//! Evaluation of patterns on the raw version of a tx
+//!
+//! Mapping a tx into its utxorpc version is the most expensive part of
+//! evaluating a predicate, and most of the mapped data isn't needed to reach an
+//! outcome. Here patterns are matched against the `MultiEraTx` of the tx, and
+//! only the parts of the tx that a pattern looks at are mapped, once per tx.
+//! Outcomes are the same as for the parsed version of the tx, except for votes
+//! and proposals, which only the raw tx carries.
+
+use pallas::interop::utxorpc::spec::cardano::{AuxData, Certificate, Withdrawal};
+use pallas::interop::utxorpc::{self as interop};
+use pallas::ledger::primitives::conway::{ProposalProcedure, RedeemerTag};
+use pallas::ledger::traverse::MultiEraTx;
+use std::cell::OnceCell;
+
+use crate::filters::parse_cbor::decode_tx;
+
+use super::*;
+
+fn mapper() -> interop::Mapper<NoLedger> {
+    interop::Mapper::default()
+}
+
+/// A raw tx along with the parts that patterns have looked at so far
+///
+/// Each part is mapped the first time a pattern needs it and reused by the
+/// rest of the patterns of the predicate.
+pub struct RawTx<'b> {
+    tx: MultiEraTx<'b>,
+    inputs: OnceCell<Vec<TxInput>>,
+    outputs: OnceCell<Vec<TxOutput>>,
+    mint: OnceCell<Vec<Multiasset>>,
+    auxiliary: OnceCell<AuxData>,
+    datums: OnceCell<Vec<Datum>>,
+    redeemers: OnceCell<Vec<Redeemer>>,
+    certificates: OnceCell<Vec<Certificate>>,
+    withdrawals: OnceCell<Vec<Withdrawal>>,
+    addresses: OnceCell<Vec<Vec<u8>>>,
+    assets: OnceCell<Vec<Multiasset>>,
+}
+
+impl<'b> From<MultiEraTx<'b>> for RawTx<'b> {
+    fn from(tx: MultiEraTx<'b>) -> Self {
+        Self {
+            tx,
+            inputs: Default::default(),
+            outputs: Default::default(),
+            mint: Default::default(),
+            auxiliary: Default::default(),
+            datums: Default::default(),
+            redeemers: Default::default(),
+            certificates: Default::default(),
+            withdrawals: Default::default(),
+            addresses: Default::default(),
+            assets: Default::default(),
+        }
+    }
+}
+
+impl<'b> RawTx<'b> {
+    pub fn tx(&self) -> &MultiEraTx<'b> {
+        &self.tx
+    }
+
+    fn inputs(&self) -> &[TxInput] {
+        self.inputs.get_or_init(|| {
+            let mapper = mapper();
+            let tx = &self.tx;
+
+            tx.inputs_sorted_set()
+                .iter()
+                .enumerate()
+                .map(|(order, x)| mapper.map_tx_input(x, tx, order as u32, &None))
+                .collect()
+        })
+    }
+
+    fn outputs(&self) -> &[TxOutput] {
+        self.outputs.get_or_init(|| {
+            let mapper = mapper();
+
+            self.tx
+                .outputs()
+                .iter()
+                .map(|x| mapper.map_tx_output(x))
+                .collect()
+        })
+    }
+
+    fn mint(&self) -> &[Multiasset] {
+        self.mint.get_or_init(|| {
+            let mapper = mapper();
+            let tx = &self.tx;
+
+            tx.mints_sorted_set()
+                .iter()
+                .enumerate()
+                .map(|(order, x)| Multiasset {
+                    redeemer: tx
+                        .find_mint_redeemer(order as u32)
+                        .map(|x| mapper.map_redeemer(&x)),
+                    ..mapper.map_policy_assets(x)
+                })
+                .collect()
+        })
+    }
+
+    fn auxiliary(&self) -> &AuxData {
+        self.auxiliary.get_or_init(|| {
+            let mapper = mapper();
+
+            let metadata = self
+                .tx
+                .metadata()
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|(label, datum)| mapper.map_metadata(label, datum))
+                .collect();
+
+            AuxData {
+                metadata,
+                ..Default::default()
+            }
+        })
+    }
+
+    fn datums(&self) -> &[Datum] {
+        self.datums.get_or_init(|| {
+            let mapper = mapper();
+
+            self.tx
+                .outputs()
+                .iter()
+                .map(|x| mapper.map_tx_datum(x))
+                .collect()
+        })
+    }
+
+    /// The redeemers of the tx, leaving out the ones for votes and proposals
+    /// which parsed txs don't carry
+    fn redeemers(&self) -> &[Redeemer] {
+        self.redeemers.get_or_init(|| {
+            let mapper = mapper();
+
+            self.tx
+                .redeemers()
+                .iter()
+                .filter(|x| {
+                    matches!(
+                        x.tag(),
+                        RedeemerTag::Spend
+                            | RedeemerTag::Mint
+                            | RedeemerTag::Cert
+                            | RedeemerTag::Reward
+                    )
+                })
+                .map(|x| mapper.map_redeemer(x))
+                .collect()
+        })
+    }
+
+    fn certificates(&self) -> &[Certificate] {
+        self.certificates.get_or_init(|| {
+            let mapper = mapper();
+            let tx = &self.tx;
+
+            tx.certs()
+                .iter()
+                .enumerate()
+                .filter_map(|(order, x)| mapper.map_cert(x, tx, order as u32))
+                .collect()
+        })
+    }
+
+    fn withdrawals(&self) -> &[Withdrawal] {
+        self.withdrawals.get_or_init(|| {
+            let mapper = mapper();
+            let tx = &self.tx;
+
+            tx.withdrawals_sorted_set()
+                .iter()
+                .enumerate()
+                .map(|(order, x)| mapper.map_withdrawals(x, tx, order as u32))
+                .collect()
+        })
+    }
+
+    /// The addresses of the outputs, as bytes
+    fn addresses(&self) -> &[Vec<u8>] {
+        self.addresses.get_or_init(|| {
+            self.tx
+                .outputs()
+                .iter()
+                .filter_map(|x| x.address().ok())
+                .map(|x| x.to_vec())
+                .collect()
+        })
+    }
+
+    /// The assets of the outputs and the mint
+    fn assets(&self) -> &[Multiasset] {
+        self.assets.get_or_init(|| {
+            let mapper = mapper();
+
+            let outputs = self.tx.outputs();
+
+            let a = outputs.iter().flat_map(|x| x.non_ada_assets());
+
+            let b = self.tx.mints_sorted_set();
+
+            a.chain(b).map(|x| mapper.map_policy_assets(&x)).collect()
+        })
+    }
+
+    fn votes(&self) -> impl Iterator<Item = VoteSubject<'_>> {
+        self.tx
+            .as_conway()
+            .and_then(|x| x.transaction_body.voting_procedures.as_ref())
+            .into_iter()
+            .flat_map(iter_votes)
+    }
+
+    fn proposals(&self) -> impl Iterator<Item = &ProposalProcedure> {
+        self.tx
+            .as_conway()
+            .and_then(|x| x.transaction_body.proposal_procedures.as_ref())
+            .into_iter()
+            .flat_map(|x| x.iter())
+    }
+}
+
+/// Matches each of the patterns against the parts of a tx, which are mapped
+/// only if there's at least one pattern
+fn match_parts<'a, P, T: 'a>(patterns: &[P], parts: impl FnOnce() -> &'a [T]) -> MatchOutcome
+where
+    for<'x> P: PatternOf<&'x T>,
+{
+    if patterns.is_empty() {
+        return MatchOutcome::Positive;
+    }
+
+    let parts = parts();
+
+    let outcomes = patterns.iter().map(|x| x.is_any_match(parts.iter()));
+
+    MatchOutcome::fold_all_of(outcomes)
+}
+
+impl PatternOf<&RawTx<'_>> for TxPattern {
+    fn is_match(&self, tx: &RawTx) -> MatchOutcome {
+        let a = match_parts(&self.inputs, || tx.inputs());
+
+        let b = match_parts(&self.outputs, || tx.outputs());
+
+        let c = match_parts(&self.mint, || tx.mint());
+
+        let d = match_parts(&self.metadata, || std::slice::from_ref(tx.auxiliary()));
+
+        let e = match_parts(&self.certificates, || tx.certificates());
+
+        let f = match_parts(&self.withdrawals, || tx.withdrawals());
+
+        MatchOutcome::fold_all_of([a, b, c, d, e, f].into_iter())
+    }
+}
+
+/// Raw txs don't carry the outputs spent by their inputs, so only the outputs
+/// are checked. Parsed txs with resolved inputs are also matched on the
+/// addresses of their inputs.
+impl PatternOf<&RawTx<'_>> for WatchlistPattern {
+    fn is_match(&self, subject: &RawTx) -> MatchOutcome {
+        let addresses = subject.addresses().iter().map(Vec::as_slice);
+
+        self.is_any_listed(addresses, subject.assets().iter())
+    }
+}
+
+impl PatternOf<&RawTx<'_>> for Pattern {
+    fn is_match(&self, subject: &RawTx) -> MatchOutcome {
+        match self {
+            // a tx record doesn't carry the block it belongs to
+            Pattern::Block(_) => MatchOutcome::Uncertain,
+            Pattern::Tx(x) => x.is_match(subject),
+            Pattern::Address(x) => {
+                // outputs are matched on the decoded address, skipping the bytes;
+                // unlike parsed txs, there are no resolved inputs to match
+                // against
+                let outcomes = subject.tx.outputs().into_iter().map(|o| match o.address() {
+                    Ok(address) => x.is_match(&address),
+                    Err(_) => MatchOutcome::Uncertain,
+                });
+
+                MatchOutcome::fold_any_of(outcomes)
+            }
+            Pattern::Asset(x) => x.is_any_match(subject.assets().iter()),
+            Pattern::Input(x) => x.is_any_match(subject.inputs().iter()),
+            Pattern::Output(x) => x.is_any_match(subject.outputs().iter()),
+            Pattern::Mint(x) => x.is_any_match(subject.mint().iter()),
+            Pattern::Metadata(x) => x.is_match(subject.auxiliary()),
+            Pattern::Datum(x) => x.is_any_match(subject.datums().iter()),
+            Pattern::Redeemer(x) => x.is_any_match(subject.redeemers().iter()),
+            Pattern::Certificate(x) => x.is_any_match(subject.certificates().iter()),
+            Pattern::Withdrawal(x) => x.is_any_match(subject.withdrawals().iter()),
+            Pattern::Vote(x) => x.is_any_match(subject.votes()),
+            Pattern::Proposal(x) => x.is_any_match(subject.proposals()),
+            Pattern::Watchlist(x) => x.is_match(subject),
+            Pattern::Json(_) => MatchOutcome::Uncertain,
+        }
+    }
+}
+
+/// Evaluates the predicate against the raw version of the cbor tx, without
+/// mapping it into its parsed version
+pub fn eval_cbor_tx(cbor: &[u8], predicate: &Predicate) -> MatchOutcome {
+    match decode_tx(cbor) {
+        Ok(tx) => eval_subject(&RawTx::from(tx), predicate),
+        Err(_) => MatchOutcome::Uncertain,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::codec::minicbor;
+    use pallas::codec::utils::{NonEmptyKeyValuePairs, Nullable};
+    use pallas::ledger::primitives::conway::{GovActionId, Vote, Voter, VotingProcedure};
+    use pallas::ledger::traverse::MultiEraBlock;
+
+    use super::*;
+
+    const PREDICATES: &[&str] = &[
+        r#""addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x""#,
+        r#""stake_vkh1xdak9nllvsp6q636e0p5lrzxqq7xnlne5d3gemafc3e9z3v4vud""#,
+        r#""asset1tra0mxecpkzgpu8a93jedlqzc9fr9wjwkf2f5y""#,
+        r##""#127""##,
+        r#""pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy""#,
+        r#""datum1kthqfw4769ejpkx3h8le45yxaph5fmzdnur2s4""#,
+        r#"{ "match": { "output": { "lovelace": { "gte": 100000000 } } } }"#,
+        r#"{ "match": { "input": { "lovelace": { "gte": 1 } } } }"#,
+        r#"{ "match": { "mint": { "assets": [] } } }"#,
+        r#"{ "match": { "withdrawal": { "coin": { "gte": 1000000 } } } }"#,
+        r#"{ "match": { "redeemer": { "Int": { "exact": 1 } } } }"#,
+        r#"{ "match": { "tx": { "outputs": [{ "lovelace": { "gte": 1 } }], "metadata": [{ "label": 127 }] } } }"#,
+        r#"{ "match": { "tx": { "certificates": [{ "kind": "stake_delegation" }], "withdrawals": [{}] } } }"#,
+        r##"{ "not": { "any": ["#127", "#9980"] } }"##,
+    ];
+
+    fn predicate(x: &str) -> StringOrStruct<Predicate> {
+        serde_json::from_str(x).unwrap()
+    }
+
+    fn raw_positives(predicate: &str) -> Vec<usize> {
+        let predicate = self::predicate(predicate);
+
+        testing::raw_test_vectors()
+            .iter()
+            .map(|x| minicbor::to_vec(x).unwrap())
+            .enumerate()
+            .filter(|(_, x)| eval_cbor_tx(x, &predicate) == MatchOutcome::Positive)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    #[test]
+    fn raw_tx_match() {
+        assert_eq!(raw_positives(PREDICATES[0]), vec![1, 3]);
+        assert_eq!(raw_positives(PREDICATES[3]), vec![1, 2]);
+        assert_eq!(raw_positives(PREDICATES[4]), vec![3]);
+        assert_eq!(raw_positives(PREDICATES[8]), vec![2]);
+        assert_eq!(raw_positives(PREDICATES[12]), vec![3]);
+        assert_eq!(raw_positives(PREDICATES[13]), vec![0]);
+    }
+
+    #[test]
+    fn raw_and_parsed_txs_agree() {
+        for tx in testing::raw_test_vectors() {
+            let cbor = minicbor::to_vec(&tx).unwrap();
+            let raw = RawTx::from(decode_tx(&cbor).unwrap());
+            let parsed = mapper().map_tx(raw.tx());
+
+            for x in PREDICATES {
+                let predicate = predicate(x);
+                assert_eq!(
+                    eval_subject(&raw, &predicate),
+                    eval_subject(&parsed, &predicate),
+                    "{x}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn raw_and_parsed_blocks_agree() {
+        let block = testing::raw_test_block(100, testing::raw_test_vectors());
+        let parsed = mapper().map_block(&MultiEraBlock::decode(&block).unwrap());
+
+        let block_predicates = [
+            r#"{ "match": { "block": { "slot": { "lte": 500 }, "tx_count": { "exact": 4 } } } }"#,
+            r##"{ "all": [{ "match": { "block": { "height": { "exact": 5 } } } }, "#127"] }"##,
+        ];
+
+        for x in PREDICATES.iter().chain(block_predicates.iter()) {
+            let predicate = predicate(x);
+
+            assert_eq!(
+                eval_cbor_block(&block, &predicate),
+                eval_block(&parsed, &predicate),
+                "{x}"
+            );
+
+            assert_eq!(
+                project_cbor_block(&block, &predicate),
+                Some(project_parsed_block(&parsed, &predicate)),
+                "{x}"
+            );
+        }
+    }
+
+    #[test]
+    fn raw_vote_match() {
+        let mut tx = testing::raw_tx(0);
+
+        tx.transaction_body.voting_procedures = Some(NonEmptyKeyValuePairs::Def(vec![(
+            Voter::ConstitutionalCommitteeKey([0xaa; 28].into()),
+            NonEmptyKeyValuePairs::Def(vec![(
+                GovActionId {
+                    transaction_id: [1; 32].into(),
+                    action_index: 0,
+                },
+                VotingProcedure {
+                    vote: Vote::No,
+                    anchor: Nullable::Null,
+                },
+            )]),
+        )]));
+
+        let cbor = minicbor::to_vec(&tx).unwrap();
+
+        let hot = predicate(r#""cc_hot1424242424242424242424242424242424242424242425vfc8yd""#);
+        assert_eq!(eval_cbor_tx(&cbor, &hot), MatchOutcome::Positive);
+
+        let yes = predicate(r#"{ "match": { "vote": { "vote": "yes" } } }"#);
+        assert_eq!(eval_cbor_tx(&cbor, &yes), MatchOutcome::Negative);
+
+        // the parsed version of the tx has no votes to match
+        let parsed = mapper().map_tx(&decode_tx(&cbor).unwrap());
+        assert_eq!(eval_subject(&parsed, &hot), MatchOutcome::Uncertain);
+    }
+}
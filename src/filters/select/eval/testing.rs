@@ -1,4 +1,10 @@
+use pallas::codec::minicbor;
+use pallas::codec::utils::{
+    KeyValuePairs, MaybeIndefArray, NonEmptyKeyValuePairs, NonEmptySet, NonZeroInt, Nullable,
+    PositiveCoin, Set,
+};
 use pallas::interop::utxorpc::spec::cardano::{metadatum, AuxData, Datum, Metadata, Metadatum, Tx};
+use pallas::ledger::primitives::conway;
 
 use super::*;
 
@@ -122,9 +128,208 @@ pub fn find_positive_test_vectors(predicate: impl Into<Predicate>) -> Vec<usize>
     subjects
         .into_iter()
         .enumerate()
-        .filter_map(|(idx, subject)| match eval_tx(&subject, &predicate) {
+        .filter_map(|(idx, subject)| match eval_subject(&subject, &predicate) {
             MatchOutcome::Positive => Some(idx),
             _ => None,
         })
         .collect()
 }
+
+const RAW_ADDRESS: &str = "019493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251";
+
+const RAW_STAKE_HASH: &str = "337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251";
+
+fn raw_output(address: &str, policies: &[(&str, &str)]) -> conway::TransactionOutput {
+    let assets = policies
+        .iter()
+        .map(|(policy, prefix)| {
+            let names = (1..=2)
+                .map(|i| {
+                    let name = format!("{prefix}{i}").into_bytes().into();
+                    (name, PositiveCoin::try_from(345000000).unwrap())
+                })
+                .collect();
+
+            (policy.parse().unwrap(), NonEmptyKeyValuePairs::Def(names))
+        })
+        .collect();
+
+    conway::TransactionOutput::PostAlonzo(conway::PostAlonzoTransactionOutput {
+        address: hex::decode(address).unwrap().into(),
+        value: conway::Value::Multiasset(123000000, NonEmptyKeyValuePairs::Def(assets)),
+        datum_option: Some(conway::DatumOption::Hash(
+            "923918e403bf43c34b4ef6b48eb2ee04babed17320d8d1b9ff9ad086e86f44ec"
+                .parse()
+                .unwrap(),
+        )),
+        script_ref: None,
+    })
+}
+
+fn raw_metadata(entries: &[(u64, &str)]) -> Nullable<conway::AuxiliaryData> {
+    let metadata: Vec<_> = entries
+        .iter()
+        .map(|(label, text)| (*label, conway::Metadatum::Text(text.to_string())))
+        .collect();
+
+    Nullable::Some(conway::AuxiliaryData::Shelley(metadata.into()))
+}
+
+/// A conway tx that spends a single input and does nothing else
+pub fn raw_tx(seed: u8) -> conway::Tx {
+    conway::Tx {
+        transaction_body: conway::TransactionBody {
+            inputs: Set::from(vec![conway::TransactionInput {
+                transaction_id: [seed; 32].into(),
+                index: 0,
+            }]),
+            outputs: vec![],
+            fee: 170000,
+            ttl: None,
+            certificates: None,
+            withdrawals: None,
+            auxiliary_data_hash: None,
+            validity_interval_start: None,
+            mint: None,
+            script_data_hash: None,
+            collateral: None,
+            required_signers: None,
+            network_id: None,
+            collateral_return: None,
+            total_collateral: None,
+            reference_inputs: None,
+            voting_procedures: None,
+            proposal_procedures: None,
+            treasury_value: None,
+            donation: None,
+        },
+        transaction_witness_set: conway::WitnessSet {
+            vkeywitness: None,
+            native_script: None,
+            bootstrap_witness: None,
+            plutus_v1_script: None,
+            plutus_data: None,
+            redeemer: None,
+            plutus_v2_script: None,
+            plutus_v3_script: None,
+        },
+        success: true,
+        auxiliary_data: Nullable::Null,
+    }
+}
+
+/// Conway versions of the test vectors, with a certificate and a withdrawal
+/// on the last one
+pub fn raw_test_vectors() -> Vec<conway::Tx> {
+    let tx0 = raw_tx(0);
+
+    let mut tx1 = raw_tx(1);
+    tx1.transaction_body.outputs = vec![raw_output(
+        RAW_ADDRESS,
+        &[
+            (
+                "7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373",
+                "abc",
+            ),
+            (
+                "1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209",
+                "123",
+            ),
+        ],
+    )];
+    tx1.auxiliary_data = raw_metadata(&[(127, "lorem"), (9980, "ipsum")]);
+
+    let mut tx2 = raw_tx(2);
+    tx2.transaction_body.outputs = vec![raw_output(
+        "619493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e",
+        &[(
+            "7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373",
+            "abc",
+        )],
+    )];
+    tx2.transaction_body.mint = Some(NonEmptyKeyValuePairs::Def(vec![(
+        "533bb94a8850ee3ccbe483106489399112b74c905342cb1792a797a0"
+            .parse()
+            .unwrap(),
+        NonEmptyKeyValuePairs::Def(vec![
+            (b"xyz1".to_vec().into(), NonZeroInt::try_from(1).unwrap()),
+            (b"xyz2".to_vec().into(), NonZeroInt::try_from(1).unwrap()),
+        ]),
+    )]));
+    tx2.auxiliary_data = raw_metadata(&[(127, "lorem")]);
+
+    let mut tx3 = raw_tx(3);
+    tx3.transaction_body.outputs = vec![raw_output(
+        RAW_ADDRESS,
+        &[(
+            "1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209",
+            "123",
+        )],
+    )];
+    tx3.transaction_body.certificates = Some(
+        NonEmptySet::try_from(vec![conway::Certificate::StakeDelegation(
+            conway::StakeCredential::AddrKeyhash(RAW_STAKE_HASH.parse().unwrap()),
+            "0f292fcaa02b8b2f9b3c8f9fd8e0bb21abedb692a6d5058df3ef2735"
+                .parse()
+                .unwrap(),
+        )])
+        .unwrap(),
+    );
+    tx3.transaction_body.withdrawals = Some(NonEmptyKeyValuePairs::Def(vec![(
+        hex::decode(format!("e1{RAW_STAKE_HASH}")).unwrap().into(),
+        5000000,
+    )]));
+    tx3.auxiliary_data = raw_metadata(&[(9980, "ipsum")]);
+
+    vec![tx0, tx1, tx2, tx3]
+}
+
+/// Encodes the txs as the cbor of a conway block
+pub fn raw_test_block(slot: u64, txs: Vec<conway::Tx>) -> Vec<u8> {
+    let auxiliary_data_set: Vec<_> = txs
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, tx)| match &tx.auxiliary_data {
+            Nullable::Some(x) => Some((idx as u32, x.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let header_body = conway::HeaderBody {
+        block_number: slot / 20,
+        slot,
+        prev_hash: None,
+        issuer_vkey: vec![0x11; 32].into(),
+        vrf_vkey: vec![0x22; 32].into(),
+        vrf_result: conway::VrfCert(vec![].into(), vec![].into()),
+        block_body_size: 0,
+        block_body_hash: [0; 32].into(),
+        operational_cert: conway::OperationalCert {
+            operational_cert_hot_vkey: vec![0x33; 32].into(),
+            operational_cert_sequence_number: 0,
+            operational_cert_kes_period: 0,
+            operational_cert_sigma: vec![0x44; 64].into(),
+        },
+        protocol_version: (9, 0),
+    };
+
+    let block = conway::Block {
+        header: conway::Header {
+            header_body,
+            body_signature: vec![0x55; 64].into(),
+        },
+        transaction_bodies: MaybeIndefArray::Def(
+            txs.iter().map(|x| x.transaction_body.clone()).collect(),
+        ),
+        transaction_witness_sets: MaybeIndefArray::Def(
+            txs.iter()
+                .map(|x| x.transaction_witness_set.clone())
+                .collect(),
+        ),
+        auxiliary_data_set: KeyValuePairs::from(auxiliary_data_set),
+        invalid_transactions: None,
+    };
+
+    // blocks are wrapped along with the tag of their era
+    minicbor::to_vec((7u16, block)).unwrap()
+}
@@ -0,0 +1,211 @@
+use pallas::interop::utxorpc::spec::cardano::metadatum;
+use regex::Regex;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+use super::*;
+
+/// A regular expression, compiled when the config is loaded
+#[derive(Clone, Debug)]
+pub struct TextRegex(Regex);
+
+impl PartialEq for TextRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl FromStr for TextRegex {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Regex::new(s).map(TextRegex)
+    }
+}
+
+impl PatternOf<&str> for TextRegex {
+    fn is_match(&self, subject: &str) -> MatchOutcome {
+        MatchOutcome::if_true(self.0.is_match(subject))
+    }
+}
+
+impl Serialize for TextRegex {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+struct TextRegexVisitor;
+
+impl<'de> Visitor<'de> for TextRegexVisitor {
+    type Value = TextRegex;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a regular expression")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        TextRegex::from_str(value).map_err(de::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for TextRegex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(TextRegexVisitor)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TextPattern {
+    Exact(String),
+    Regex(TextRegex),
+    StartsWith(String),
+    EndsWith(String),
+    Contains(String),
+}
+
+impl FromStr for TextPattern {
+    type Err = anyhow::Error;
+
+    /// Parses any string as the exact text
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(TextPattern::Exact(s.to_owned()))
+    }
+}
+
+impl PatternOf<&str> for TextPattern {
+    fn is_match(&self, subject: &str) -> MatchOutcome {
+        match self {
+            TextPattern::Exact(x) => MatchOutcome::if_equal(x.as_str(), subject),
+            TextPattern::Regex(x) => x.is_match(subject),
+            TextPattern::StartsWith(x) => MatchOutcome::if_true(subject.starts_with(x.as_str())),
+            TextPattern::EndsWith(x) => MatchOutcome::if_true(subject.ends_with(x.as_str())),
+            TextPattern::Contains(x) => MatchOutcome::if_true(subject.contains(x.as_str())),
+        }
+    }
+}
+
+impl PatternOf<&[u8]> for TextPattern {
+    fn is_match(&self, subject: &[u8]) -> MatchOutcome {
+        let subject = match std::str::from_utf8(subject) {
+            Ok(subject) => subject,
+            Err(_) => return MatchOutcome::Uncertain,
+        };
+
+        self.is_match(subject)
+    }
+}
+
+impl PatternOf<&Metadatum> for TextPattern {
+    fn is_match(&self, subject: &Metadatum) -> MatchOutcome {
+        match subject.metadatum.as_ref() {
+            Some(metadatum::Metadatum::Text(subject)) => self.is_match(subject.as_str()),
+            _ => MatchOutcome::Negative,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_shorthand() {
+        let parsed = TextPattern::from_str("lorem").unwrap();
+        assert_eq!(parsed, TextPattern::Exact("lorem".into()));
+
+        // strings between slashes aren't regular expressions
+        let parsed = TextPattern::from_str("/^lo.*m$/").unwrap();
+        assert_eq!(parsed, TextPattern::Exact("/^lo.*m$/".into()));
+    }
+
+    #[test]
+    fn deser_struct() {
+        let parsed: StringOrStruct<TextPattern> =
+            serde_json::from_str(r#"{ "StartsWith": "abc" }"#).unwrap();
+        assert_eq!(parsed.0, TextPattern::StartsWith("abc".into()));
+
+        let parsed: StringOrStruct<TextPattern> =
+            serde_json::from_str(r#"{ "Exact": "abc" }"#).unwrap();
+        assert_eq!(parsed.0, TextPattern::Exact("abc".into()));
+
+        let parsed: StringOrStruct<TextPattern> =
+            serde_json::from_str(r#"{ "Regex": "^lo.*m$" }"#).unwrap();
+        assert_eq!(
+            parsed.0,
+            TextPattern::Regex(TextRegex::from_str("^lo.*m$").unwrap())
+        );
+
+        let parsed = serde_json::from_str::<StringOrStruct<TextPattern>>(r#"{ "Regex": "(" }"#);
+        assert!(parsed.is_err());
+
+        let parsed = serde_json::from_str::<StringOrStruct<TextPattern>>(r#"{ "exact": "abc" }"#);
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn text_match() {
+        let subject = "lorem ipsum";
+
+        let outcome = |x: &str| {
+            serde_json::from_str::<TextPattern>(x)
+                .unwrap()
+                .is_match(subject)
+        };
+
+        assert_eq!(
+            outcome(r#"{ "Exact": "lorem ipsum" }"#),
+            MatchOutcome::Positive
+        );
+        assert_eq!(outcome(r#"{ "Exact": "lorem" }"#), MatchOutcome::Negative);
+        assert_eq!(
+            outcome(r#"{ "StartsWith": "lorem" }"#),
+            MatchOutcome::Positive
+        );
+        assert_eq!(
+            outcome(r#"{ "EndsWith": "lorem" }"#),
+            MatchOutcome::Negative
+        );
+        assert_eq!(outcome(r#"{ "Contains": "m ip" }"#), MatchOutcome::Positive);
+        assert_eq!(
+            outcome(r#"{ "Regex": "^l\\w+ i" }"#),
+            MatchOutcome::Positive
+        );
+        assert_eq!(outcome(r#"{ "Regex": "^ipsum" }"#), MatchOutcome::Negative);
+    }
+
+    #[test]
+    fn asset_name_match() {
+        let pattern = |regex: &str| {
+            let text = TextPattern::Regex(TextRegex::from_str(regex).unwrap());
+
+            Pattern::Asset(
+                AssetPattern {
+                    name_text: Some(text.into()),
+                    ..Default::default()
+                }
+                .into(),
+            )
+        };
+
+        let positives = testing::find_positive_test_vectors(pattern("^abc"));
+        assert_eq!(positives, vec![1, 2]);
+
+        let positives = testing::find_positive_test_vectors(pattern("^123\\d$"));
+        assert_eq!(positives, vec![1, 3]);
+
+        let positives = testing::find_positive_test_vectors(pattern("^zzz"));
+        assert_eq!(positives, Vec::<usize>::new());
+    }
+}
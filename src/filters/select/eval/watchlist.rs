@@ -0,0 +1,570 @@
+//! Large lists of addresses, credentials and policies to match txs against
+//!
+//! Entries are loaded from a file or a Redis set when the filter is
+//! bootstrapped, and indexed in hash sets so the cost of matching a tx doesn't
+//! grow with the size of the list. A background thread started along with the
+//! list checks the source for changes and swaps the index once the new entries
+//! are loaded, so matching never waits on the source nor takes a lock.
+
+use arc_swap::ArcSwap;
+use bech32::FromBase32;
+use pallas::ledger::addresses::Address;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, Weak};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+use super::*;
+
+const DEFAULT_RELOAD_INTERVAL: u64 = 30;
+
+/// Longest wait between reloads of a source that keeps failing, unless the
+/// reload interval is longer
+const MAX_RELOAD_BACKOFF: Duration = Duration::from_secs(600);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RedisWatchlist {
+    url: String,
+    key: String,
+
+    /// Key that writers bump whenever they change the set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WatchlistSource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<PathBuf>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    redis: Option<RedisWatchlist>,
+
+    /// Seconds between checks of the source for changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reload_interval: Option<u64>,
+}
+
+/// Reads the members of the set, unless its version key still holds the
+/// given version
+#[cfg(feature = "redis")]
+fn read_redis_entries(
+    source: &RedisWatchlist,
+    current: Option<&Version>,
+) -> anyhow::Result<Option<(Vec<String>, Option<Version>)>> {
+    use r2d2_redis::redis;
+
+    let client = redis::Client::open(source.url.as_str())?;
+    let mut conn = client.get_connection()?;
+
+    let version = match &source.version_key {
+        Some(key) => {
+            let value = redis::cmd("GET")
+                .arg(key)
+                .query::<Option<String>>(&mut conn)?;
+
+            value.map(Version::Redis)
+        }
+        None => None,
+    };
+
+    if version.is_some() && version.as_ref() == current {
+        return Ok(None);
+    }
+
+    let entries = redis::cmd("SMEMBERS")
+        .arg(&source.key)
+        .query::<Vec<String>>(&mut conn)?;
+
+    Ok(Some((entries, version)))
+}
+
+#[cfg(not(feature = "redis"))]
+fn read_redis_entries(
+    _: &RedisWatchlist,
+    _: Option<&Version>,
+) -> anyhow::Result<Option<(Vec<String>, Option<Version>)>> {
+    anyhow::bail!("watchlists from redis require oura to be built with the `redis` feature")
+}
+
+/// Identifies a version of the source, to skip reloads when nothing changed
+#[derive(Clone, PartialEq)]
+enum Version {
+    /// Checked before reading the file
+    File { modified: SystemTime, len: u64 },
+    /// The value of the version key of a Redis set
+    #[cfg(feature = "redis")]
+    Redis(String),
+}
+
+/// Where the entries are read from, as resolved when bootstrapping
+#[derive(Clone)]
+enum Origin {
+    File(PathBuf),
+    Redis(RedisWatchlist),
+}
+
+impl Origin {
+    fn version(&self) -> Option<Version> {
+        let Origin::File(path) = self else {
+            return None;
+        };
+
+        let meta = std::fs::metadata(path).ok()?;
+
+        Some(Version::File {
+            modified: meta.modified().ok()?,
+            len: meta.len(),
+        })
+    }
+
+    /// Whether the source can tell that it changed, otherwise it's only
+    /// loaded once
+    fn is_versioned(&self) -> bool {
+        match self {
+            Origin::File(_) => true,
+            Origin::Redis(x) => x.version_key.is_some(),
+        }
+    }
+
+    /// Reads the entries, unless they're still the given version
+    fn load(&self, current: Option<&Version>) -> anyhow::Result<Option<(Index, Option<Version>)>> {
+        match self {
+            Origin::File(path) => {
+                let version = self.version();
+
+                if version.is_some() && version.as_ref() == current {
+                    return Ok(None);
+                }
+
+                let content = std::fs::read_to_string(path)?;
+                Ok(Some((Index::parse(content.lines())?, version)))
+            }
+            Origin::Redis(redis) => {
+                let Some((entries, version)) = read_redis_entries(redis, current)? else {
+                    return Ok(None);
+                };
+
+                let index = Index::parse(entries.iter().map(String::as_str))?;
+                Ok(Some((index, version)))
+            }
+        }
+    }
+}
+
+impl WatchlistSource {
+    fn reload_interval(&self) -> Duration {
+        Duration::from_secs(self.reload_interval.unwrap_or(DEFAULT_RELOAD_INTERVAL))
+    }
+
+    fn origin(&self, ctx: &Context) -> Result<Origin, Error> {
+        match (&self.file, &self.redis) {
+            (Some(path), None) => Ok(Origin::File(ctx.current_dir.join(path))),
+            (None, Some(redis)) => Ok(Origin::Redis(redis.clone())),
+            _ => Err(Error::config(
+                "watchlist needs either a `file` or a `redis` source",
+            )),
+        }
+    }
+}
+
+/// The entries of a watchlist, split by the part of the tx they match
+#[derive(Default)]
+struct Index {
+    addresses: HashSet<Vec<u8>>,
+    payment: HashSet<Vec<u8>>,
+    delegation: HashSet<Vec<u8>>,
+    policies: HashSet<Vec<u8>>,
+    fingerprints: HashSet<Vec<u8>>,
+}
+
+impl Index {
+    fn insert(&mut self, entry: &str) -> anyhow::Result<()> {
+        if let Ok((hrp, content, _)) = bech32::decode(entry) {
+            let content = Vec::<u8>::from_base32(&content)?;
+
+            match hrp.as_str() {
+                "addr" | "addr_test" => self.addresses.insert(content),
+                // stake addresses are the credential prefixed by a header byte
+                "stake" | "stake_test" => match content.get(1..) {
+                    Some(x) => self.delegation.insert(x.to_vec()),
+                    None => anyhow::bail!("stake address without a credential"),
+                },
+                "addr_vkh" | "script" => self.payment.insert(content),
+                "stake_vkh" => self.delegation.insert(content),
+                "asset" => self.fingerprints.insert(content),
+                _ => anyhow::bail!("unsupported bech32 prefix '{hrp}'"),
+            };
+
+            return Ok(());
+        }
+
+        let policy = hex::decode(entry)?;
+
+        if policy.len() != 28 {
+            anyhow::bail!("expected a policy id of 28 bytes");
+        }
+
+        self.policies.insert(policy);
+
+        Ok(())
+    }
+
+    /// Parses the entries, one per line, skipping blank lines and `#` comments
+    fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> anyhow::Result<Self> {
+        let mut index = Self::default();
+
+        for line in lines.map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            index
+                .insert(line)
+                .map_err(|err| anyhow::anyhow!("invalid watchlist entry '{line}': {err}"))?;
+        }
+
+        Ok(index)
+    }
+
+    fn len(&self) -> usize {
+        self.addresses.len()
+            + self.payment.len()
+            + self.delegation.len()
+            + self.policies.len()
+            + self.fingerprints.len()
+    }
+
+    fn contains_address(&self, address: &[u8]) -> bool {
+        if self.addresses.contains(address) {
+            return true;
+        }
+
+        if self.payment.is_empty() && self.delegation.is_empty() {
+            return false;
+        }
+
+        match Address::from_bytes(address) {
+            Ok(Address::Shelley(x)) => {
+                self.payment.contains(x.payment().as_hash().as_slice())
+                    || x.delegation()
+                        .as_hash()
+                        .is_some_and(|x| self.delegation.contains(x.as_slice()))
+            }
+            Ok(Address::Stake(x)) => self.delegation.contains(x.payload().as_hash().as_slice()),
+            _ => false,
+        }
+    }
+
+    fn contains_asset(&self, subject: &Multiasset) -> bool {
+        if self.policies.contains(subject.policy_id.as_ref()) {
+            return true;
+        }
+
+        if self.fingerprints.is_empty() {
+            return false;
+        }
+
+        subject.assets.iter().any(|x| {
+            let hash = cip14::compute_hash(&subject.policy_id, &x.name);
+            self.fingerprints.contains(hash.as_slice())
+        })
+    }
+}
+
+/// The entries of a bootstrapped watchlist, along with the state of its
+/// reloads
+struct Loaded {
+    origin: Origin,
+    index: ArcSwap<Index>,
+    version: Mutex<Option<Version>>,
+    /// Dropped along with the list, which stops the refresher
+    _stop: mpsc::Sender<()>,
+}
+
+impl Loaded {
+    /// Reloads the entries if the source changed
+    ///
+    /// A source that fails to load keeps the previous entries in place.
+    fn refresh(&self) -> anyhow::Result<()> {
+        let mut version = self.version.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some((index, new)) = self.origin.load(version.as_ref())? {
+            info!(entries = index.len(), "watchlist reloaded");
+
+            self.index.store(Arc::new(index));
+            *version = new;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reloads the list at every interval until it's dropped, backing off while
+/// the source fails
+fn spawn_refresher(loaded: Weak<Loaded>, interval: Duration, stop: mpsc::Receiver<()>) {
+    std::thread::spawn(move || {
+        let mut failures = 0;
+
+        loop {
+            let delay = interval
+                .saturating_mul(2u32.saturating_pow(failures))
+                .min(MAX_RELOAD_BACKOFF.max(interval));
+
+            if stop.recv_timeout(delay) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+
+            let Some(loaded) = loaded.upgrade() else {
+                return;
+            };
+
+            match loaded.refresh() {
+                Ok(()) => failures = 0,
+                Err(err) => {
+                    failures += 1;
+                    warn!(%err, "can't reload watchlist, keeping previous entries");
+                }
+            }
+        }
+    });
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(from = "WatchlistSource", into = "WatchlistSource")]
+pub struct WatchlistPattern {
+    source: WatchlistSource,
+    loaded: Arc<OnceLock<Arc<Loaded>>>,
+}
+
+impl From<WatchlistSource> for WatchlistPattern {
+    fn from(source: WatchlistSource) -> Self {
+        Self {
+            source,
+            loaded: Default::default(),
+        }
+    }
+}
+
+impl From<WatchlistPattern> for WatchlistSource {
+    fn from(value: WatchlistPattern) -> Self {
+        value.source
+    }
+}
+
+impl std::fmt::Debug for WatchlistPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchlistPattern")
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for WatchlistPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl WatchlistPattern {
+    /// Loads the entries of the list
+    ///
+    /// On a dry run, only the config of the source is checked, files aren't
+    /// read and Redis isn't contacted.
+    pub(super) fn bootstrap(&self, ctx: &Context) -> Result<(), Error> {
+        let origin = self.source.origin(ctx)?;
+
+        if ctx.dry_run {
+            return match &origin {
+                Origin::File(path) if !path.is_file() => Err(Error::config(format!(
+                    "watchlist file {} doesn't exist",
+                    path.display()
+                ))),
+                Origin::Redis(_) if cfg!(not(feature = "redis")) => Err(Error::config(
+                    "watchlists from redis require oura to be built with the `redis` feature",
+                )),
+                _ => Ok(()),
+            };
+        }
+
+        let (index, version) = origin
+            .load(None)
+            .map_err(|err| Error::config(format!("can't load watchlist: {err}")))?
+            .unwrap_or_default();
+
+        info!(entries = index.len(), "watchlist loaded");
+
+        let versioned = origin.is_versioned();
+        let (stop, stopped) = mpsc::channel();
+
+        let loaded = Arc::new(Loaded {
+            origin,
+            index: ArcSwap::from_pointee(index),
+            version: Mutex::new(version),
+            _stop: stop,
+        });
+
+        // the same pattern can't be bootstrapped twice, the first load stays
+        if self.loaded.set(loaded.clone()).is_ok() && versioned {
+            spawn_refresher(
+                Arc::downgrade(&loaded),
+                self.source.reload_interval(),
+                stopped,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Checks if any of the addresses or assets of a tx is in the list
+    ///
+    /// The outcome is uncertain until the list is loaded by bootstrapping.
+    pub(super) fn is_any_listed<'a>(
+        &self,
+        mut addresses: impl Iterator<Item = &'a [u8]>,
+        mut assets: impl Iterator<Item = &'a Multiasset>,
+    ) -> MatchOutcome {
+        let Some(loaded) = self.loaded.get() else {
+            return MatchOutcome::Uncertain;
+        };
+
+        let index = loaded.index.load();
+
+        let a = addresses.any(|x| index.contains_address(x));
+
+        let b = assets.any(|x| index.contains_asset(x));
+
+        MatchOutcome::if_true(a || b)
+    }
+}
+
+impl PatternOf<&ParsedTx> for WatchlistPattern {
+    fn is_match(&self, subject: &ParsedTx) -> MatchOutcome {
+        self.is_any_listed(iter_tx_addresses(subject), iter_tx_assets(subject))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(dir: &tempfile::TempDir, dry_run: bool) -> Context {
+        Context {
+            chain: Default::default(),
+            intersect: IntersectConfig::Tip,
+            finalize: None,
+            current_dir: dir.path().to_owned(),
+            breadcrumbs: Breadcrumbs::new(0),
+            dead_letters: Default::default(),
+            pause: Default::default(),
+            tip: Default::default(),
+            dry_run,
+        }
+    }
+
+    fn watchlist(entries: &str) -> (WatchlistPattern, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("list.txt"), entries).unwrap();
+
+        let source = r#"{ "file": "list.txt", "reload_interval": 3600 }"#;
+        let pattern: WatchlistPattern = serde_json::from_str(source).unwrap();
+
+        pattern.bootstrap(&context(&dir, false)).unwrap();
+
+        (pattern, dir)
+    }
+
+    fn positives(pattern: &WatchlistPattern) -> Vec<usize> {
+        testing::find_positive_test_vectors(Pattern::Watchlist(pattern.clone()))
+    }
+
+    fn refresh(pattern: &WatchlistPattern) -> anyhow::Result<()> {
+        pattern.loaded.get().unwrap().refresh()
+    }
+
+    #[test]
+    fn match_entries() {
+        let (pattern, dir) = watchlist(
+            "# customers\n\naddr_vkh1jjfnzhxe966a33psfenm0ct2udkkr569qf55v4uprgkgu8zsvmg\n",
+        );
+
+        assert_eq!(positives(&pattern), vec![1, 2, 3]);
+
+        let path = dir.path().join("list.txt");
+        std::fs::write(path, "asset1tra0mxecpkzgpu8a93jedlqzc9fr9wjwkf2f5y\n").unwrap();
+
+        // the previous entries stay in place until the list is refreshed
+        assert_eq!(positives(&pattern), vec![1, 2, 3]);
+
+        refresh(&pattern).unwrap();
+        assert_eq!(positives(&pattern), vec![1, 3]);
+    }
+
+    #[test]
+    fn refresh_in_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("list.txt");
+        std::fs::write(&path, "").unwrap();
+
+        let source = r#"{ "file": "list.txt", "reload_interval": 1 }"#;
+        let pattern: WatchlistPattern = serde_json::from_str(source).unwrap();
+        pattern.bootstrap(&context(&dir, false)).unwrap();
+
+        assert!(positives(&pattern).is_empty());
+
+        std::fs::write(path, "asset1tra0mxecpkzgpu8a93jedlqzc9fr9wjwkf2f5y\n").unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+
+        while positives(&pattern).is_empty() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "watchlist wasn't refreshed"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(positives(&pattern), vec![1, 3]);
+    }
+
+    #[test]
+    fn keep_entries_on_invalid_reload() {
+        let (pattern, dir) =
+            watchlist("stake_vkh1xdak9nllvsp6q636e0p5lrzxqq7xnlne5d3gemafc3e9z3v4vud\n");
+
+        assert_eq!(positives(&pattern), vec![1, 3]);
+
+        std::fs::write(dir.path().join("list.txt"), "not an entry\n").unwrap();
+
+        assert!(refresh(&pattern).is_err());
+        assert_eq!(positives(&pattern), vec![1, 3]);
+    }
+
+    #[test]
+    fn reject_invalid_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("list.txt"),
+            "pool1pu5jlj4q9w9jlxeu370a3c9myx47md5j5m2str0naunn2q3lkdy\n",
+        )
+        .unwrap();
+
+        let pattern: WatchlistPattern = serde_json::from_str(r#"{ "file": "list.txt" }"#).unwrap();
+
+        assert!(pattern.bootstrap(&context(&dir, false)).is_err());
+
+        // a dry run doesn't read the file
+        assert!(pattern.bootstrap(&context(&dir, true)).is_ok());
+    }
+
+    #[test]
+    fn uncertain_until_bootstrapped() {
+        let pattern: WatchlistPattern = serde_json::from_str(r#"{ "file": "list.txt" }"#).unwrap();
+        let tx = ParsedTx::default();
+
+        assert_eq!(pattern.is_match(&tx), MatchOutcome::Uncertain);
+    }
+}
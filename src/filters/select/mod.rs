@@ -2,17 +2,42 @@ use gasket::framework::*;
 use serde::Deserialize;
 use tracing::info;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
-use self::eval::{MatchOutcome, Predicate, StringOrStruct};
+use self::eval::{MatchOutcome, StringOrStruct};
 
-mod eval;
+pub(crate) mod eval;
+
+pub use self::eval::{eval, NoLedger, Predicate};
+
+/// Fails if the predicate has patterns that parsed records can't match
+pub(crate) fn check_parsed(predicate: &Predicate) -> Result<(), Error> {
+    if predicate.has_governance_patterns() {
+        return Err(Error::config(
+            "vote and proposal patterns can't match the records of a ParseCbor stage, place the filter before it",
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub enum Mode {
+    /// Matching records are passed through as they are
+    #[default]
+    Filter,
+    /// Matching blocks are trimmed down to the txs that match
+    Project,
+}
 
 #[derive(Stage)]
 #[stage(name = "select", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
     predicate: Predicate,
     skip_uncertain: bool,
+    mode: Mode,
+    dead_letters: DeadLetters,
 
     pub input: FilterInputPort,
     pub output: FilterOutputPort,
@@ -47,11 +72,26 @@ impl gasket::framework::Worker<Stage> for Worker {
         };
 
         match is_match {
-            MatchOutcome::Positive => stage.output.send(unit.clone().into()).await.or_panic()?,
+            MatchOutcome::Positive => {
+                let unit = match (stage.mode, unit) {
+                    (Mode::Project, ChainEvent::Apply(p, r)) => {
+                        ChainEvent::Apply(p.clone(), eval::project(r, &stage.predicate))
+                    }
+                    (Mode::Project, ChainEvent::Undo(p, r)) => {
+                        ChainEvent::Undo(p.clone(), eval::project(r, &stage.predicate))
+                    }
+                    _ => unit.clone(),
+                };
+
+                stage.output.send(unit.into()).await.or_panic()?
+            }
             MatchOutcome::Negative => (),
             MatchOutcome::Uncertain => {
                 if !stage.skip_uncertain {
-                    return Err(WorkerError::Panic);
+                    stage
+                        .dead_letters
+                        .divert("select", "uncertain match outcome", unit)
+                        .await?;
                 }
             }
         };
@@ -66,15 +106,25 @@ impl gasket::framework::Worker<Stage> for Worker {
 pub struct Config {
     pub predicate: StringOrStruct<Predicate>,
     pub skip_uncertain: bool,
+    pub mode: Option<Mode>,
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    /// Fails if the predicate can't be evaluated on parsed records
+    pub fn check_parsed(&self) -> Result<(), Error> {
+        check_parsed(&self.predicate)
+    }
+
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         info!(predicate = ?self.predicate, "selection filter predicate");
 
+        self.predicate.bootstrap(ctx)?;
+
         let stage = Stage {
             predicate: self.predicate.unwrap(),
             skip_uncertain: self.skip_uncertain,
+            mode: self.mode.unwrap_or_default(),
+            dead_letters: ctx.dead_letters.clone(),
             ops_count: Default::default(),
             input: Default::default(),
             output: Default::default(),
@@ -3,6 +3,7 @@
 use gasket::framework::*;
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 #[derive(Stage)]
@@ -11,7 +12,9 @@ pub struct Stage {
     pub input: FilterInputPort,
     pub output: FilterOutputPort,
 
-    plugin: extism::Plugin,
+    /// The loaded plugin, absent when the pipeline is only validated
+    plugin: Option<extism::Plugin>,
+    dead_letters: DeadLetters,
 
     #[metric]
     ops_count: gasket::metrics::Counter,
@@ -19,26 +22,20 @@ pub struct Stage {
 
 impl Stage {
     fn map_record(&mut self, r: Record) -> Result<Vec<Record>, Error> {
+        let plugin = self
+            .plugin
+            .as_mut()
+            .ok_or_else(|| Error::custom("wasm plugin not loaded"))?;
+
         let extism::convert::Json::<serde_json::Value>(output) = match r {
-            Record::CborBlock(x) => self.plugin.call("map_cbor_block", x).unwrap(),
-            Record::CborTx(x) => self.plugin.call("map_cbor_tx", x).unwrap(),
-            Record::ParsedTx(x) => self
-                .plugin
-                .call("map_u5c_tx", extism::convert::Json(x))
-                .unwrap(),
-            Record::ParsedBlock(x) => self
-                .plugin
-                .call("map_u5c_block", extism::convert::Json(x))
-                .unwrap(),
-            Record::GenericJson(x) => self
-                .plugin
-                .call("map_json", extism::convert::Json(x))
-                .unwrap(),
-            Record::OuraV1Event(x) => self
-                .plugin
-                .call("map_json", extism::convert::Json(x))
-                .unwrap(),
-        };
+            Record::CborBlock(x) => plugin.call("map_cbor_block", x),
+            Record::CborTx(x) => plugin.call("map_cbor_tx", x),
+            Record::ParsedTx(x) => plugin.call("map_u5c_tx", extism::convert::Json(x)),
+            Record::ParsedBlock(x) => plugin.call("map_u5c_block", extism::convert::Json(x)),
+            Record::GenericJson(x) => plugin.call("map_json", extism::convert::Json(x)),
+            Record::OuraV1Event(x) => plugin.call("map_json", extism::convert::Json(x)),
+        }
+        .map_err(Error::custom)?;
 
         let output = match output {
             serde_json::Value::Null => vec![],
@@ -75,10 +72,14 @@ impl gasket::framework::Worker<Stage> for Worker {
     }
 
     async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
-        let output = unit
-            .clone()
-            .try_map_record_to_many(|x| stage.map_record(x))
-            .or_panic()?;
+        let output = match unit.clone().try_map_record_to_many(|x| stage.map_record(x)) {
+            Ok(x) => x,
+            Err(err) => {
+                stage.dead_letters.divert("filter-wasm", err, unit).await?;
+
+                return Ok(());
+            }
+        };
 
         for unit in output {
             stage.output.send(unit.clone().into()).await.or_panic()?;
@@ -95,16 +96,33 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
-        let wasm = extism::Wasm::file(self.path);
-        let manifest = extism::Manifest::new([wasm]);
-        let plugin = extism::Plugin::new(&manifest, [], true).map_err(Error::custom)?;
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
+        let path = ctx.current_dir.join(self.path);
+
+        if !path.is_file() {
+            return Err(Error::config(format!(
+                "wasm plugin not found at {}",
+                path.display()
+            )));
+        }
+
+        // validating a pipeline only checks that the plugin exists
+        let plugin = match ctx.dry_run {
+            true => None,
+            false => {
+                let wasm = extism::Wasm::file(path);
+                let manifest = extism::Manifest::new([wasm]);
+                let plugin = extism::Plugin::new(&manifest, [], true).map_err(Error::custom)?;
+                Some(plugin)
+            }
+        };
 
         Ok(Stage {
             input: Default::default(),
             output: Default::default(),
             ops_count: Default::default(),
             plugin,
+            dead_letters: ctx.dead_letters.clone(),
         })
     }
 }
@@ -0,0 +1,206 @@
+//! Hand-off of events that a stage can't process to a dead-letter sink
+//!
+//! Stages get a [`DeadLetters`] handle through the [`Context`](super::Context).
+//! When the pipeline has no dead-letter sink configured, the handle keeps the
+//! previous behavior: errors are retried or make the stage panic. A sink then
+//! retries a failing event according to the retry policy, which stops the
+//! pipeline once `max_retries` run out, or skips the event without a trace
+//! when the policy is `dismissible`.
+
+use gasket::framework::WorkerError;
+use gasket::messaging::tokio::ChannelSendAdapter;
+use gasket::messaging::SendAdapter;
+use serde_json::{json, Value as JsonValue};
+use std::fmt::Display;
+use tracing::{error, warn};
+
+use super::{ChainEvent, Record};
+
+pub const DEFAULT_MAX_ATTEMPTS: usize = 5;
+
+/// An event that a stage gave up on, along with the reason
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub stage: String,
+    pub error: String,
+    pub event: ChainEvent,
+}
+
+impl DeadLetter {
+    /// The event delivered to the dead-letter sink, with the details as json
+    pub fn into_event(self) -> ChainEvent {
+        let point = self.event.point().clone();
+
+        let record = json!({
+            "stage": self.stage,
+            "error": self.error,
+            "event": JsonValue::from(self.event),
+        });
+
+        ChainEvent::Apply(point, Record::GenericJson(record))
+    }
+}
+
+/// Handle used by stages to send events to the dead-letter sink
+#[derive(Clone, Default)]
+pub struct DeadLetters {
+    sender: Option<ChannelSendAdapter<DeadLetter>>,
+    max_attempts: usize,
+    attempts: usize,
+}
+
+impl DeadLetters {
+    pub fn new(sender: ChannelSendAdapter<DeadLetter>, max_attempts: usize) -> Self {
+        Self {
+            sender: Some(sender),
+            max_attempts,
+            attempts: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Sends an event to the dead-letter sink
+    ///
+    /// Without a dead-letter sink, the error makes the stage panic.
+    pub async fn divert(
+        &mut self,
+        stage: &str,
+        err: impl Display,
+        event: &ChainEvent,
+    ) -> Result<(), WorkerError> {
+        let Some(sender) = self.sender.as_mut() else {
+            error!(stage, %err, "can't process event");
+            return Err(WorkerError::Panic);
+        };
+
+        warn!(stage, %err, point = ?event.point(), "sending event to dead-letter sink");
+
+        let letter = DeadLetter {
+            stage: stage.to_owned(),
+            error: err.to_string(),
+            event: event.clone(),
+        };
+
+        sender.send(letter.into()).await.map_err(|err| {
+            error!(%err, "can't reach dead-letter sink");
+            WorkerError::Panic
+        })
+    }
+
+    /// Checks the result of an attempt at processing an event
+    ///
+    /// Failed attempts are retried, until the max number of attempts is
+    /// reached and the event is sent to the dead-letter sink. Returns `None`
+    /// in that case, so that the stage can move on to the next event. Without
+    /// a dead-letter sink, every failed attempt is left to the retry policy of
+    /// the stage, which fails once its `max_retries` are exhausted or drops
+    /// the event if the policy is `dismissible`.
+    pub async fn attempt<T, E: Display>(
+        &mut self,
+        stage: &str,
+        event: &ChainEvent,
+        result: Result<T, E>,
+    ) -> Result<Option<T>, WorkerError> {
+        let err = match result {
+            Ok(x) => {
+                self.attempts = 0;
+                return Ok(Some(x));
+            }
+            Err(err) => err,
+        };
+
+        self.attempts += 1;
+
+        if !self.is_enabled() || self.attempts < self.max_attempts {
+            warn!(stage, %err, attempt = self.attempts, "failed to process event");
+            return Err(WorkerError::Retry);
+        }
+
+        self.attempts = 0;
+        self.divert(stage, err, event).await?;
+
+        Ok(None)
+    }
+
+    /// Checks the result of a sink delivering an event, see [`Self::attempt`]
+    ///
+    /// Delivered events are counted in the given metric.
+    pub async fn deliver<T, E: Display>(
+        &mut self,
+        stage: &str,
+        event: &ChainEvent,
+        result: Result<T, E>,
+        ops_count: &gasket::metrics::Counter,
+    ) -> Result<Option<T>, WorkerError> {
+        let delivered = self.attempt(stage, event, result).await?;
+
+        if delivered.is_some() {
+            ops_count.inc(1);
+        }
+
+        Ok(delivered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::messaging::tokio::mpsc_channel;
+    use gasket::messaging::RecvAdapter;
+    use pallas::network::miniprotocols::Point;
+
+    use super::*;
+
+    fn event() -> ChainEvent {
+        ChainEvent::Apply(
+            Point::Specific(10, vec![0xab]),
+            Record::GenericJson(json!({})),
+        )
+    }
+
+    #[test]
+    fn diverts_after_max_attempts() {
+        futures::executor::block_on(async {
+            let (sender, mut receiver) = mpsc_channel(10);
+            let mut letters = DeadLetters::new(sender, 3);
+
+            for _ in 0..2 {
+                let result = letters
+                    .attempt("sink", &event(), Err::<(), _>("boom"))
+                    .await;
+                assert!(matches!(result, Err(WorkerError::Retry)));
+            }
+
+            let result = letters
+                .attempt("sink", &event(), Err::<(), _>("boom"))
+                .await;
+            assert!(matches!(result, Ok(None)));
+
+            let letter = receiver.recv().await.unwrap().payload;
+            assert_eq!(letter.stage, "sink");
+            assert_eq!(letter.error, "boom");
+
+            let result = letters.attempt("sink", &event(), Ok::<_, &str>(1)).await;
+            assert!(matches!(result, Ok(Some(1))));
+        });
+    }
+
+    #[test]
+    fn panics_without_a_sink() {
+        futures::executor::block_on(async {
+            let mut letters = DeadLetters::default();
+
+            let result = letters.divert("select", "boom", &event()).await;
+            assert!(matches!(result, Err(WorkerError::Panic)));
+
+            for _ in 0..10 {
+                let result = letters
+                    .attempt("sink", &event(), Err::<(), _>("boom"))
+                    .await;
+                assert!(matches!(result, Err(WorkerError::Retry)));
+            }
+        });
+    }
+}
@@ -0,0 +1,67 @@
+//! Helpers to connect a single output port to many input ports
+
+use gasket::messaging::tokio::{mpsc_channel, ChannelSendAdapter};
+use gasket::messaging::{InputPort, Message, OutputPort, SendAdapter};
+
+/// Send adapter that delivers a copy of each message to every receiver
+///
+/// Each send awaits all of the underlying channels, so the slowest receiver
+/// dictates the pace. This is intentional: a broadcast channel would make
+/// slow receivers lag and lose messages, which is not acceptable for sinks.
+pub struct FanoutSendAdapter<P>(Vec<ChannelSendAdapter<P>>);
+
+#[async_trait::async_trait]
+impl<P> SendAdapter<P> for FanoutSendAdapter<P>
+where
+    P: Send + Sync + Clone,
+{
+    async fn send(&mut self, msg: Message<P>) -> Result<(), gasket::error::Error> {
+        for sender in self.0.iter_mut() {
+            sender.send(msg.clone()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn connect_fanout<P>(output: &mut OutputPort<P>, inputs: Vec<&mut InputPort<P>>, cap: usize)
+where
+    P: Send + Sync + Clone + 'static,
+{
+    let mut senders = Vec::with_capacity(inputs.len());
+
+    for input in inputs {
+        let (sender, receiver) = mpsc_channel::<P>(cap);
+        input.connect(receiver);
+        senders.push(sender);
+    }
+
+    output.connect(FanoutSendAdapter(senders));
+}
+
+/// Send adapter that forwards each message to a set of output ports
+///
+/// Used when the ports on the receiving end are already connected to their
+/// own downstream stages, eg: a source shared by many pipelines.
+pub struct PortsSendAdapter<P>(Vec<OutputPort<P>>);
+
+#[async_trait::async_trait]
+impl<P> SendAdapter<P> for PortsSendAdapter<P>
+where
+    P: Send + Sync + Clone,
+{
+    async fn send(&mut self, msg: Message<P>) -> Result<(), gasket::error::Error> {
+        for port in self.0.iter_mut() {
+            port.send(msg.clone()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn connect_fanout_ports<P>(output: &mut OutputPort<P>, ports: Vec<OutputPort<P>>)
+where
+    P: Send + Sync + Clone + 'static,
+{
+    output.connect(PortsSendAdapter(ports));
+}
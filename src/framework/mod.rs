@@ -16,12 +16,19 @@ pub use pallas::interop::utxorpc::spec::cardano::Tx as ParsedTx;
 // we use GenesisValues from Pallas as our ChainConfig
 pub use pallas::ledger::traverse::wellknown::GenesisValues;
 
+pub mod dead_letter;
 pub mod errors;
+pub mod fanout;
 pub mod legacy_v1;
+pub mod pause;
+pub mod rollback;
+pub mod tip;
 
 pub use errors::*;
+pub use pause::PauseSwitch;
+pub use tip::ChainTip;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Breadcrumbs {
     state: VecDeque<Point>,
     max: usize,
@@ -99,6 +106,13 @@ pub struct Context {
     pub finalize: Option<FinalizeConfig>,
     pub current_dir: PathBuf,
     pub breadcrumbs: Breadcrumbs,
+    pub dead_letters: dead_letter::DeadLetters,
+    pub pause: PauseSwitch,
+    pub tip: ChainTip,
+
+    /// Stages are bootstrapped only to check the config, they shouldn't open
+    /// files, databases or connections
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -286,41 +300,138 @@ impl IntersectConfig {
 /// Optional configuration to stop processing new blocks after processing:
 ///   1. a block with the given hash
 ///   2. the first block on or after a given absolute slot
-///   3. TODO: a total of X blocks
-#[derive(Deserialize, Debug, Clone)]
+///   3. a total of X blocks
+///   4. the last block of a given epoch
+///   5. the last block before a given wall-clock time (unix seconds)
+///   6. the block at the tip of the chain
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct FinalizeConfig {
     until_hash: Option<String>,
     max_block_slot: Option<u64>,
-    // max_block_quantity: Option<u64>,
+    max_block_quantity: Option<u64>,
+    until_epoch: Option<u64>,
+    until_timestamp: Option<u64>,
+    #[serde(default)]
+    until_tip: bool,
+}
+
+impl FinalizeConfig {
+    pub fn until_tip(&self) -> bool {
+        self.until_tip
+    }
 }
 
+/// Outcome of evaluating the finalize conditions for a new block
+#[derive(Debug, PartialEq, Eq)]
+pub enum Finalize {
+    /// Keep processing blocks
+    No,
+    /// Process this block and stop afterwards
+    AfterBlock,
+    /// Stop without processing this block, it's beyond the requested limits
+    BeforeBlock,
+}
+
+/// Evaluates the finalize conditions for a block about to be sent downstream
+///
+/// The block count includes the block being evaluated.
 pub fn should_finalize(
     config: &Option<FinalizeConfig>,
-    last_point: &Point,
-    // block_count: u64,
-) -> bool {
+    chain: &GenesisValues,
+    point: &Point,
+    block_count: u64,
+) -> Finalize {
     let config = match config {
         Some(x) => x,
-        None => return false,
+        None => return Finalize::No,
     };
 
+    let slot = point.slot_or_default();
+
+    if let Some(epoch) = config.until_epoch {
+        let (current, _) = chain.absolute_slot_to_relative(slot);
+
+        if current > epoch {
+            return Finalize::BeforeBlock;
+        }
+    }
+
+    if let Some(timestamp) = config.until_timestamp {
+        if chain.slot_to_wallclock(slot) >= timestamp {
+            return Finalize::BeforeBlock;
+        }
+    }
+
     if let Some(expected) = &config.until_hash {
-        if let Point::Specific(_, current) = last_point {
-            return expected == &hex::encode(current);
+        if let Point::Specific(_, current) = point {
+            if expected == &hex::encode(current) {
+                return Finalize::AfterBlock;
+            }
         }
     }
 
     if let Some(max) = config.max_block_slot {
-        if last_point.slot_or_default() >= max {
-            return true;
+        if slot >= max {
+            return Finalize::AfterBlock;
+        }
+    }
+
+    if let Some(max) = config.max_block_quantity {
+        if block_count >= max {
+            return Finalize::AfterBlock;
         }
     }
 
-    // if let Some(max) = config.max_block_quantity {
-    //     if block_count >= max {
-    //         return true;
-    //     }
-    // }
+    Finalize::No
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: serde_json::Value) -> Option<FinalizeConfig> {
+        Some(serde_json::from_value(json).unwrap())
+    }
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, vec![0xab; 32])
+    }
 
-    false
+    #[test]
+    fn finalize_conditions() {
+        let chain = GenesisValues::mainnet();
+
+        assert_eq!(should_finalize(&None, &chain, &point(10), 10), Finalize::No);
+
+        let max_blocks = config(json!({ "max_block_quantity": 3 }));
+        assert_eq!(
+            should_finalize(&max_blocks, &chain, &point(10), 2),
+            Finalize::No
+        );
+        assert_eq!(
+            should_finalize(&max_blocks, &chain, &point(10), 3),
+            Finalize::AfterBlock
+        );
+
+        // epoch 208 is the first shelley epoch on mainnet
+        let epoch = config(json!({ "until_epoch": 208 }));
+        assert_eq!(
+            should_finalize(&epoch, &chain, &point(4492800), 1),
+            Finalize::No
+        );
+        assert_eq!(
+            should_finalize(&epoch, &chain, &point(4492800 + 432000), 1),
+            Finalize::BeforeBlock
+        );
+
+        let timestamp = config(json!({ "until_timestamp": 1596059091u64 }));
+        assert_eq!(
+            should_finalize(&timestamp, &chain, &point(4492799), 1),
+            Finalize::No
+        );
+        assert_eq!(
+            should_finalize(&timestamp, &chain, &point(4492800), 1),
+            Finalize::BeforeBlock
+        );
+    }
 }
@@ -0,0 +1,43 @@
+//! Switch used to hold a source from pulling new events
+//!
+//! Sources check the switch before fetching each event. While paused, they
+//! stay idle without closing their connection, so the rest of the stages can
+//! drain the in-flight events.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a paused source waits before checking the switch again
+const PAUSED_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Default, Debug)]
+pub struct PauseSwitch(Arc<AtomicBool>);
+
+impl PauseSwitch {
+    pub fn pause(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Waits for a while if the switch is paused, returning true in that case
+    ///
+    /// Sources return an idle schedule when this is true, so that the stage
+    /// can still be dismissed while paused.
+    pub async fn hold(&self) -> bool {
+        if !self.is_paused() {
+            return false;
+        }
+
+        tokio::time::sleep(PAUSED_INTERVAL).await;
+
+        true
+    }
+}
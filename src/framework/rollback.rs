@@ -0,0 +1,139 @@
+//! A buffer of recently applied records used to undo them on rollback
+
+use pallas::network::miniprotocols::Point;
+use std::collections::VecDeque;
+
+use super::Record;
+
+/// Keeps the records of the latest blocks applied to the pipeline
+///
+/// Records are grouped by the point of the block they belong to and the
+/// buffer holds at most `max_depth` blocks. When a rollback happens, the
+/// records that are newer than the rollback point are returned in the
+/// reverse order in which they were applied, so that they can be undone.
+pub struct RollbackBuffer {
+    blocks: VecDeque<(Point, Vec<Record>)>,
+    max_depth: usize,
+}
+
+impl RollbackBuffer {
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            blocks: Default::default(),
+            max_depth,
+        }
+    }
+
+    /// Number of blocks currently held in the buffer
+    pub fn depth(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// The oldest point that can still be undone
+    pub fn oldest(&self) -> Option<&Point> {
+        self.blocks.front().map(|(p, _)| p)
+    }
+
+    pub fn apply(&mut self, point: &Point, record: Record) {
+        match self.blocks.back_mut() {
+            Some((last, records)) if last == point => records.push(record),
+            _ => {
+                self.blocks.push_back((point.clone(), vec![record]));
+
+                if self.blocks.len() > self.max_depth {
+                    self.blocks.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Removes and returns the records applied after the rollback point
+    ///
+    /// The returned records are ordered from newest to oldest. A block at the
+    /// same slot as the rollback point but with a different hash is on the
+    /// abandoned fork, so it's undone as well.
+    pub fn rollback(&mut self, point: &Point) -> Vec<(Point, Record)> {
+        let mut undone = vec![];
+
+        while let Some((last, _)) = self.blocks.back() {
+            if last == point || last.slot_or_default() < point.slot_or_default() {
+                break;
+            }
+
+            let (last, records) = self.blocks.pop_back().unwrap();
+            undone.extend(records.into_iter().rev().map(|r| (last.clone(), r)));
+        }
+
+        undone
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    fn record(value: u64) -> Record {
+        Record::GenericJson(value.into())
+    }
+
+    fn slots(undone: &[(Point, Record)]) -> Vec<u64> {
+        undone.iter().map(|(p, _)| p.slot_or_default()).collect()
+    }
+
+    #[test]
+    fn undoes_newer_records_in_reverse() {
+        let mut buffer = RollbackBuffer::new(10);
+
+        buffer.apply(&point(1), record(1));
+        buffer.apply(&point(2), record(2));
+        buffer.apply(&point(2), record(3));
+        buffer.apply(&point(3), record(4));
+
+        let undone = buffer.rollback(&point(1));
+        assert_eq!(slots(&undone), vec![3, 2, 2]);
+
+        let values: Vec<_> = undone
+            .into_iter()
+            .map(|(_, r)| match r {
+                Record::GenericJson(x) => x.as_u64().unwrap(),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(values, vec![4, 3, 2]);
+        assert_eq!(buffer.depth(), 1);
+    }
+
+    #[test]
+    fn undoes_forks_at_the_same_slot() {
+        let mut buffer = RollbackBuffer::new(10);
+
+        buffer.apply(&point(1), record(1));
+        buffer.apply(&point(2), record(2));
+
+        let fork = Point::Specific(2, vec![0xff]);
+        assert_eq!(slots(&buffer.rollback(&fork)), vec![2]);
+
+        assert!(buffer.rollback(&point(1)).is_empty());
+        assert_eq!(buffer.depth(), 1);
+    }
+
+    #[test]
+    fn keeps_max_depth() {
+        let mut buffer = RollbackBuffer::new(2);
+
+        buffer.apply(&point(1), record(1));
+        buffer.apply(&point(2), record(2));
+        buffer.apply(&point(3), record(3));
+
+        assert_eq!(buffer.depth(), 2);
+        assert_eq!(buffer.oldest(), Some(&point(2)));
+
+        let undone = buffer.rollback(&Point::Origin);
+        assert_eq!(slots(&undone), vec![3, 2]);
+    }
+}
@@ -0,0 +1,114 @@
+//! The latest blocks sent downstream by the source of a pipeline
+
+use pallas::network::miniprotocols::Point;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+struct Blocks {
+    points: VecDeque<Point>,
+    max: usize,
+}
+
+/// Shared record of the latest blocks that the source sent downstream
+///
+/// Filters can drop events, so the stages after them don't see every block of
+/// the chain. The source records each block it sends here, which lets those
+/// stages tell how far the chain has advanced past the events they hold.
+#[derive(Clone, Debug, Default)]
+pub struct ChainTip(Arc<Mutex<Blocks>>);
+
+impl ChainTip {
+    /// Makes sure that at least the latest `depth` blocks are kept
+    pub fn keep(&self, depth: usize) {
+        let mut blocks = self.0.lock().unwrap();
+        blocks.max = blocks.max.max(depth + 1);
+    }
+
+    pub fn apply(&self, point: &Point) {
+        let mut blocks = self.0.lock().unwrap();
+
+        if blocks.points.back() == Some(point) {
+            return;
+        }
+
+        blocks.points.push_back(point.clone());
+
+        while blocks.points.len() > blocks.max {
+            blocks.points.pop_front();
+        }
+    }
+
+    /// Forgets the given block if it's the latest one, for sources that undo
+    /// blocks one by one
+    pub fn undo(&self, point: &Point) {
+        let mut blocks = self.0.lock().unwrap();
+
+        if blocks.points.back() == Some(point) {
+            blocks.points.pop_back();
+        }
+    }
+
+    /// Forgets the blocks after the rollback point
+    pub fn rollback(&self, point: &Point) {
+        let mut blocks = self.0.lock().unwrap();
+
+        while let Some(last) = blocks.points.back() {
+            if last == point || last.slot_or_default() < point.slot_or_default() {
+                break;
+            }
+
+            blocks.points.pop_back();
+        }
+    }
+
+    /// Number of blocks sent after the given one
+    ///
+    /// Blocks older than the ones that are kept are at least as deep as the
+    /// number of kept blocks. Returns none if the block is unknown, either
+    /// because it was rolled back or because the source doesn't record its
+    /// blocks here.
+    pub fn depth_of(&self, point: &Point) -> Option<usize> {
+        let blocks = self.0.lock().unwrap();
+
+        if let Some(idx) = blocks.points.iter().rposition(|x| x == point) {
+            return Some(blocks.points.len() - 1 - idx);
+        }
+
+        let oldest = blocks.points.front()?;
+
+        let forgotten =
+            blocks.points.len() >= blocks.max && point.slot_or_default() < oldest.slot_or_default();
+
+        forgotten.then_some(blocks.points.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> Point {
+        Point::Specific(slot, slot.to_be_bytes().to_vec())
+    }
+
+    #[test]
+    fn measures_depth() {
+        let tip = ChainTip::default();
+        tip.keep(2);
+
+        for slot in 1..=5 {
+            tip.apply(&point(slot));
+        }
+
+        assert_eq!(tip.depth_of(&point(5)), Some(0));
+        assert_eq!(tip.depth_of(&point(3)), Some(2));
+
+        // forgotten blocks are at least as deep as the kept ones
+        assert_eq!(tip.depth_of(&point(1)), Some(3));
+
+        tip.rollback(&Point::Specific(4, vec![0xff]));
+        assert_eq!(tip.depth_of(&point(3)), Some(0));
+        assert_eq!(tip.depth_of(&point(4)), None);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod cursor;
 pub mod filters;
 pub mod framework;
+pub mod pipeline;
 pub mod sinks;
 pub mod sources;
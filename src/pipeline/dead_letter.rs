@@ -0,0 +1,215 @@
+//! Delivery of dead-lettered events to a dedicated sink
+//!
+//! Stages hand the events they can't process to a relay stage, which wraps
+//! them as regular events and forwards them to the dead-letter sink. The
+//! dead-letter sink doesn't report to the cursor of the pipeline.
+
+use gasket::framework::*;
+use gasket::messaging::tokio::{connect_ports, mpsc_channel};
+use gasket::messaging::{InputPort, Message, SendAdapter};
+use gasket::runtime::{Policy, Tether};
+use serde::Deserialize;
+
+use crate::framework::dead_letter::{DeadLetter, DeadLetters, DEFAULT_MAX_ATTEMPTS};
+use crate::framework::*;
+use crate::sinks;
+
+/// Send adapter that drops every message
+struct Discard;
+
+#[async_trait::async_trait]
+impl<P: Send + 'static> SendAdapter<P> for Discard {
+    async fn send(&mut self, _: Message<P>) -> Result<(), gasket::error::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Default::default())
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<DeadLetter>, WorkerError> {
+        // once every stage of the pipeline is gone there's nothing left to relay
+        match stage.input.recv().await {
+            Ok(msg) => Ok(WorkSchedule::Unit(msg.payload)),
+            Err(_) => Ok(WorkSchedule::Done),
+        }
+    }
+
+    async fn execute(&mut self, unit: &DeadLetter, stage: &mut Stage) -> Result<(), WorkerError> {
+        let event = unit.clone().into_event();
+
+        stage.output.send(event.into()).await.or_panic()?;
+        stage.dead_letter_count.inc(1);
+
+        Ok(())
+    }
+}
+
+#[derive(Stage)]
+#[stage(name = "dead-letter", unit = "DeadLetter", worker = "Worker")]
+pub struct Stage {
+    pub input: InputPort<DeadLetter>,
+    pub output: FilterOutputPort,
+
+    #[metric]
+    dead_letter_count: gasket::metrics::Counter,
+}
+
+/// The relay and the sink, bootstrapped but not yet spawned
+pub struct Stages {
+    relay: Stage,
+    sink: sinks::Bootstrapper,
+}
+
+impl Stages {
+    pub fn spawn(self, policy: Policy) -> Vec<Tether> {
+        let Self {
+            mut relay,
+            mut sink,
+        } = self;
+
+        connect_ports(&mut relay.output, sink.borrow_input(), 100);
+        sink.borrow_cursor().connect(Discard);
+
+        vec![
+            gasket::runtime::spawn_stage(relay, policy.clone()),
+            sink.spawn(policy),
+        ]
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    pub sink: sinks::Config,
+    pub max_attempts: Option<usize>,
+}
+
+impl Config {
+    /// Bootstraps the dead-letter stages, returning the handle for the rest
+    /// of the stages of the pipeline
+    ///
+    /// Failed attempts go through the retry policy of the pipeline, so the
+    /// stages need to be allowed at least as many retries as attempts.
+    pub fn bootstrapper(
+        self,
+        ctx: &Context,
+        max_retries: usize,
+    ) -> Result<(Stages, DeadLetters), Error> {
+        let max_attempts = self.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS);
+
+        if max_attempts == 0 {
+            return Err(Error::config("dead letter max_attempts must be at least 1"));
+        }
+
+        if max_attempts > max_retries.max(1) {
+            return Err(Error::config(format!(
+                "dead letter max_attempts ({max_attempts}) can't exceed the max_retries of the pipeline ({max_retries})"
+            )));
+        }
+
+        let sink = self.sink.bootstrapper(ctx)?;
+
+        let mut relay = Stage {
+            input: Default::default(),
+            output: Default::default(),
+            dead_letter_count: Default::default(),
+        };
+
+        let (sender, receiver) = mpsc_channel(100);
+        relay.input.connect(receiver);
+
+        let handle = DeadLetters::new(sender, max_attempts);
+
+        Ok((Stages { relay, sink }, handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gasket::messaging::tokio::ChannelSendAdapter;
+    use pallas::network::miniprotocols::Point;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::sinks::custom::{self, Sink};
+
+    struct Collect(Arc<Mutex<Vec<ChainEvent>>>);
+
+    #[async_trait::async_trait(?Send)]
+    impl Sink for Collect {
+        async fn apply(&mut self, event: &ChainEvent) -> Result<(), Error> {
+            self.0.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn relays_letters_to_the_sink() {
+        let collected = Arc::new(Mutex::new(vec![]));
+        let sink = custom::Stage::new(Collect(collected.clone()));
+
+        let mut relay = Stage {
+            input: Default::default(),
+            output: Default::default(),
+            dead_letter_count: Default::default(),
+        };
+
+        let (mut sender, receiver): (ChannelSendAdapter<DeadLetter>, _) = mpsc_channel(10);
+        relay.input.connect(receiver);
+
+        let stages = Stages {
+            relay,
+            sink: sinks::Bootstrapper::Custom(sink),
+        };
+
+        let tethers = stages.spawn(Policy::default());
+
+        let letter = DeadLetter {
+            stage: "sink-webhook".into(),
+            error: "boom".into(),
+            event: ChainEvent::Apply(
+                Point::Specific(10, vec![0xab]),
+                Record::GenericJson(json!(1)),
+            ),
+        };
+
+        futures::executor::block_on(sender.send(letter.into())).unwrap();
+
+        for _ in 0..50 {
+            if !collected.lock().unwrap().is_empty() {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        let collected = collected.lock().unwrap();
+
+        match collected.first() {
+            Some(ChainEvent::Apply(point, Record::GenericJson(x))) => {
+                assert_eq!(point.slot_or_default(), 10);
+                assert_eq!(x["stage"], "sink-webhook");
+                assert_eq!(x["error"], "boom");
+            }
+            x => panic!("unexpected event {x:?}"),
+        }
+
+        // the relay ends once there's no one left to send letters
+        drop(sender);
+
+        for tether in tethers {
+            tether.join_stage();
+        }
+    }
+}
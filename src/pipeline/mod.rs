@@ -0,0 +1,991 @@
+//! Assembly of complete pipelines, for the daemon and for embedding oura
+//!
+//! A pipeline is made of a source, an optional chain of filters, an outlet
+//! (one or more sinks, or a router with branches) and a cursor. The
+//! [`Builder`] takes the definition of each of those pieces, either as
+//! configuration or as already-built stages, connects their ports and spawns
+//! them, returning a [`Pipeline`] handle to control the running stages.
+//!
+//! ```no_run
+//! use oura::framework::*;
+//! use oura::pipeline::Builder;
+//! use oura::sinks::custom::Sink;
+//!
+//! struct Print;
+//!
+//! #[async_trait::async_trait(?Send)]
+//! impl Sink for Print {
+//!     async fn apply(&mut self, event: &ChainEvent) -> Result<(), Error> {
+//!         println!("{:?}", event.point());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn run(source: oura::sources::Config) -> Result<(), Error> {
+//! let pipeline = Builder::new()
+//!     .chain(ChainConfig::Preview)
+//!     .intersect(IntersectConfig::Tip)
+//!     .source(source)
+//!     .custom_sink(Print)
+//!     .build()?;
+//!
+//! pipeline.block();
+//! pipeline.teardown();
+//! # Ok(())
+//! # }
+//! ```
+
+use gasket::daemon::Daemon;
+use gasket::messaging::tokio::{ChannelRecvAdapter, ChannelSendAdapter};
+use gasket::messaging::Message;
+use gasket::runtime::{Policy, StagePhase, Tether, TetherState};
+use pallas::network::miniprotocols::Point;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::filters::route;
+use crate::framework::dead_letter::DeadLetters;
+use crate::framework::*;
+use crate::{cursor, filters, sinks, sources};
+
+pub mod dead_letter;
+pub mod multi;
+pub mod reload;
+
+#[cfg(feature = "stream")]
+pub mod stream;
+
+/// Name given to a pipeline that wasn't explicitly named
+pub const DEFAULT_NAME: &str = "default";
+
+pub fn define_gasket_policy(config: Option<&gasket::retries::Policy>) -> Policy {
+    let default_policy = gasket::retries::Policy {
+        max_retries: 20,
+        backoff_unit: Duration::from_secs(1),
+        backoff_factor: 2,
+        max_backoff: Duration::from_secs(60),
+        dismissible: false,
+    };
+
+    Policy {
+        tick_timeout: None,
+        bootstrap_retry: config.cloned().unwrap_or(default_policy.clone()),
+        work_retry: config.cloned().unwrap_or(default_policy.clone()),
+        teardown_retry: config.cloned().unwrap_or(default_policy.clone()),
+    }
+}
+
+/// The terminal section of the pipeline, where events leave oura
+pub enum Outlet {
+    Sinks(Vec<sinks::Bootstrapper>),
+    Route(route::Stage, Vec<route::Branch>),
+}
+
+/// Connects an output to the chain of filters, returning the last output
+fn connect_filters<'a>(
+    mut prev: &'a mut FilterOutputPort,
+    filters: &'a mut [filters::Bootstrapper],
+) -> &'a mut FilterOutputPort {
+    for filter in filters.iter_mut() {
+        gasket::messaging::tokio::connect_ports(prev, filter.borrow_input(), 100);
+        prev = filter.borrow_output();
+    }
+
+    prev
+}
+
+/// Connects the track input of the cursor to a new channel, returning the
+/// sending end of it
+fn connect_cursor(cursor: &mut cursor::Bootstrapper) -> tokio::sync::mpsc::Sender<Message<Point>> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(100);
+    cursor
+        .borrow_track()
+        .connect(ChannelRecvAdapter::Mpsc(receiver));
+
+    sender
+}
+
+/// Connects the filters and the outlet and spawns them
+///
+/// The filters and outlet are fed from the given output port, which doesn't
+/// need to belong to a source stage, and report to the cursor through the
+/// given channel.
+fn connect_downstream(
+    input: &mut FilterOutputPort,
+    mut filters: Vec<filters::Bootstrapper>,
+    outlet: Outlet,
+    track: &ChannelSendAdapter<Point>,
+    policy: Policy,
+) -> Result<Vec<Tether>, Error> {
+    let prev = connect_filters(input, &mut filters);
+
+    let mut tethers = vec![];
+
+    match outlet {
+        Outlet::Sinks(mut sinks) => {
+            let mut fanin = None;
+
+            match sinks.as_mut_slice() {
+                [] => return Err(Error::config("at least one sink is required")),
+                [sink] => {
+                    gasket::messaging::tokio::connect_ports(prev, sink.borrow_input(), 100);
+                    sink.borrow_cursor().connect(track.clone());
+                }
+                many => {
+                    let inputs = many.iter_mut().map(|x| x.borrow_input()).collect();
+                    fanout::connect_fanout(prev, inputs, 100);
+
+                    let mut stage = cursor::fanin::Stage::default();
+
+                    let ports = many.iter_mut().map(|x| x.borrow_cursor()).collect();
+                    stage.connect_sinks(ports, None, 100);
+                    stage.output.connect(track.clone());
+
+                    fanin = Some(stage);
+                }
+            }
+
+            tethers.extend(sinks.into_iter().map(|x| x.spawn(policy.clone())));
+            tethers.extend(fanin.map(|x| gasket::runtime::spawn_stage(x, policy.clone())));
+        }
+        Outlet::Route(mut route, mut branches) => {
+            gasket::messaging::tokio::connect_ports(prev, &mut route.input, 100);
+
+            let mut fanin = cursor::fanin::Stage::default();
+
+            let ports = branches
+                .iter_mut()
+                .map(|x| x.sink.borrow_cursor())
+                .collect();
+            let tracker = fanin.connect_sinks(ports, Some(&mut route.signals), 100);
+
+            for (idx, (output, branch)) in route
+                .outputs
+                .iter_mut()
+                .zip(branches.iter_mut())
+                .enumerate()
+            {
+                let mut prev = (output, false);
+
+                for filter in branch.filters.iter_mut() {
+                    let tracked = !filter.holds_events();
+                    tracker.connect(idx, prev, (filter.borrow_input(), tracked), 100);
+                    prev = (filter.borrow_output(), tracked);
+                }
+
+                tracker.connect(idx, prev, (branch.sink.borrow_input(), false), 100);
+            }
+
+            fanin.output.connect(track.clone());
+
+            tethers.push(gasket::runtime::spawn_stage(route, policy.clone()));
+
+            for branch in branches {
+                info!(branch = branch.name, "spawning route branch");
+                tethers.extend(branch.filters.into_iter().map(|x| x.spawn(policy.clone())));
+                tethers.push(branch.sink.spawn(policy.clone()));
+            }
+
+            tethers.push(gasket::runtime::spawn_stage(fanin, policy.clone()));
+        }
+    }
+
+    tethers.extend(filters.into_iter().map(|x| x.spawn(policy.clone())));
+
+    Ok(tethers)
+}
+
+pub fn connect_stages(
+    mut source: sources::Bootstrapper,
+    filters: Vec<filters::Bootstrapper>,
+    outlet: Outlet,
+    mut cursor: cursor::Bootstrapper,
+    policy: Policy,
+) -> Result<Daemon, Error> {
+    let track = ChannelSendAdapter::Mpsc(connect_cursor(&mut cursor));
+
+    let mut tethers = connect_downstream(
+        source.borrow_output(),
+        filters,
+        outlet,
+        &track,
+        policy.clone(),
+    )?;
+
+    tethers.insert(0, source.spawn(policy.clone()));
+    tethers.push(cursor.spawn(policy));
+
+    let runtime = Daemon(tethers);
+
+    Ok(runtime)
+}
+
+/// Definition of a pipeline, as found in the daemon config file
+#[derive(Deserialize)]
+pub struct Config {
+    pub source: sources::Config,
+    pub filters: Option<Vec<filters::Config>>,
+    pub sink: Option<sinks::Config>,
+    pub sinks: Option<Vec<sinks::Config>>,
+    pub route: Option<route::Config>,
+    pub intersect: IntersectConfig,
+    pub finalize: Option<FinalizeConfig>,
+    pub chain: Option<ChainConfig>,
+    pub retries: Option<gasket::retries::Policy>,
+    pub cursor: Option<cursor::Config>,
+    pub dead_letter: Option<dead_letter::Config>,
+}
+
+/// The bootstrapped stages of a pipeline, not yet connected
+struct Stages {
+    source: sources::Bootstrapper,
+    filters: Vec<filters::Bootstrapper>,
+    outlet: Outlet,
+    cursor: cursor::Bootstrapper,
+    dead_letter: Option<dead_letter::Stages>,
+    dead_letters: DeadLetters,
+    pause: PauseSwitch,
+    tip: ChainTip,
+    policy: Policy,
+}
+
+/// A piece of the pipeline, either still as config or already built
+enum Def<C, B> {
+    Config(C),
+    Built(B),
+}
+
+/// Bootstraps the filters and the outlet, the stages that get replaced when a
+/// pipeline is reloaded
+fn bootstrap_downstream(
+    filters: Vec<Def<filters::Config, filters::Bootstrapper>>,
+    route: Option<route::Config>,
+    sinks: Vec<Def<sinks::Config, sinks::Bootstrapper>>,
+    ctx: &Context,
+) -> Result<(Vec<filters::Bootstrapper>, Outlet), Error> {
+    let mut parsed = false;
+
+    for filter in &filters {
+        parsed = match filter {
+            Def::Config(x) => filters::check_parsed(std::slice::from_ref(x), parsed)?,
+            Def::Built(x) => parsed || matches!(x, filters::Bootstrapper::ParseCbor(_)),
+        };
+    }
+
+    if let Some(route) = &route {
+        route.check_parsed(parsed)?;
+    }
+
+    let filters = filters
+        .into_iter()
+        .map(|x| match x {
+            Def::Config(x) => x.bootstrapper(ctx),
+            Def::Built(x) => Ok(x.with_dead_letters(&ctx.dead_letters)),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let outlet = match route {
+        Some(_) if !sinks.is_empty() => {
+            return Err(Error::config(
+                "a route can't be combined with top-level sinks",
+            ));
+        }
+        Some(route) => {
+            let (stage, branches) = route.bootstrapper(ctx)?;
+            Outlet::Route(stage, branches)
+        }
+        None => {
+            let sinks = sinks
+                .into_iter()
+                .map(|x| match x {
+                    Def::Config(x) => x.bootstrapper(ctx),
+                    Def::Built(x) => Ok(x.with_dead_letters(&ctx.dead_letters)),
+                })
+                .collect::<Result<_, _>>()?;
+
+            Outlet::Sinks(sinks)
+        }
+    };
+
+    Ok((filters, outlet))
+}
+
+/// Connects the stages of a pipeline and spawns them
+pub struct Builder {
+    chain: ChainConfig,
+    intersect: IntersectConfig,
+    finalize: Option<FinalizeConfig>,
+    current_dir: Option<PathBuf>,
+    breadcrumbs: Option<Breadcrumbs>,
+    source_breadcrumbs: Option<Breadcrumbs>,
+    /// Blocks of the source, shared by the pipelines that share the source
+    tip: ChainTip,
+    retries: Option<gasket::retries::Policy>,
+    source: Option<Def<sources::Config, sources::Bootstrapper>>,
+    filters: Vec<Def<filters::Config, filters::Bootstrapper>>,
+    sinks: Vec<Def<sinks::Config, sinks::Bootstrapper>>,
+    route: Option<route::Config>,
+    cursor: Option<Def<cursor::Config, cursor::Bootstrapper>>,
+    dead_letter: Option<dead_letter::Config>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            chain: Default::default(),
+            intersect: IntersectConfig::Tip,
+            finalize: None,
+            current_dir: None,
+            breadcrumbs: None,
+            source_breadcrumbs: None,
+            tip: Default::default(),
+            retries: None,
+            source: None,
+            filters: vec![],
+            sinks: vec![],
+            route: None,
+            cursor: None,
+            dead_letter: None,
+        }
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(config: Config) -> Self {
+        let mut builder = Self::new()
+            .chain(config.chain.unwrap_or_default())
+            .intersect(config.intersect)
+            .source(config.source);
+
+        builder.finalize = config.finalize;
+        builder.retries = config.retries;
+        builder.route = config.route;
+        builder.cursor = config.cursor.map(Def::Config);
+        builder.dead_letter = config.dead_letter;
+
+        for filter in config.filters.into_iter().flatten() {
+            builder = builder.filter(filter);
+        }
+
+        for sink in config
+            .sink
+            .into_iter()
+            .chain(config.sinks.into_iter().flatten())
+        {
+            builder = builder.sink(sink);
+        }
+
+        builder
+    }
+
+    pub fn chain(mut self, chain: ChainConfig) -> Self {
+        self.chain = chain;
+        self
+    }
+
+    pub fn intersect(mut self, intersect: IntersectConfig) -> Self {
+        self.intersect = intersect;
+        self
+    }
+
+    pub fn finalize(mut self, finalize: FinalizeConfig) -> Self {
+        self.finalize = Some(finalize);
+        self
+    }
+
+    /// Directory used to resolve relative paths, defaults to the working dir
+    pub fn current_dir(mut self, dir: PathBuf) -> Self {
+        self.current_dir = Some(dir);
+        self
+    }
+
+    /// Points to resume from, instead of the ones loaded by the cursor config
+    pub fn breadcrumbs(mut self, breadcrumbs: Breadcrumbs) -> Self {
+        self.breadcrumbs = Some(breadcrumbs);
+        self
+    }
+
+    pub fn retries(mut self, retries: gasket::retries::Policy) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    pub fn source(mut self, config: sources::Config) -> Self {
+        self.source = Some(Def::Config(config));
+        self
+    }
+
+    pub fn source_stage(mut self, stage: sources::Bootstrapper) -> Self {
+        self.source = Some(Def::Built(stage));
+        self
+    }
+
+    /// Appends a filter at the end of the filter chain
+    pub fn filter(mut self, config: filters::Config) -> Self {
+        self.filters.push(Def::Config(config));
+        self
+    }
+
+    pub fn filter_stage(mut self, stage: filters::Bootstrapper) -> Self {
+        self.filters.push(Def::Built(stage));
+        self
+    }
+
+    pub fn custom_filter(self, filter: impl filters::custom::Filter + 'static) -> Self {
+        let stage = filters::custom::Stage::new(filter);
+        self.filter_stage(filters::Bootstrapper::Custom(stage))
+    }
+
+    /// Adds a sink, every sink receives a copy of each event
+    pub fn sink(mut self, config: sinks::Config) -> Self {
+        self.sinks.push(Def::Config(config));
+        self
+    }
+
+    pub fn sink_stage(mut self, stage: sinks::Bootstrapper) -> Self {
+        self.sinks.push(Def::Built(stage));
+        self
+    }
+
+    pub fn custom_sink(self, sink: impl sinks::custom::Sink + 'static) -> Self {
+        let stage = sinks::custom::Stage::new(sink);
+        self.sink_stage(sinks::Bootstrapper::Custom(stage))
+    }
+
+    /// Routes events to branches instead of sending them to every sink
+    pub fn route(mut self, config: route::Config) -> Self {
+        self.route = Some(config);
+        self
+    }
+
+    pub fn cursor(mut self, config: cursor::Config) -> Self {
+        self.cursor = Some(Def::Config(config));
+        self
+    }
+
+    pub fn cursor_stage(mut self, stage: cursor::Bootstrapper) -> Self {
+        self.cursor = Some(Def::Built(stage));
+        self
+    }
+
+    /// Points the pipeline resumes from, before bootstrapping any stage
+    fn initial_breadcrumbs(&self, dry_run: bool) -> Result<Breadcrumbs, Error> {
+        match (&self.breadcrumbs, &self.cursor) {
+            (Some(x), _) => Ok(x.clone()),
+            (None, _) if dry_run => cursor::memory::Config.initial_load(),
+            (None, Some(Def::Config(x))) => x.initial_load(),
+            (None, Some(Def::Built(_))) => cursor::memory::Config.initial_load(),
+            (None, None) => cursor::Config::default().initial_load(),
+        }
+    }
+
+    /// Sends the events that a stage can't process to a dedicated sink
+    pub fn dead_letter(mut self, config: dead_letter::Config) -> Self {
+        self.dead_letter = Some(config);
+        self
+    }
+
+    fn bootstrap(self) -> Result<Stages, Error> {
+        self.bootstrap_with(false)
+    }
+
+    /// Bootstraps every stage, without touching external resources when
+    /// `dry_run` is set
+    fn bootstrap_with(self, dry_run: bool) -> Result<Stages, Error> {
+        let breadcrumbs = self.initial_breadcrumbs(dry_run)?;
+
+        let current_dir = match self.current_dir {
+            Some(x) => x,
+            None => std::env::current_dir().map_err(Error::custom)?,
+        };
+
+        let mut ctx = Context {
+            chain: self.chain,
+            intersect: self.intersect,
+            finalize: self.finalize,
+            current_dir,
+            breadcrumbs,
+            dead_letters: Default::default(),
+            pause: Default::default(),
+            tip: self.tip,
+            dry_run,
+        };
+
+        let policy = define_gasket_policy(self.retries.as_ref());
+
+        // the rest of the stages need the handle to reach the dead-letter sink
+        let dead_letter = match self.dead_letter {
+            Some(config) => {
+                let max_retries = policy.work_retry.max_retries;
+                let (stages, handle) = config.bootstrapper(&ctx, max_retries)?;
+                ctx.dead_letters = handle;
+                Some(stages)
+            }
+            None => None,
+        };
+
+        let (filters, outlet) = bootstrap_downstream(self.filters, self.route, self.sinks, &ctx)?;
+
+        let cursor = match self.cursor {
+            Some(Def::Config(x)) => x.bootstrapper(&ctx)?,
+            Some(Def::Built(x)) => x,
+            None => cursor::Config::default().bootstrapper(&ctx)?,
+        };
+
+        // a shared source might need to resume from an older point than the
+        // one tracked by this pipeline
+        if let Some(x) = self.source_breadcrumbs {
+            ctx.breadcrumbs = x;
+        }
+
+        let source = match self.source {
+            Some(Def::Config(x)) => x.bootstrapper(&ctx)?,
+            Some(Def::Built(x)) => x,
+            None => return Err(Error::config("a source is required")),
+        };
+
+        Ok(Stages {
+            source,
+            filters,
+            outlet,
+            cursor,
+            dead_letter,
+            dead_letters: ctx.dead_letters,
+            pause: ctx.pause,
+            tip: ctx.tip,
+            policy,
+        })
+    }
+
+    /// Bootstraps only the filters and the outlet, to take the place of the
+    /// ones of a pipeline whose source and cursor are already running
+    fn bootstrap_downstream(
+        self,
+        breadcrumbs: Breadcrumbs,
+        dead_letters: DeadLetters,
+        tip: ChainTip,
+    ) -> Result<(Vec<filters::Bootstrapper>, Outlet, Policy), Error> {
+        let current_dir = match self.current_dir {
+            Some(x) => x,
+            None => std::env::current_dir().map_err(Error::custom)?,
+        };
+
+        let ctx = Context {
+            chain: self.chain,
+            intersect: self.intersect,
+            finalize: self.finalize,
+            current_dir,
+            breadcrumbs,
+            dead_letters,
+            pause: Default::default(),
+            tip,
+            dry_run: false,
+        };
+
+        let policy = define_gasket_policy(self.retries.as_ref());
+        let (filters, outlet) = bootstrap_downstream(self.filters, self.route, self.sinks, &ctx)?;
+
+        Ok((filters, outlet, policy))
+    }
+
+    /// Bootstraps every stage without connecting or spawning them
+    ///
+    /// Useful to check that a pipeline definition is valid before running it.
+    /// Nothing is created or contacted: the cursor isn't loaded, and stores
+    /// and plugins are left closed.
+    pub fn validate(self) -> Result<(), Error> {
+        let stages = self.bootstrap_with(true)?;
+
+        if matches!(&stages.outlet, Outlet::Sinks(x) if x.is_empty()) {
+            return Err(Error::config("at least one sink is required"));
+        }
+
+        Ok(())
+    }
+
+    /// Bootstraps, connects and spawns every stage, returning the raw daemon
+    pub fn connect(self) -> Result<Daemon, Error> {
+        let stages = self.bootstrap()?;
+
+        connect_stages(
+            stages.source,
+            stages.filters,
+            stages.outlet,
+            stages.cursor,
+            stages.policy,
+        )
+    }
+
+    /// Bootstraps, connects and spawns every stage of the pipeline
+    pub fn build(self) -> Result<Pipeline, Error> {
+        if self.route.is_none() && self.sinks.is_empty() {
+            return Err(Error::config("at least one sink is required"));
+        }
+
+        multi::Builder::new().pipeline(DEFAULT_NAME, self).build()
+    }
+}
+
+fn has_ended(state: &TetherState) -> bool {
+    match state {
+        TetherState::Dropped => true,
+        TetherState::Blocked(phase) => *phase == StagePhase::Ended,
+        TetherState::Alive(phase) => *phase == StagePhase::Ended,
+    }
+}
+
+/// State shared with the running stages of a named pipeline
+#[derive(Debug)]
+struct Handle {
+    name: String,
+    /// Points tracked by the cursor
+    cursor: cursor::SharedBreadcrumbs,
+    /// Switch of the source that feeds the pipeline
+    pause: PauseSwitch,
+    /// Links needed to replace the filters and sinks, if they can be replaced
+    attachment: Option<reload::Attachment>,
+}
+
+impl Handle {
+    fn new(name: &str, stages: &Stages, pause: PauseSwitch) -> Self {
+        Self {
+            name: name.to_owned(),
+            cursor: stages.cursor.breadcrumbs(),
+            pause,
+            attachment: None,
+        }
+    }
+}
+
+/// How a running stage relates to the source of its pipeline
+#[derive(Debug)]
+enum Role {
+    Source,
+    /// Fed by the given source, flagged if the stage is replaced when the
+    /// pipeline is reloaded (filters and sinks, unlike the cursor)
+    Fed(Arc<Tether>, bool),
+    /// Ends on its own, eg: dead-letter delivery
+    Auxiliary,
+}
+
+/// A running stage, along with the pipeline that owns it
+#[derive(Debug)]
+struct Entry {
+    tether: Arc<Tether>,
+    /// Name of the pipeline, or names of every pipeline fed by a shared
+    /// source, separated by commas
+    owner: String,
+    role: Role,
+}
+
+/// Handle to the running stages of a pipeline
+///
+/// The stages might belong to more than one named pipeline when they were
+/// built through a [`multi::Builder`].
+#[derive(Debug)]
+pub struct Pipeline {
+    /// Running stages, the filters and sinks of a pipeline are replaced when
+    /// it's reloaded
+    stages: RwLock<Vec<Entry>>,
+    members: Vec<Handle>,
+}
+
+impl Pipeline {
+    /// Name and current state of each stage, the source comes first
+    pub fn state(&self) -> Vec<(String, TetherState)> {
+        self.stages
+            .read()
+            .unwrap()
+            .iter()
+            .map(|x| (x.tether.name().to_string(), x.tether.check_state()))
+            .collect()
+    }
+
+    /// Each running stage along with the name of the pipeline that owns it
+    ///
+    /// A source shared by many pipelines is labelled with all of their names,
+    /// separated by commas.
+    pub fn named_stages(&self) -> Vec<(String, Arc<Tether>)> {
+        self.stages
+            .read()
+            .unwrap()
+            .iter()
+            .map(|x| (x.owner.clone(), x.tether.clone()))
+            .collect()
+    }
+
+    /// Names of the pipelines, in the order they were built
+    pub fn pipeline_names(&self) -> impl Iterator<Item = &str> {
+        self.members.iter().map(|x| x.name.as_str())
+    }
+
+    fn member(&self, name: &str) -> Option<&Handle> {
+        self.members.iter().find(|x| x.name == name)
+    }
+
+    /// Points acknowledged so far by the sinks of the named pipeline
+    pub fn breadcrumbs(&self, name: &str) -> Option<Breadcrumbs> {
+        self.member(name).map(|x| x.cursor.lock().unwrap().clone())
+    }
+
+    /// Holds the source of the named pipeline, or of every pipeline if no name
+    /// is given, from pulling new events
+    ///
+    /// Returns false if there's no pipeline with that name. A source shared by
+    /// many pipelines is held for all of them.
+    pub fn pause(&self, name: Option<&str>) -> bool {
+        self.switches(name).map(|x| x.pause()).count() > 0
+    }
+
+    /// Lets the sources held by [`Pipeline::pause`] pull events again
+    pub fn resume(&self, name: Option<&str>) -> bool {
+        self.switches(name).map(|x| x.resume()).count() > 0
+    }
+
+    /// True if the source of the named pipeline is paused
+    pub fn is_paused(&self, name: &str) -> Option<bool> {
+        self.member(name).map(|x| x.pause.is_paused())
+    }
+
+    fn switches<'a>(&'a self, name: Option<&'a str>) -> impl Iterator<Item = &'a PauseSwitch> {
+        self.members
+            .iter()
+            .filter(move |x| match name {
+                Some(name) => x.name == name,
+                None => true,
+            })
+            .map(|x| &x.pause)
+    }
+
+    /// True once every source has connected and found the intersection
+    ///
+    /// Sources find the intersection while bootstrapping, so a source is ready
+    /// once its stage reaches the working phase.
+    pub fn is_ready(&self) -> bool {
+        self.stages
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|x| matches!(x.role, Role::Source))
+            .all(|x| {
+                matches!(
+                    x.tether.check_state(),
+                    TetherState::Alive(StagePhase::Working)
+                        | TetherState::Blocked(StagePhase::Working)
+                )
+            })
+    }
+
+    /// True once every stage has ended
+    pub fn has_finished(&self) -> bool {
+        self.stages
+            .read()
+            .unwrap()
+            .iter()
+            .all(|x| has_ended(&x.tether.check_state()))
+    }
+
+    /// True once every stage has ended, or once any stage ended while its
+    /// source was still running
+    pub fn has_stopped(&self) -> bool {
+        let stages = self.stages.read().unwrap();
+
+        let failed = stages.iter().any(|x| match &x.role {
+            Role::Fed(source, _) => {
+                has_ended(&x.tether.check_state()) && !has_ended(&source.check_state())
+            }
+            _ => false,
+        });
+
+        failed || stages.iter().all(|x| has_ended(&x.tether.check_state()))
+    }
+
+    /// Blocks until the pipeline stops
+    ///
+    /// When the source finishes on its own (eg: a finalize condition was
+    /// reached) the rest of the stages are left running until they drain all
+    /// of the in-flight events. If any other stage ends while its source is
+    /// still running, this returns right away so that the pipeline can be
+    /// torn down.
+    pub fn block(&self) {
+        loop {
+            std::thread::sleep(Duration::from_millis(1500));
+
+            if self.has_finished() {
+                info!("all stages drained");
+                break;
+            }
+
+            if self.has_stopped() {
+                break;
+            }
+        }
+    }
+
+    /// Stops the sources and waits for the rest of the stages to process
+    /// every in-flight event
+    ///
+    /// Returns false if the stages didn't drain before the timeout, in which
+    /// case they are left running.
+    pub fn drain(&self, timeout: Duration) -> bool {
+        for entry in self.stages.read().unwrap().iter() {
+            if !matches!(entry.role, Role::Source) || has_ended(&entry.tether.check_state()) {
+                continue;
+            }
+
+            if let Err(err) = entry.tether.dismiss_stage() {
+                warn!(%err, "couldn't dismiss source");
+            }
+        }
+
+        if self.wait(timeout) {
+            info!("all stages drained");
+            return true;
+        }
+
+        false
+    }
+
+    /// Waits until every stage has ended, returning false on timeout
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let deadline = std::time::Instant::now() + timeout;
+
+        while std::time::Instant::now() < deadline {
+            if self.has_finished() {
+                return true;
+            }
+
+            std::thread::sleep(Duration::from_millis(500));
+        }
+
+        false
+    }
+
+    /// Dismisses every stage of the pipeline that is still running
+    pub fn teardown(&self) {
+        for entry in self.stages.read().unwrap().iter() {
+            if has_ended(&entry.tether.check_state()) {
+                continue;
+            }
+
+            info!(stage = entry.tether.name(), "dismissing stage");
+
+            if let Err(err) = entry.tether.dismiss_stage() {
+                warn!(%err, "couldn't dismiss stage");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn builder(config: serde_json::Value) -> Builder {
+        Builder::from_config(serde_json::from_value(config).unwrap())
+    }
+
+    #[test]
+    fn validates_without_reading_the_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cursor.json");
+        std::fs::write(&path, "not a cursor").unwrap();
+
+        let config = json!({
+            "source": { "type": "N2C", "socket_path": "/nonexistent/node.socket" },
+            "intersect": { "type": "Tip" },
+            "sink": { "type": "Noop" },
+            "cursor": { "type": "File", "path": path },
+        });
+
+        builder(config.clone()).validate().unwrap();
+
+        assert!(builder(config).bootstrap().is_err());
+    }
+
+    #[test]
+    fn rejects_governance_patterns_on_parsed_records() {
+        let votes = json!({ "match": { "vote": { "vote": "no" } } });
+        let select = json!({ "type": "Select", "predicate": votes, "skip_uncertain": false });
+        let parse = json!({ "type": "ParseCbor" });
+
+        let config = |filters: serde_json::Value| {
+            json!({
+                "source": { "type": "N2C", "socket_path": "/nonexistent/node.socket" },
+                "intersect": { "type": "Tip" },
+                "filters": filters,
+                "sink": { "type": "Noop" },
+            })
+        };
+
+        builder(config(json!([select, parse]))).validate().unwrap();
+
+        let err = builder(config(json!([parse, select])))
+            .validate()
+            .unwrap_err();
+        assert!(matches!(err, Error::Config(x) if x.contains("ParseCbor")));
+
+        let routed = |filters: serde_json::Value| {
+            json!({
+                "source": { "type": "N2C", "socket_path": "/nonexistent/node.socket" },
+                "intersect": { "type": "Tip" },
+                "filters": filters,
+                "route": {
+                    "branches": [
+                        { "name": "votes", "predicate": votes, "sink": { "type": "Noop" } },
+                        { "name": "rest", "sink": { "type": "Noop" } },
+                    ],
+                },
+            })
+        };
+
+        builder(routed(json!([]))).validate().unwrap();
+        let err = builder(routed(json!([parse]))).validate().unwrap_err();
+        assert!(matches!(err, Error::Config(x) if x.contains("ParseCbor")));
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn validates_without_loading_the_cursor() {
+        // nothing listens on the port, loading the cursor would fail
+        let config = json!({
+            "source": { "type": "N2C", "socket_path": "/nonexistent/node.socket" },
+            "intersect": { "type": "Tip" },
+            "sink": { "type": "Noop" },
+            "cursor": { "type": "Redis", "url": "redis://127.0.0.1:1", "key": "oura" },
+        });
+
+        builder(config.clone()).validate().unwrap();
+
+        assert!(builder(config).bootstrap().is_err());
+    }
+
+    #[cfg(feature = "utxo-store")]
+    #[test]
+    fn validates_without_creating_the_utxo_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("utxos.redb");
+
+        let config = json!({
+            "source": { "type": "N2C", "socket_path": "/nonexistent/node.socket" },
+            "intersect": { "type": "Tip" },
+            "filters": [{ "type": "ParseCbor", "utxo_store": { "path": path } }],
+            "sink": { "type": "Noop" },
+        });
+
+        builder(config.clone()).validate().unwrap();
+        assert!(!path.exists());
+
+        builder(config).bootstrap().unwrap();
+        assert!(path.exists());
+    }
+}
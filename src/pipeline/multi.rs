@@ -0,0 +1,307 @@
+//! Many named pipelines running inside a single daemon
+//!
+//! Each pipeline keeps its own filters, outlet and cursor, but all of the
+//! stages are spawned as part of the same [`Pipeline`] handle. Pipelines that
+//! are added to the same source group share a single source stage (eg: one
+//! chain-sync connection to the node) which hands a copy of each event to
+//! every member of the group.
+//!
+//! ```no_run
+//! use oura::framework::*;
+//! use oura::pipeline::{multi, Builder};
+//!
+//! # fn run(relay: Vec<oura::sources::Config>, sinks: Vec<oura::sinks::Config>) -> Result<(), Error> {
+//! # let [relay_a, relay_b] = <[_; 2]>::try_from(relay).ok().unwrap();
+//! # let [sink_a, sink_b] = <[_; 2]>::try_from(sinks).ok().unwrap();
+//! let pipeline = multi::Builder::new()
+//!     .shared("payments", "relay", Builder::new().source(relay_a).sink(sink_a))
+//!     .shared("rewards", "relay", Builder::new().source(relay_b).sink(sink_b))
+//!     .build()?;
+//!
+//! pipeline.block();
+//! pipeline.teardown();
+//! # Ok(())
+//! # }
+//! ```
+
+use gasket::messaging::tokio::ChannelSendAdapter;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+use crate::framework::*;
+
+use super::reload::Attachment;
+use super::{connect_cursor, connect_downstream, Entry, Handle, Outlet, Pipeline, Role};
+
+struct Member {
+    name: String,
+    group: Option<String>,
+    builder: super::Builder,
+}
+
+/// Picks the breadcrumbs that resume furthest back in the chain
+///
+/// A pipeline without breadcrumbs starts from its intersect config, which
+/// can't be compared with the rest, so it takes precedence.
+fn oldest(all: Vec<Breadcrumbs>) -> Option<Breadcrumbs> {
+    all.into_iter().min_by_key(|x| {
+        x.points()
+            .first()
+            .map(|p| p.slot_or_default() as i128)
+            .unwrap_or(-1)
+    })
+}
+
+/// Connects many named pipelines into a single set of running stages
+#[derive(Default)]
+pub struct Builder {
+    members: Vec<Member>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pipeline that runs its own source
+    pub fn pipeline(mut self, name: impl Into<String>, builder: super::Builder) -> Self {
+        self.members.push(Member {
+            name: name.into(),
+            group: None,
+            builder,
+        });
+
+        self
+    }
+
+    /// Adds a pipeline that shares its source with the rest of the group
+    ///
+    /// Only the source of the first pipeline of each group is used, the
+    /// others are expected to define an equivalent one. The shared source
+    /// resumes from the oldest point tracked by any of the members, which
+    /// means that members that were further ahead will see some events again.
+    pub fn shared(
+        mut self,
+        name: impl Into<String>,
+        group: impl Into<String>,
+        builder: super::Builder,
+    ) -> Self {
+        self.members.push(Member {
+            name: name.into(),
+            group: Some(group.into()),
+            builder,
+        });
+
+        self
+    }
+
+    /// Splits the members into groups that share a source, keeping the order
+    fn groups(self) -> Result<Vec<Vec<Member>>, Error> {
+        let mut names = HashSet::new();
+        let mut groups: Vec<Vec<Member>> = vec![];
+
+        for member in self.members {
+            if !names.insert(member.name.clone()) {
+                return Err(Error::config(format!(
+                    "pipeline name `{}` is used more than once",
+                    member.name
+                )));
+            }
+
+            let existing = groups.iter_mut().find(|x| {
+                member.group.is_some() && x.first().is_some_and(|x| x.group == member.group)
+            });
+
+            match existing {
+                Some(group) => group.push(member),
+                None => groups.push(vec![member]),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Bootstraps, connects and spawns every stage of every pipeline
+    pub fn build(self) -> Result<Pipeline, Error> {
+        let mut groups = vec![];
+
+        for mut group in self.groups()? {
+            if group.len() > 1 {
+                let all = group
+                    .iter()
+                    .map(|x| x.builder.initial_breadcrumbs(false))
+                    .collect::<Result<_, _>>()?;
+
+                group[0].builder.source_breadcrumbs = oldest(all);
+
+                // every member learns about the blocks of the shared source
+                let tip = ChainTip::default();
+
+                for member in group.iter_mut() {
+                    member.builder.tip = tip.clone();
+                }
+            }
+
+            let stages = group
+                .into_iter()
+                .map(|x| Ok((x.name, x.builder.bootstrap()?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            groups.push(stages);
+        }
+
+        if groups.is_empty() {
+            return Err(Error::config("at least one pipeline is required"));
+        }
+
+        // check before spawning anything, so that a bad pipeline doesn't leave
+        // the ones before it running
+        for (name, stages) in groups.iter().flatten() {
+            if matches!(&stages.outlet, Outlet::Sinks(x) if x.is_empty()) {
+                return Err(Error::config(format!(
+                    "pipeline `{name}` requires at least one sink"
+                )));
+            }
+        }
+
+        let mut entries = vec![];
+        let mut handles = vec![];
+
+        for group in groups {
+            let label = group
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            let mut source = None;
+            let mut pause = None;
+            let mut ports = vec![];
+            let mut downstream = vec![];
+            let mut auxiliary = vec![];
+
+            // the source of the first member feeds the whole group, the rest
+            // are never spawned
+            for (name, stages) in group {
+                // pausing any member holds the shared source
+                let pause = pause.get_or_insert_with(|| stages.pause.clone()).clone();
+                let mut handle = Handle::new(&name, &stages, pause);
+
+                if let Some(x) = stages.dead_letter {
+                    auxiliary.push((name.clone(), x.spawn(stages.policy.clone())));
+                }
+
+                let mut cursor = stages.cursor;
+                let track = connect_cursor(&mut cursor);
+
+                let mut inlet = FilterOutputPort::default();
+
+                let tethers = connect_downstream(
+                    &mut inlet,
+                    stages.filters,
+                    stages.outlet,
+                    &ChannelSendAdapter::Mpsc(track.clone()),
+                    stages.policy.clone(),
+                )?;
+
+                let mut port = FilterOutputPort::default();
+                let attachment =
+                    Attachment::splice(&mut port, inlet, &track, stages.dead_letters, stages.tip);
+                handle.attachment = Some(attachment);
+
+                source.get_or_insert((stages.source, stages.policy.clone()));
+                ports.push(port);
+                downstream.push((name, tethers, cursor.spawn(stages.policy)));
+                handles.push(handle);
+            }
+
+            let (mut source, policy) = source.expect("groups are never empty");
+
+            fanout::connect_fanout_ports(source.borrow_output(), ports);
+
+            info!(pipelines = label, "spawning source");
+
+            let source = Arc::new(source.spawn(policy));
+
+            entries.push(Entry {
+                tether: source.clone(),
+                owner: label,
+                role: Role::Source,
+            });
+
+            for (name, tethers, cursor) in downstream {
+                for tether in tethers {
+                    entries.push(Entry {
+                        tether: Arc::new(tether),
+                        owner: name.clone(),
+                        role: Role::Fed(source.clone(), true),
+                    });
+                }
+
+                entries.push(Entry {
+                    tether: Arc::new(cursor),
+                    owner: name,
+                    role: Role::Fed(source.clone(), false),
+                });
+            }
+
+            for (name, tethers) in auxiliary {
+                for tether in tethers {
+                    entries.push(Entry {
+                        tether: Arc::new(tether),
+                        owner: name.clone(),
+                        role: Role::Auxiliary,
+                    });
+                }
+            }
+        }
+
+        Ok(Pipeline {
+            stages: RwLock::new(entries),
+            members: handles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::network::miniprotocols::Point;
+
+    use super::*;
+
+    #[test]
+    fn oldest_prefers_missing_breadcrumbs() {
+        let at = |slot| Breadcrumbs::from_points(vec![Point::Specific(slot, vec![])], 10);
+
+        let picked = oldest(vec![at(30), at(10), at(20)]).unwrap();
+        assert_eq!(picked.points()[0].slot_or_default(), 10);
+
+        let picked = oldest(vec![at(30), Breadcrumbs::new(10), at(20)]).unwrap();
+        assert!(picked.is_empty());
+    }
+
+    #[test]
+    fn groups_keep_order_and_reject_duplicates() {
+        let groups = Builder::new()
+            .shared("a", "relay", Default::default())
+            .pipeline("b", Default::default())
+            .shared("c", "relay", Default::default())
+            .groups()
+            .unwrap();
+
+        let names: Vec<Vec<_>> = groups
+            .iter()
+            .map(|x| x.iter().map(|m| m.name.as_str()).collect())
+            .collect();
+
+        assert_eq!(names, vec![vec!["a", "c"], vec!["b"]]);
+
+        let result = Builder::new()
+            .pipeline("a", Default::default())
+            .pipeline("a", Default::default())
+            .groups();
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Replacement of the filters and sinks of a running pipeline
+//!
+//! The source of a pipeline doesn't feed its filters directly: its output goes
+//! through a splice, which can be pointed at a new set of stages while the
+//! source keeps running. The cursor is fed through a channel that outlives the
+//! sinks, so the new sinks report to the same cursor stage as the old ones.
+//!
+//! A reload happens in two steps. [`Pipeline::detach`] holds the source and
+//! waits for the current filters and sinks to process every in-flight event.
+//! [`Detached::attach`] bootstraps the stages of a new definition and lets the
+//! source go again. If the new stages can't be bootstrapped, the detached
+//! pipeline is handed back so that another definition can be attached.
+
+use gasket::messaging::tokio::ChannelSendAdapter;
+use gasket::messaging::{Message, SendAdapter};
+use gasket::runtime::Tether;
+use pallas::network::miniprotocols::Point;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{Sender, WeakSender};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tracing::{info, warn};
+
+use crate::framework::dead_letter::DeadLetters;
+use crate::framework::*;
+
+use super::{connect_downstream, has_ended, Builder, Entry, Pipeline, Role};
+
+/// Send adapter that forwards each event to a port that can be replaced
+///
+/// The port stays locked while an event is being sent, so a reload waits for
+/// the source to hand over its current event before replacing it.
+struct SpliceSendAdapter(Arc<Mutex<FilterOutputPort>>);
+
+#[async_trait::async_trait]
+impl SendAdapter<ChainEvent> for SpliceSendAdapter {
+    async fn send(&mut self, msg: Message<ChainEvent>) -> Result<(), gasket::error::Error> {
+        self.0.lock().await.send(msg).await
+    }
+}
+
+/// The parts of a running pipeline needed to replace its filters and sinks
+pub(crate) struct Attachment {
+    /// Port fed by the source, only alive while the source is
+    splice: Weak<Mutex<FilterOutputPort>>,
+    /// Channel that feeds the cursor, only alive while any sink is
+    track: WeakSender<Message<Point>>,
+    dead_letters: DeadLetters,
+    tip: ChainTip,
+}
+
+impl std::fmt::Debug for Attachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Attachment").finish_non_exhaustive()
+    }
+}
+
+impl Attachment {
+    /// Connects the output to the inlet of the filters and sinks through a
+    /// splice
+    pub(crate) fn splice(
+        output: &mut FilterOutputPort,
+        inlet: FilterOutputPort,
+        track: &Sender<Message<Point>>,
+        dead_letters: DeadLetters,
+        tip: ChainTip,
+    ) -> Self {
+        let inlet = Arc::new(Mutex::new(inlet));
+        output.connect(SpliceSendAdapter(inlet.clone()));
+
+        Self {
+            splice: Arc::downgrade(&inlet),
+            track: track.downgrade(),
+            dead_letters,
+            tip,
+        }
+    }
+}
+
+/// A pipeline whose filters and sinks were drained and removed
+///
+/// The source is held until new stages are attached. Dropping this without
+/// attaching any stages leaves the source without a downstream, which makes
+/// it fail on the next event.
+pub struct Detached<'a> {
+    pipeline: &'a Pipeline,
+    name: String,
+    port: OwnedMutexGuard<FilterOutputPort>,
+    track: Sender<Message<Point>>,
+    source: Arc<Tether>,
+}
+
+impl Pipeline {
+    /// Holds the source of the named pipeline and drains its filters and sinks
+    ///
+    /// The source and the cursor keep running. Fails without changing
+    /// anything if the source doesn't hand over its current event before the
+    /// timeout. If the filters and sinks don't drain in time, they are
+    /// dismissed along with the events they didn't process.
+    pub fn detach(&self, name: &str, timeout: Duration) -> Result<Detached<'_>, Error> {
+        let member = self
+            .member(name)
+            .ok_or_else(|| Error::config(format!("there's no pipeline named `{name}`")))?;
+
+        let attachment = member
+            .attachment
+            .as_ref()
+            .ok_or_else(|| Error::config(format!("pipeline `{name}` can't be reloaded")))?;
+
+        let stopped = || Error::custom(format!("pipeline `{name}` is no longer running"));
+
+        let splice = attachment.splice.upgrade().ok_or_else(stopped)?;
+        let track = attachment.track.upgrade().ok_or_else(stopped)?;
+
+        let deadline = Instant::now() + timeout;
+
+        // the lock is fair, so the request is queued behind the event that the
+        // source is sending instead of racing the source for the next one
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || sender.send(splice.blocking_lock_owned()));
+
+        let mut port = receiver.recv_timeout(timeout).map_err(|_| {
+            Error::custom(format!(
+                "pipeline `{name}` didn't hand over its current event in time"
+            ))
+        })?;
+
+        let mut stages = self.stages.write().unwrap();
+
+        // the cursor is fed by the same source, and it's never replaced
+        let source = stages
+            .iter()
+            .find_map(|x| match &x.role {
+                Role::Fed(source, _) if x.owner == name => Some(source.clone()),
+                _ => None,
+            })
+            .ok_or_else(stopped)?;
+
+        let (downstream, rest): (Vec<_>, _) = std::mem::take(&mut *stages)
+            .into_iter()
+            .partition(|x| x.owner == name && matches!(x.role, Role::Fed(_, true)));
+
+        *stages = rest;
+        drop(stages);
+
+        // the filters and sinks end on their own once their input is closed
+        *port = FilterOutputPort::default();
+
+        info!(pipeline = name, "draining filters and sinks");
+
+        let drained = loop {
+            if downstream
+                .iter()
+                .all(|x| has_ended(&x.tether.check_state()))
+            {
+                break true;
+            }
+
+            if Instant::now() > deadline {
+                break false;
+            }
+
+            std::thread::sleep(Duration::from_millis(100));
+        };
+
+        if !drained {
+            warn!(
+                pipeline = name,
+                "filters and sinks didn't drain in time, some events will be skipped"
+            );
+
+            for entry in downstream.iter() {
+                if let Err(err) = entry.tether.dismiss_stage() {
+                    warn!(%err, "couldn't dismiss stage");
+                }
+            }
+        }
+
+        Ok(Detached {
+            pipeline: self,
+            name: name.to_owned(),
+            port,
+            track,
+            source,
+        })
+    }
+}
+
+impl Detached<'_> {
+    /// Bootstraps the filters and sinks of the given builder and connects
+    /// them to the running source and cursor
+    ///
+    /// Only the filters, sinks, route and retries of the builder are used, the
+    /// rest of the pipeline keeps running as it is. On error, the pipeline is
+    /// handed back still detached.
+    pub fn attach(mut self, builder: Builder) -> Result<(), (Error, Self)> {
+        let member = self
+            .pipeline
+            .member(&self.name)
+            .and_then(|x| Some((x, x.attachment.as_ref()?)));
+
+        let Some((member, attachment)) = member else {
+            let err = Error::custom(format!("pipeline `{}` is gone", self.name));
+            return Err((err, self));
+        };
+
+        let breadcrumbs = member.cursor.lock().unwrap().clone();
+        let dead_letters = attachment.dead_letters.clone();
+        let tip = attachment.tip.clone();
+
+        let (filters, outlet, policy) =
+            match builder.bootstrap_downstream(breadcrumbs, dead_letters, tip) {
+                Ok(x) => x,
+                Err(err) => return Err((err, self)),
+            };
+
+        let mut port = FilterOutputPort::default();
+        let track = ChannelSendAdapter::Mpsc(self.track.clone());
+
+        let tethers = match connect_downstream(&mut port, filters, outlet, &track, policy) {
+            Ok(x) => x,
+            Err(err) => return Err((err, self)),
+        };
+
+        let mut stages = self.pipeline.stages.write().unwrap();
+
+        stages.extend(tethers.into_iter().map(|x| Entry {
+            tether: Arc::new(x),
+            owner: self.name.clone(),
+            role: Role::Fed(self.source.clone(), true),
+        }));
+
+        *self.port = port;
+
+        info!(pipeline = self.name, "filters and sinks attached");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+//! Consumption of a pipeline as an async stream of events
+//!
+//! Instead of ending in sinks, the output of the source and filters is
+//! exposed as a [`futures::Stream`] of [`ChainEvent`]. The consumer reports
+//! the points it has processed through an [`Acker`], which feeds the cursor
+//! stage the same way a sink would.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use oura::framework::*;
+//! use oura::pipeline::Builder;
+//!
+//! # async fn run(source: oura::sources::Config) -> Result<(), Error> {
+//! let (mut events, mut acker) = Builder::new()
+//!     .intersect(IntersectConfig::Tip)
+//!     .source(source)
+//!     .stream()?;
+//!
+//! while let Some(event) = events.next().await {
+//!     println!("{:?}", event.point());
+//!     acker.ack(event.point().clone()).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use futures::stream::{BoxStream, Stream, StreamExt};
+use gasket::messaging::tokio::{mpsc_channel, ChannelSendAdapter};
+use gasket::messaging::{RecvAdapter, SendAdapter};
+use gasket::runtime::Tether;
+use pallas::network::miniprotocols::Point;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+
+use crate::framework::*;
+
+use super::{connect_filters, Builder, Entry, Handle, Pipeline, Role, DEFAULT_NAME};
+
+/// Stream of the events that come out of the source and filters
+///
+/// The stream ends once the source and filters are done (eg: a finalize
+/// condition was reached) and every in-flight event was consumed.
+pub struct EventStream {
+    inner: BoxStream<'static, ChainEvent>,
+    pipeline: Pipeline,
+}
+
+impl EventStream {
+    /// Handle to the running stages behind the stream
+    pub fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+}
+
+impl Stream for EventStream {
+    type Item = ChainEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// Reports processed points to the cursor of the pipeline
+///
+/// Once every acker is dropped, the cursor stage persists its state one last
+/// time and ends.
+#[derive(Clone)]
+pub struct Acker(ChannelSendAdapter<Point>);
+
+impl Acker {
+    pub async fn ack(&mut self, point: Point) -> Result<(), Error> {
+        self.0.send(point.into()).await.map_err(Error::custom)
+    }
+}
+
+/// Wraps the stages behind a stream
+///
+/// The stages can't be reloaded, the consumer of the stream takes the place of
+/// the sinks.
+fn wrap(source: Tether, fed: Vec<Tether>, auxiliary: Vec<Tether>, member: Handle) -> Pipeline {
+    let source = Arc::new(source);
+
+    let fed = fed
+        .into_iter()
+        .map(|x| (Arc::new(x), Role::Fed(source.clone(), false)));
+
+    let auxiliary = auxiliary
+        .into_iter()
+        .map(|x| (Arc::new(x), Role::Auxiliary));
+
+    let entries = std::iter::once((source.clone(), Role::Source))
+        .chain(fed)
+        .chain(auxiliary)
+        .map(|(tether, role)| Entry {
+            tether,
+            owner: DEFAULT_NAME.to_owned(),
+            role,
+        })
+        .collect();
+
+    Pipeline {
+        stages: RwLock::new(entries),
+        members: vec![member],
+    }
+}
+
+impl Builder {
+    /// Runs the source and filters, exposing their output as a stream
+    ///
+    /// Sinks and routes can't be used in this mode, the consumer of the
+    /// stream takes their place.
+    pub fn stream(self) -> Result<(EventStream, Acker), Error> {
+        if !self.sinks.is_empty() || self.route.is_some() {
+            return Err(Error::config(
+                "sinks and routes can't be combined with a stream",
+            ));
+        }
+
+        let mut stages = self.bootstrap()?;
+
+        let output = connect_filters(stages.source.borrow_output(), &mut stages.filters);
+        let (sender, receiver) = mpsc_channel(100);
+        output.connect(sender);
+
+        let (acks, track) = mpsc_channel(100);
+        stages.cursor.borrow_track().connect(track);
+
+        let member = Handle::new(DEFAULT_NAME, &stages, stages.pause.clone());
+
+        let inner = futures::stream::unfold(receiver, |mut receiver| async move {
+            let msg = receiver.recv().await.ok()?;
+            Some((msg.payload, receiver))
+        })
+        .boxed();
+
+        let policy = stages.policy;
+
+        let auxiliary = match stages.dead_letter {
+            Some(x) => x.spawn(policy.clone()),
+            None => vec![],
+        };
+
+        let source = stages.source.spawn(policy.clone());
+
+        let mut fed: Vec<_> = stages
+            .filters
+            .into_iter()
+            .map(|x| x.spawn(policy.clone()))
+            .collect();
+
+        fed.push(stages.cursor.spawn(policy));
+
+        let pipeline = wrap(source, fed, auxiliary, member);
+
+        let stream = EventStream { inner, pipeline };
+
+        Ok((stream, Acker(acks)))
+    }
+}
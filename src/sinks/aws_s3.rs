@@ -5,6 +5,7 @@ use gasket::framework::*;
 use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -57,7 +58,8 @@ impl gasket::framework::Worker<Stage> for Worker {
         }
         .or_panic()?;
 
-        self.client
+        let result = self
+            .client
             .put_object()
             .bucket(&stage.config.bucket)
             .key(key)
@@ -65,10 +67,13 @@ impl gasket::framework::Worker<Stage> for Worker {
             .metadata("slot", point.slot_or_default().to_string())
             .content_type("application/cbor")
             .send()
-            .await
-            .or_retry()?;
+            .await;
+
+        stage
+            .dead_letters
+            .deliver("sink-aws-s3", unit, result, &stage.ops_count)
+            .await?;
 
-        stage.ops_count.inc(1);
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
 
@@ -79,6 +84,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-aws-s3", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
 
     pub input: MapperInputPort,
@@ -100,8 +106,9 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             ops_count: Default::default(),
             latest_block: Default::default(),
@@ -4,6 +4,7 @@ use aws_types::region::Region;
 use gasket::framework::*;
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -65,9 +66,13 @@ impl gasket::framework::Worker<Stage> for Worker {
             req = req.set_message_group_id(self.group_id.clone())
         }
 
-        req.send().await.or_retry()?;
+        let result = req.send().await;
+
+        stage
+            .dead_letters
+            .deliver("sink-aws-sqs", unit, result, &stage.ops_count)
+            .await?;
 
-        stage.ops_count.inc(1);
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
 
@@ -78,6 +83,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-aws-sqs", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
 
     pub input: MapperInputPort,
@@ -98,8 +104,9 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             ops_count: Default::default(),
             latest_block: Default::default(),
@@ -0,0 +1,89 @@
+//! A sink stage that wraps user-supplied sink logic
+//!
+//! Applications embedding oura as a library can implement the [`Sink`] trait
+//! and plug it into a pipeline through `pipeline::Builder`. This stage takes
+//! care of the gasket plumbing, including reporting processed points to the
+//! cursor.
+
+use gasket::framework::*;
+
+use crate::framework::dead_letter::DeadLetters;
+use crate::framework::*;
+
+/// Logic of a custom sink
+///
+/// Each event that reaches the stage is handed to `apply`. Once it returns
+/// successfully, the point of the event is reported to the cursor. Errors are
+/// retried according to the retry policy of the pipeline, so `apply` needs to
+/// be safe to call more than once for the same event. If the pipeline has a
+/// dead-letter sink, the event is sent there once the attempts run out.
+///
+/// Implementations need to use `#[async_trait::async_trait(?Send)]`.
+#[async_trait::async_trait(?Send)]
+pub trait Sink: Send {
+    async fn apply(&mut self, event: &ChainEvent) -> Result<(), Error>;
+}
+
+#[derive(Default)]
+pub struct Worker;
+
+#[async_trait::async_trait(?Send)]
+impl gasket::framework::Worker<Stage> for Worker {
+    async fn bootstrap(_: &Stage) -> Result<Self, WorkerError> {
+        Ok(Default::default())
+    }
+
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<ChainEvent>, WorkerError> {
+        let msg = stage.input.recv().await.or_panic()?;
+
+        Ok(WorkSchedule::Unit(msg.payload))
+    }
+
+    async fn execute(&mut self, unit: &ChainEvent, stage: &mut Stage) -> Result<(), WorkerError> {
+        let point = unit.point().clone();
+
+        let result = stage.sink.apply(unit).await;
+
+        stage
+            .dead_letters
+            .deliver("sink-custom", unit, result, &stage.ops_count)
+            .await?;
+
+        stage.latest_block.set(point.slot_or_default() as i64);
+        stage.cursor.send(point.into()).await.or_panic()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Stage)]
+#[stage(name = "sink-custom", unit = "ChainEvent", worker = "Worker")]
+pub struct Stage {
+    sink: Box<dyn Sink>,
+    pub(crate) dead_letters: DeadLetters,
+
+    pub input: SinkInputPort,
+    pub cursor: SinkCursorPort,
+
+    #[metric]
+    ops_count: gasket::metrics::Counter,
+
+    #[metric]
+    latest_block: gasket::metrics::Gauge,
+}
+
+impl Stage {
+    pub fn new(sink: impl Sink + 'static) -> Self {
+        Self {
+            sink: Box::new(sink),
+            dead_letters: Default::default(),
+            input: Default::default(),
+            cursor: Default::default(),
+            ops_count: Default::default(),
+            latest_block: Default::default(),
+        }
+    }
+}
@@ -11,6 +11,7 @@ use elasticsearch::{
 use gasket::framework::*;
 use serde::{Deserialize, Serialize};
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 #[derive(Serialize)]
@@ -77,15 +78,19 @@ impl gasket::framework::Worker<Stage> for Worker {
         let timestamp = stage.genesis.slot_to_wallclock(slot);
         let payload = ESRecord::new(record.unwrap(), timestamp);
 
-        self.client
+        let result = self
+            .client
             .index(parts)
             .body(payload)
             .op_type(OpType::Create)
             .send()
-            .await
-            .or_retry()?;
+            .await;
+
+        stage
+            .dead_letters
+            .deliver("sink-elasticsearch", unit, result, &stage.ops_count)
+            .await?;
 
-        stage.ops_count.inc(1);
         stage.latest_block.set(slot as i64);
         stage.cursor.send(point.into()).await.or_panic()?;
 
@@ -96,6 +101,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-elasticsearch", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
     genesis: GenesisValues,
 
@@ -137,6 +143,7 @@ pub struct Config {
 impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             genesis: ctx.chain.clone().into(),
             ops_count: Default::default(),
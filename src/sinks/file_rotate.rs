@@ -10,6 +10,7 @@ use gasket::framework::*;
 use serde::Deserialize;
 use serde_json::Value as JsonValue;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -65,12 +66,15 @@ impl gasket::framework::Worker<Stage> for Worker {
         let point = unit.point();
         let json = JsonValue::from(unit.clone());
 
-        self.writer
+        let result = self
+            .writer
             .write_all(json.to_string().as_bytes())
-            .and_then(|_| self.writer.write_all(b"\n"))
-            .or_retry()?;
+            .and_then(|_| self.writer.write_all(b"\n"));
 
-        stage.ops_count.inc(1);
+        stage
+            .dead_letters
+            .deliver("sink-filerotate", unit, result, &stage.ops_count)
+            .await?;
 
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
@@ -82,6 +86,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-filerotate", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
     current_dir: PathBuf,
 
@@ -116,6 +121,7 @@ const DEFAULT_MAX_TOTAL_FILES: usize = 200;
 impl Config {
     pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             current_dir: ctx.current_dir.clone(),
             ops_count: Default::default(),
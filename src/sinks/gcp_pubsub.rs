@@ -5,6 +5,7 @@ use google_cloud_pubsub::client::{Client, ClientConfig};
 
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -43,14 +44,18 @@ impl gasket::framework::Worker<Stage> for Worker {
             ..Default::default()
         };
 
-        self.client
+        let result = self
+            .client
             .topic(&stage.config.topic)
             .new_publisher(None)
             .publish_immediately(vec![message], None)
-            .await
-            .or_retry()?;
+            .await;
+
+        stage
+            .dead_letters
+            .deliver("sink-gcp-pubsub", unit, result, &stage.ops_count)
+            .await?;
 
-        stage.ops_count.inc(1);
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
 
@@ -61,6 +66,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-gcp-pubsub", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
 
     pub input: MapperInputPort,
@@ -79,8 +85,9 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             ops_count: Default::default(),
             latest_block: Default::default(),
@@ -4,6 +4,7 @@ use gasket::framework::*;
 use kafka::producer::{Producer, Record, RequiredAcks};
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -54,7 +55,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 
         let payload = serde_json::to_vec(&serde_json::Value::from(record.unwrap())).or_panic()?;
 
-        match self.partitioning {
+        let result = match self.partitioning {
             PartitionStrategy::ByBlock => {
                 let slot = point.slot_or_default().to_be_bytes();
                 let kafka_record = Record::from_key_value(&stage.config.topic, &slot[..], payload);
@@ -64,10 +65,13 @@ impl gasket::framework::Worker<Stage> for Worker {
                 let kafka_record = Record::from_value(&stage.config.topic, payload);
                 self.producer.send(&kafka_record)
             }
-        }
-        .or_retry()?;
+        };
+
+        stage
+            .dead_letters
+            .deliver("sink-kafka", unit, result, &stage.ops_count)
+            .await?;
 
-        stage.ops_count.inc(1);
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
 
@@ -78,6 +82,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-kafka", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
 
     pub input: MapperInputPort,
@@ -105,8 +110,9 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             ops_count: Default::default(),
             latest_block: Default::default(),
@@ -1,10 +1,12 @@
 use gasket::runtime::Tether;
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 mod assert;
 mod common;
+pub mod custom;
 mod file_rotate;
 mod noop;
 mod stdout;
@@ -48,6 +50,7 @@ pub enum Bootstrapper {
     Assert(assert::Stage),
     FileRotate(file_rotate::Stage),
     WebHook(webhook::Stage),
+    Custom(custom::Stage),
 
     #[cfg(feature = "rabbitmq")]
     Rabbitmq(rabbitmq::Stage),
@@ -89,6 +92,7 @@ impl Bootstrapper {
             Bootstrapper::Assert(p) => &mut p.input,
             Bootstrapper::FileRotate(p) => &mut p.input,
             Bootstrapper::WebHook(p) => &mut p.input,
+            Bootstrapper::Custom(p) => &mut p.input,
 
             #[cfg(feature = "rabbitmq")]
             Bootstrapper::Rabbitmq(p) => &mut p.input,
@@ -130,6 +134,7 @@ impl Bootstrapper {
             Bootstrapper::Assert(p) => &mut p.cursor,
             Bootstrapper::FileRotate(p) => &mut p.cursor,
             Bootstrapper::WebHook(p) => &mut p.cursor,
+            Bootstrapper::Custom(p) => &mut p.cursor,
 
             #[cfg(feature = "rabbitmq")]
             Bootstrapper::Rabbitmq(p) => &mut p.cursor,
@@ -163,6 +168,18 @@ impl Bootstrapper {
         }
     }
 
+    /// Hands the dead-letter handle to stages that were built outside of the
+    /// pipeline
+    pub(crate) fn with_dead_letters(self, handle: &DeadLetters) -> Self {
+        match self {
+            Bootstrapper::Custom(mut x) => {
+                x.dead_letters = handle.clone();
+                Bootstrapper::Custom(x)
+            }
+            x => x,
+        }
+    }
+
     pub fn spawn(self, policy: gasket::runtime::Policy) -> Tether {
         match self {
             Bootstrapper::Terminal(x) => gasket::runtime::spawn_stage(x, policy),
@@ -171,6 +188,7 @@ impl Bootstrapper {
             Bootstrapper::Assert(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::FileRotate(x) => gasket::runtime::spawn_stage(x, policy),
             Bootstrapper::WebHook(x) => gasket::runtime::spawn_stage(x, policy),
+            Bootstrapper::Custom(x) => gasket::runtime::spawn_stage(x, policy),
 
             #[cfg(feature = "rabbitmq")]
             Bootstrapper::Rabbitmq(x) => gasket::runtime::spawn_stage(x, policy),
@@ -4,6 +4,7 @@ use lapin::{
 };
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -40,7 +41,8 @@ impl gasket::framework::Worker<Stage> for Worker {
 
         let payload = serde_json::to_vec(&serde_json::Value::from(record.unwrap())).or_panic()?;
 
-        self.channel
+        let result = self
+            .channel
             .basic_publish(
                 &stage.config.exchange,
                 &stage.config.routing_key.clone().unwrap_or_default(),
@@ -48,10 +50,13 @@ impl gasket::framework::Worker<Stage> for Worker {
                 &payload,
                 BasicProperties::default(),
             )
-            .await
-            .or_retry()?;
+            .await;
+
+        stage
+            .dead_letters
+            .deliver("sink-rabbitmq", unit, result, &stage.ops_count)
+            .await?;
 
-        stage.ops_count.inc(1);
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
 
@@ -62,6 +67,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-rabbitmq", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
 
     pub input: MapperInputPort,
@@ -82,8 +88,9 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             ops_count: Default::default(),
             latest_block: Default::default(),
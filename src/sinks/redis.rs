@@ -7,6 +7,7 @@ use r2d2_redis::{
 };
 use serde::Deserialize;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -64,13 +65,16 @@ impl gasket::framework::Worker<Stage> for Worker {
             command.arg(maxlen);
         }
 
-        command
+        let result = command
             .arg("*")
             .arg(&[point.slot_or_default().to_string(), payload])
-            .query(conn.deref_mut())
-            .or_retry()?;
+            .query::<()>(conn.deref_mut());
+
+        stage
+            .dead_letters
+            .deliver("sink-redis", unit, result, &stage.ops_count)
+            .await?;
 
-        stage.ops_count.inc(1);
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
 
@@ -81,6 +85,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-redis", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
 
     pub input: MapperInputPort,
@@ -106,8 +111,9 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             ops_count: Default::default(),
             latest_block: Default::default(),
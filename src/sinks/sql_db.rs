@@ -3,6 +3,7 @@ use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
 use tracing::debug;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -60,10 +61,17 @@ impl gasket::framework::Worker<Stage> for Worker {
 
         let statement = template.or_panic()?;
 
-        let result = sqlx::query(&statement).execute(&self.db).await.or_retry()?;
-        debug!(rows = result.rows_affected(), "sql statement executed");
+        let result = sqlx::query(&statement).execute(&self.db).await;
+
+        let result = stage
+            .dead_letters
+            .deliver("sql", unit, result, &stage.ops_count)
+            .await?;
+
+        if let Some(result) = result {
+            debug!(rows = result.rows_affected(), "sql statement executed");
+        }
 
-        stage.ops_count.inc(1);
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
 
@@ -74,6 +82,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sql", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
     templates: handlebars::Handlebars<'static>,
 
@@ -97,7 +106,7 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         sqlx::any::install_default_drivers();
 
         let mut templates = handlebars::Handlebars::new();
@@ -115,6 +124,7 @@ impl Config {
             .map_err(Error::config)?;
 
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             templates,
             ops_count: Default::default(),
@@ -5,6 +5,7 @@ use gasket::framework::*;
 use serde::Deserialize;
 use serde_json::Value as JsonValue;
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 pub struct Worker {
@@ -31,12 +32,15 @@ impl gasket::framework::Worker<Stage> for Worker {
         let point = unit.point();
         let json = JsonValue::from(unit.clone());
 
-        self.stdout
+        let result = self
+            .stdout
             .write_all(json.to_string().as_bytes())
-            .and_then(|_| self.stdout.write_all(b"\n"))
-            .or_retry()?;
+            .and_then(|_| self.stdout.write_all(b"\n"));
 
-        stage.ops_count.inc(1);
+        stage
+            .dead_letters
+            .deliver("sink-stdout", unit, result, &stage.ops_count)
+            .await?;
 
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
@@ -48,6 +52,8 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-stdout", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
+
     pub input: MapperInputPort,
     pub cursor: SinkCursorPort,
 
@@ -62,8 +68,9 @@ pub struct Stage {
 pub struct Config;
 
 impl Config {
-    pub fn bootstrapper(self, _: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             ops_count: Default::default(),
             latest_block: Default::default(),
             input: Default::default(),
@@ -3,6 +3,7 @@ use pallas::network::miniprotocols::Point;
 use serde::Deserialize;
 use std::{collections::HashMap, time::Duration};
 
+use crate::framework::dead_letter::DeadLetters;
 use crate::framework::*;
 
 use super::common::web::{build_headers_map, APP_USER_AGENT};
@@ -63,13 +64,16 @@ impl gasket::framework::Worker<Stage> for Worker {
             .build()
             .or_panic()?;
 
-        self.client
+        let result = self
+            .client
             .execute(request)
             .await
-            .and_then(|res| res.error_for_status())
-            .or_retry()?;
+            .and_then(|res| res.error_for_status());
 
-        stage.ops_count.inc(1);
+        stage
+            .dead_letters
+            .deliver("sink-webhook", unit, result, &stage.ops_count)
+            .await?;
 
         stage.latest_block.set(point.slot_or_default() as i64);
         stage.cursor.send(point.clone().into()).await.or_panic()?;
@@ -81,6 +85,7 @@ impl gasket::framework::Worker<Stage> for Worker {
 #[derive(Stage)]
 #[stage(name = "sink-webhook", unit = "ChainEvent", worker = "Worker")]
 pub struct Stage {
+    dead_letters: DeadLetters,
     config: Config,
 
     pub input: MapperInputPort,
@@ -109,8 +114,9 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bootstrapper(self, _ctx: &Context) -> Result<Stage, Error> {
+    pub fn bootstrapper(self, ctx: &Context) -> Result<Stage, Error> {
         let stage = Stage {
+            dead_letters: ctx.dead_letters.clone(),
             config: self,
             ops_count: Default::default(),
             latest_block: Default::default(),
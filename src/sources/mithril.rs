@@ -226,6 +226,11 @@ fn read_blocks_with_config(
 pub struct Stage {
     config: Config,
     intersect: IntersectConfig,
+    chain: GenesisValues,
+    finalize: Option<FinalizeConfig>,
+    block_count: u64,
+    pause: PauseSwitch,
+    tip: ChainTip,
     pub output: SourceOutputPort,
 }
 
@@ -295,7 +300,11 @@ impl gasket::framework::Worker<Stage> for Worker {
             .context("reading immutable db")
             .map_err(|_| WorkerError::Panic)?;
 
-        for chunk in iter.chunks(100).into_iter() {
+        'chunks: for chunk in iter.chunks(100).into_iter() {
+            // the whole snapshot is read in a single unit of work, so the
+            // switch is checked between chunks instead of on each schedule
+            while stage.pause.hold().await {}
+
             let bodies: Vec<_> = chunk
                 .try_collect()
                 .into_diagnostic()
@@ -314,8 +323,24 @@ impl gasket::framework::Worker<Stage> for Worker {
                 .collect();
 
             for (point, block) in blocks {
+                let finalize =
+                    should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count + 1);
+
+                if finalize == Finalize::BeforeBlock {
+                    info!(?point, "finalize condition reached, skipping block");
+                    break 'chunks;
+                }
+
+                stage.tip.apply(&point);
                 let event = ChainEvent::Apply(point, Record::CborBlock(block));
                 stage.output.send(event.into()).await.or_panic()?;
+
+                stage.block_count += 1;
+
+                if finalize == Finalize::AfterBlock {
+                    info!("finalize condition reached");
+                    break 'chunks;
+                }
             }
         }
 
@@ -338,6 +363,11 @@ impl Config {
         let stage = Stage {
             config: self,
             intersect: ctx.intersect.clone(),
+            chain: ctx.chain.clone().into(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            pause: ctx.pause.clone(),
+            tip: ctx.tip.clone(),
             output: Default::default(),
         };
 
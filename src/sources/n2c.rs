@@ -26,6 +26,16 @@ pub struct Stage {
 
     breadcrumbs: Breadcrumbs,
 
+    finalize: Option<FinalizeConfig>,
+
+    block_count: u64,
+
+    finalized: bool,
+
+    pause: PauseSwitch,
+
+    tip: ChainTip,
+
     pub output: SourceOutputPort,
 
     #[metric]
@@ -103,8 +113,18 @@ impl Worker {
 
                 debug!(slot, %hash, "chain sync roll forward");
 
+                let finalize =
+                    should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count + 1);
+
+                if finalize == Finalize::BeforeBlock {
+                    info!(slot, "finalize condition reached, skipping block");
+                    stage.finalized = true;
+                    return Ok(());
+                }
+
                 let evt = ChainEvent::Apply(point.clone(), Record::CborBlock(cbor.to_vec()));
 
+                stage.tip.apply(&point);
                 stage.output.send(evt.into()).await.or_panic()?;
 
                 stage.breadcrumbs.track(point.clone());
@@ -112,6 +132,12 @@ impl Worker {
                 stage.chain_tip.set(tip.0.slot_or_default() as i64);
                 stage.current_slot.set(slot as i64);
                 stage.ops_count.inc(1);
+                stage.block_count += 1;
+
+                if finalize == Finalize::AfterBlock {
+                    info!(slot, "finalize condition reached");
+                    stage.finalized = true;
+                }
 
                 Ok(())
             }
@@ -121,6 +147,8 @@ impl Worker {
                     Point::Specific(slot, _) => debug!(slot, "rollback"),
                 };
 
+                stage.tip.rollback(point);
+
                 stage
                     .output
                     .send(ChainEvent::reset(point.clone()))
@@ -138,6 +166,12 @@ impl Worker {
             }
             NextResponse::Await => {
                 info!("chain-sync reached the tip of the chain");
+
+                if stage.finalize.as_ref().is_some_and(|x| x.until_tip()) {
+                    info!("finalizing at the tip of the chain");
+                    stage.finalized = true;
+                }
+
                 Ok(())
             }
         }
@@ -166,8 +200,16 @@ impl gasket::framework::Worker<Stage> for Worker {
 
     async fn schedule(
         &mut self,
-        _stage: &mut Stage,
+        stage: &mut Stage,
     ) -> Result<WorkSchedule<NextResponse<BlockContent>>, WorkerError> {
+        if stage.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
+        if stage.pause.hold().await {
+            return Ok(WorkSchedule::Idle);
+        }
+
         let client = self.peer_session.chainsync();
 
         let next = match client.has_agency() {
@@ -205,6 +247,11 @@ impl Config {
             breadcrumbs: ctx.breadcrumbs.clone(),
             chain: ctx.chain.clone().into(),
             intersect: ctx.intersect.clone(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            finalized: false,
+            pause: ctx.pause.clone(),
+            tip: ctx.tip.clone(),
             output: Default::default(),
             ops_count: Default::default(),
             chain_tip: Default::default(),
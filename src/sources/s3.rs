@@ -2,6 +2,7 @@ use aws_config::BehaviorVersion;
 use aws_sdk_s3::Client as S3Client;
 use gasket::framework::*;
 use serde::Deserialize;
+use tracing::info;
 
 use crate::framework::*;
 
@@ -16,6 +17,18 @@ pub struct Stage {
 
     breadcrumbs: Breadcrumbs,
 
+    chain: GenesisValues,
+
+    finalize: Option<FinalizeConfig>,
+
+    block_count: u64,
+
+    finalized: bool,
+
+    pause: PauseSwitch,
+
+    tip: ChainTip,
+
     pub output: SourceOutputPort,
 
     #[metric]
@@ -58,6 +71,14 @@ impl gasket::framework::Worker<Stage> for Worker {
     }
 
     async fn schedule(&mut self, stage: &mut Stage) -> Result<WorkSchedule<KeyBatch>, WorkerError> {
+        if stage.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
+        if stage.pause.hold().await {
+            return Ok(WorkSchedule::Idle);
+        }
+
         let result = self
             .s3_client
             .list_objects_v2()
@@ -75,6 +96,13 @@ impl gasket::framework::Worker<Stage> for Worker {
             .filter_map(|obj| obj.key)
             .collect::<Vec<_>>();
 
+        // there are no more blocks in the bucket, which is the tip for us
+        if keys.is_empty() && stage.finalize.as_ref().is_some_and(|x| x.until_tip()) {
+            info!("finalizing at the tip of the bucket");
+            stage.finalized = true;
+            return Ok(WorkSchedule::Done);
+        }
+
         Ok(WorkSchedule::Unit(KeyBatch { keys }))
     }
 
@@ -107,11 +135,30 @@ impl gasket::framework::Worker<Stage> for Worker {
                 hex::decode(hash).or_panic()?,
             );
 
+            let finalize =
+                should_finalize(&stage.finalize, &stage.chain, &point, stage.block_count + 1);
+
+            if finalize == Finalize::BeforeBlock {
+                info!(%key, "finalize condition reached, skipping block");
+                stage.finalized = true;
+                break;
+            }
+
             let body = object.body.collect().await.or_retry()?;
 
+            stage.tip.apply(&point);
+
             let event = ChainEvent::Apply(point, Record::CborBlock(body.into_bytes().to_vec()));
 
             stage.output.send(event.into()).await.or_panic()?;
+
+            stage.block_count += 1;
+
+            if finalize == Finalize::AfterBlock {
+                info!(%key, "finalize condition reached");
+                stage.finalized = true;
+                break;
+            }
         }
 
         Ok(())
@@ -131,6 +178,12 @@ impl Config {
             items_per_batch: self.items_per_batch,
             breadcrumbs: ctx.breadcrumbs.clone(),
             intersect: ctx.intersect.clone(),
+            chain: ctx.chain.clone().into(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            finalized: false,
+            pause: ctx.pause.clone(),
+            tip: ctx.tip.clone(),
             output: Default::default(),
             ops_count: Default::default(),
         };
@@ -3,7 +3,7 @@ use gasket::framework::*;
 use serde::Deserialize;
 use tonic::transport::Channel;
 use tonic::Streaming;
-use tracing::{debug, error};
+use tracing::{debug, error, info};
 
 use pallas::interop::utxorpc::spec::sync::any_chain_block::Chain;
 use pallas::interop::utxorpc::spec::sync::follow_tip_response::Action;
@@ -39,6 +39,33 @@ impl Worker {
                 if let Some(chain) = &block.chain {
                     match chain {
                         Chain::Cardano(block) => {
+                            let finalize = match &block.header {
+                                Some(header) => {
+                                    let point = Point::Specific(header.slot, header.hash.to_vec());
+
+                                    let finalize = should_finalize(
+                                        &stage.finalize,
+                                        &stage.chain,
+                                        &point,
+                                        stage.block_count + 1,
+                                    );
+
+                                    if finalize == Finalize::BeforeBlock {
+                                        info!(
+                                            slot = header.slot,
+                                            "finalize condition reached, skipping block"
+                                        );
+                                        stage.finalized = true;
+                                        return Ok(());
+                                    }
+
+                                    stage.tip.apply(&point);
+
+                                    finalize
+                                }
+                                None => Finalize::No,
+                            };
+
                             if block.body.is_some() {
                                 let header = block.header.as_ref().unwrap();
 
@@ -54,6 +81,13 @@ impl Worker {
                                     stage.chain_tip.set(header.slot as i64);
                                 }
                             }
+
+                            stage.block_count += 1;
+
+                            if finalize == Finalize::AfterBlock {
+                                info!("finalize condition reached");
+                                stage.finalized = true;
+                            }
                         }
                     }
                 }
@@ -62,6 +96,11 @@ impl Worker {
                 if let Some(chain) = &block.chain {
                     match chain {
                         Chain::Cardano(block) => {
+                            if let Some(header) = &block.header {
+                                let point = Point::Specific(header.slot, header.hash.to_vec());
+                                stage.tip.undo(&point);
+                            }
+
                             if block.body.is_some() {
                                 let header = block.header.as_ref().unwrap();
 
@@ -82,9 +121,13 @@ impl Worker {
                 }
             }
             Action::Reset(reset) => {
+                let point = Point::new(reset.index, reset.hash.to_vec());
+
+                stage.tip.rollback(&point);
+
                 stage
                     .output
-                    .send(ChainEvent::Reset(Point::new(reset.index, reset.hash.to_vec())).into())
+                    .send(ChainEvent::Reset(point).into())
                     .await
                     .or_panic()?;
 
@@ -185,16 +228,38 @@ impl gasket::framework::Worker<Stage> for Worker {
         })
     }
 
-    async fn schedule(&mut self, _: &mut Stage) -> Result<WorkSchedule<Vec<Action>>, WorkerError> {
+    async fn schedule(
+        &mut self,
+        stage: &mut Stage,
+    ) -> Result<WorkSchedule<Vec<Action>>, WorkerError> {
+        if stage.finalized {
+            return Ok(WorkSchedule::Done);
+        }
+
+        if stage.pause.hold().await {
+            return Ok(WorkSchedule::Idle);
+        }
+
         if self.intersect.is_some() {
             return self.next_dump_history().await;
         }
 
+        // the history is exhausted, from here on the blocks come from the tip
+        if stage.finalize.as_ref().is_some_and(|x| x.until_tip()) {
+            info!("finalizing at the tip of the chain");
+            stage.finalized = true;
+            return Ok(WorkSchedule::Done);
+        }
+
         self.next_stream().await
     }
 
     async fn execute(&mut self, unit: &Vec<Action>, stage: &mut Stage) -> Result<(), WorkerError> {
         for action in unit {
+            if stage.finalized {
+                break;
+            }
+
             self.process_next(stage, action).await.or_retry()?;
         }
 
@@ -208,6 +273,12 @@ pub struct Stage {
     config: Config,
     breadcrumbs: Breadcrumbs,
     intersect: IntersectConfig,
+    chain: GenesisValues,
+    finalize: Option<FinalizeConfig>,
+    block_count: u64,
+    finalized: bool,
+    pause: PauseSwitch,
+    tip: ChainTip,
 
     pub output: SourceOutputPort,
 
@@ -233,6 +304,12 @@ impl Config {
             config: self,
             breadcrumbs: ctx.breadcrumbs.clone(),
             intersect: ctx.intersect.clone(),
+            chain: ctx.chain.clone().into(),
+            finalize: ctx.finalize.clone(),
+            block_count: 0,
+            finalized: false,
+            pause: ctx.pause.clone(),
+            tip: ctx.tip.clone(),
             output: Default::default(),
             ops_count: Default::default(),
             chain_tip: Default::default(),
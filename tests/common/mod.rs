@@ -0,0 +1,114 @@
+//! A fake node that serves a fixed chain through a node-to-client socket
+
+use pallas::codec::minicbor;
+use pallas::codec::utils::{KeyValuePairs, MaybeIndefArray};
+use pallas::ledger::primitives::conway;
+use pallas::ledger::traverse::MultiEraBlock;
+use pallas::network::facades::NodeServer;
+use pallas::network::miniprotocols::chainsync::{BlockContent, ClientRequest, Tip};
+use pallas::network::miniprotocols::Point;
+use std::path::PathBuf;
+use tokio::net::UnixListener;
+use tokio::runtime::Runtime;
+
+/// Network magic of the mainnet, the default chain of a pipeline
+pub const MAGIC: u64 = 764824073;
+
+/// Encodes an empty conway block for the slot
+pub fn block(slot: u64) -> Vec<u8> {
+    let header_body = conway::HeaderBody {
+        block_number: slot,
+        slot,
+        prev_hash: None,
+        issuer_vkey: vec![0x11; 32].into(),
+        vrf_vkey: vec![0x22; 32].into(),
+        vrf_result: conway::VrfCert(vec![].into(), vec![].into()),
+        block_body_size: 0,
+        block_body_hash: [0; 32].into(),
+        operational_cert: conway::OperationalCert {
+            operational_cert_hot_vkey: vec![0x33; 32].into(),
+            operational_cert_sequence_number: 0,
+            operational_cert_kes_period: 0,
+            operational_cert_sigma: vec![0x44; 64].into(),
+        },
+        protocol_version: (9, 0),
+    };
+
+    let block = conway::Block {
+        header: conway::Header {
+            header_body,
+            body_signature: vec![0x55; 64].into(),
+        },
+        transaction_bodies: MaybeIndefArray::Def(vec![]),
+        transaction_witness_sets: MaybeIndefArray::Def(vec![]),
+        auxiliary_data_set: KeyValuePairs::from(vec![]),
+        invalid_transactions: None,
+    };
+
+    // blocks are wrapped along with the tag of their era
+    minicbor::to_vec((7u16, block)).unwrap()
+}
+
+pub fn point(cbor: &[u8]) -> Point {
+    let block = MultiEraBlock::decode(cbor).unwrap();
+    Point::Specific(block.slot(), block.hash().to_vec())
+}
+
+/// A node that serves the blocks once, to a single client
+pub struct Node {
+    _runtime: Runtime,
+    pub socket: PathBuf,
+    _dir: tempfile::TempDir,
+}
+
+impl Node {
+    pub fn spawn(blocks: Vec<Vec<u8>>) -> Self {
+        let runtime = Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("node.socket");
+
+        let listener = runtime.block_on(async { UnixListener::bind(&socket).unwrap() });
+
+        runtime.spawn(async move {
+            let mut server = NodeServer::accept(&listener, MAGIC).await.unwrap();
+            let chainsync = server.chainsync();
+
+            let tip = Tip(point(blocks.last().unwrap()), blocks.len() as u64);
+            let mut blocks = blocks.into_iter();
+
+            while let Ok(Some(request)) = chainsync.recv_while_idle().await {
+                let sent = match request {
+                    ClientRequest::Intersect(_) => {
+                        chainsync
+                            .send_intersect_found(Point::Origin, tip.clone())
+                            .await
+                    }
+                    ClientRequest::RequestNext => match blocks.next() {
+                        Some(x) => {
+                            chainsync
+                                .send_roll_forward(BlockContent(x), tip.clone())
+                                .await
+                        }
+                        // the client waits here until it goes away
+                        None => chainsync.send_await_reply().await,
+                    },
+                };
+
+                if sent.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            _runtime: runtime,
+            socket,
+            _dir: dir,
+        }
+    }
+
+    pub fn source(&self) -> oura::sources::Config {
+        let config = serde_json::json!({ "type": "N2C", "socket_path": self.socket });
+        serde_json::from_value(config).unwrap()
+    }
+}
@@ -0,0 +1,190 @@
+use oura::framework::*;
+use oura::pipeline::Builder;
+use pallas::network::miniprotocols::Point;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+
+/// Drops the blocks of odd slots
+struct EvenSlots;
+
+#[async_trait::async_trait(?Send)]
+impl oura::filters::custom::Filter for EvenSlots {
+    async fn apply(&mut self, event: ChainEvent) -> Result<Vec<ChainEvent>, Error> {
+        match event.point().slot_or_default() % 2 {
+            0 => Ok(vec![event]),
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<u64>>>, Duration);
+
+impl Collect {
+    fn slow(delay: Duration) -> Self {
+        Self(Default::default(), delay)
+    }
+
+    fn slots(&self) -> Vec<u64> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl oura::sinks::custom::Sink for Collect {
+    async fn apply(&mut self, event: &ChainEvent) -> Result<(), Error> {
+        std::thread::sleep(self.1);
+
+        if let ChainEvent::Apply(point, Record::CborBlock(_)) = event {
+            self.0.lock().unwrap().push(point.slot_or_default());
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn runs_custom_stages() {
+    let blocks: Vec<_> = (10..20).map(common::block).collect();
+    let node = common::Node::spawn(blocks.clone());
+
+    let finalize = serde_json::from_str(r#"{ "max_block_quantity": 6 }"#).unwrap();
+    let sink = Collect::default();
+
+    let pipeline = Builder::new()
+        .intersect(IntersectConfig::Origin)
+        .finalize(finalize)
+        .source(node.source())
+        .custom_filter(EvenSlots)
+        .custom_sink(sink.clone())
+        .build()
+        .unwrap();
+
+    assert!(pipeline.wait(Duration::from_secs(30)));
+
+    assert_eq!(*sink.0.lock().unwrap(), vec![10, 12, 14]);
+
+    // the cursor follows the sink, the dropped block 15 isn't acked
+    let breadcrumbs = pipeline.breadcrumbs("default").unwrap();
+    assert_eq!(
+        breadcrumbs.points().first(),
+        Some(&common::point(&blocks[4]))
+    );
+
+    drop(node);
+}
+
+#[test]
+fn finalizes_at_the_end_of_an_epoch() {
+    // first slot of epoch 209 on mainnet
+    let boundary = 4492800 + 432000;
+
+    let blocks: Vec<_> = (boundary - 3..boundary + 3).map(common::block).collect();
+    let node = common::Node::spawn(blocks);
+
+    let finalize = serde_json::from_str(r#"{ "until_epoch": 208 }"#).unwrap();
+    let sink = Collect::default();
+
+    let pipeline = Builder::new()
+        .intersect(IntersectConfig::Origin)
+        .finalize(finalize)
+        .source(node.source())
+        .custom_sink(sink.clone())
+        .build()
+        .unwrap();
+
+    assert!(pipeline.wait(Duration::from_secs(30)));
+
+    assert_eq!(sink.slots(), vec![boundary - 3, boundary - 2, boundary - 1]);
+
+    drop(node);
+}
+
+#[test]
+fn replaces_sinks_of_running_pipeline() {
+    let blocks: Vec<_> = (0..300).map(common::block).collect();
+    let node = common::Node::spawn(blocks.clone());
+
+    let finalize = serde_json::from_str(r#"{ "max_block_quantity": 300 }"#).unwrap();
+    let first = Collect::slow(Duration::from_millis(2));
+
+    let pipeline = Builder::new()
+        .intersect(IntersectConfig::Origin)
+        .finalize(finalize)
+        .source(node.source())
+        .custom_sink(first.clone())
+        .build()
+        .unwrap();
+
+    while first.slots().is_empty() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let detached = pipeline.detach("default", Duration::from_secs(30)).unwrap();
+
+    let second = Collect::default();
+
+    detached
+        .attach(Builder::new().custom_sink(second.clone()))
+        .map_err(|(err, _)| err)
+        .unwrap();
+
+    assert!(pipeline.wait(Duration::from_secs(30)));
+
+    // the new sink picks up right where the previous one stopped
+    assert!(!second.slots().is_empty());
+    let slots: Vec<_> = first.slots().into_iter().chain(second.slots()).collect();
+    assert_eq!(slots, (0..300).collect::<Vec<_>>());
+
+    // the cursor kept running and follows the new sink
+    let breadcrumbs = pipeline.breadcrumbs("default").unwrap();
+    assert_eq!(
+        breadcrumbs.points().first(),
+        Some(&common::point(blocks.last().unwrap()))
+    );
+
+    drop(node);
+}
+
+#[test]
+fn writes_file_cursor_after_draining() {
+    let blocks: Vec<_> = (0..300).map(common::block).collect();
+    let node = common::Node::spawn(blocks.clone());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cursor.json");
+
+    // the cursor won't flush on its own before the pipeline stops
+    let cursor = serde_json::json!({ "type": "File", "path": path, "flush_interval": 3600 });
+    let sink = Collect::slow(Duration::from_millis(2));
+
+    let pipeline = Builder::new()
+        .intersect(IntersectConfig::Origin)
+        .source(node.source())
+        .custom_sink(sink.clone())
+        .cursor(serde_json::from_value(cursor).unwrap())
+        .build()
+        .unwrap();
+
+    while sink.slots().is_empty() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(pipeline.drain(Duration::from_secs(30)));
+
+    let last = *sink.slots().last().unwrap();
+    assert!(last < 299);
+
+    let data: Vec<(u64, String)> =
+        serde_json::from_reader(std::fs::File::open(&path).unwrap()).unwrap();
+
+    let Point::Specific(slot, hash) = common::point(&blocks[last as usize]) else {
+        unreachable!()
+    };
+
+    assert_eq!(data.first(), Some(&(slot, hex::encode(hash))));
+
+    drop(node);
+}
@@ -0,0 +1,65 @@
+use futures::StreamExt;
+use oura::framework::*;
+use oura::pipeline::Builder;
+use std::time::{Duration, Instant};
+
+mod common;
+
+/// Waits until the cursor of the pipeline points to the expected block
+fn wait_cursor(pipeline: &oura::pipeline::Pipeline, expected: &[u8]) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let expected = common::point(expected);
+
+    while Instant::now() < deadline {
+        let breadcrumbs = pipeline.breadcrumbs("default").unwrap();
+
+        if breadcrumbs.points().first() == Some(&expected) {
+            return true;
+        }
+
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    false
+}
+
+#[test]
+fn streams_and_acks_events() {
+    let blocks: Vec<_> = (10..20).map(common::block).collect();
+    let node = common::Node::spawn(blocks.clone());
+
+    let finalize = serde_json::from_str(r#"{ "max_block_quantity": 4 }"#).unwrap();
+
+    let (mut events, mut acker) = Builder::new()
+        .intersect(IntersectConfig::Origin)
+        .finalize(finalize)
+        .source(node.source())
+        .stream()
+        .unwrap();
+
+    futures::executor::block_on(async {
+        let mut slots = vec![];
+
+        while let Some(event) = events.next().await {
+            slots.push(event.point().slot_or_default());
+
+            // the cursor only moves as far as the acked events
+            if slots.len() <= 2 {
+                acker.ack(event.point().clone()).await.unwrap();
+            }
+        }
+
+        assert_eq!(slots, vec![10, 11, 12, 13]);
+    });
+
+    assert!(wait_cursor(events.pipeline(), &blocks[1]));
+
+    futures::executor::block_on(acker.ack(common::point(&blocks[3]))).unwrap();
+    assert!(wait_cursor(events.pipeline(), &blocks[3]));
+
+    // the cursor ends once every acker is gone
+    drop(acker);
+    assert!(events.pipeline().wait(Duration::from_secs(30)));
+
+    drop(node);
+}